@@ -0,0 +1,87 @@
+//! Run with `cargo bench`. Evaluation uses 1000 windows and 100 rules, roughly a
+//! worst-case desktop.
+
+use aerospace_rules::condition::Condition;
+use aerospace_rules::config::Config;
+use aerospace_rules::rules::{self, Trigger};
+use aerospace_rules::testing::window;
+use aerospace_rules::{Response, WindowInfo};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const APPS: [&str; 5] = ["Slack", "Safari", "Ghostty", "IntelliJ IDEA", "Mail"];
+
+fn windows(count: u32) -> Vec<WindowInfo> {
+    (0..count)
+        .map(|id| {
+            window(APPS[id as usize % APPS.len()])
+                .id(id)
+                .title(&format!("Window {id}"))
+                .workspace(&(id % 10).to_string())
+                .build()
+        })
+        .collect()
+}
+
+/// `count` rules, alternating between app and title conditions.
+fn config_source(count: usize) -> String {
+    (0..count)
+        .map(|index| {
+            let condition = if index % 2 == 0 {
+                format!("app-name = '{}'", APPS[index % APPS.len()])
+            } else {
+                format!("window-title contains 'Window {index}'")
+            };
+            format!(
+                "[[rules]]\nname = \"Rule {index}\"\ntype = \"window\"\ncondition = \"{condition}\"\naction = \"move-to-workspace {}\"\n\n",
+                index % 10
+            )
+        })
+        .collect()
+}
+
+fn parsing(c: &mut Criterion) {
+    c.bench_function("parse app-name condition", |b| {
+        b.iter(|| Condition::parse(black_box("app-name = 'IntelliJ IDEA'")))
+    });
+    c.bench_function("parse numeric condition", |b| {
+        b.iter(|| Condition::parse(black_box("window-width > 1200")))
+    });
+
+    let source = config_source(100);
+    c.bench_function("parse config with 100 rules", |b| {
+        b.iter(|| toml::from_str::<Config>(black_box(&source)).unwrap())
+    });
+}
+
+fn evaluation(c: &mut Criterion) {
+    let windows = windows(1000);
+    let config: Config = toml::from_str(&config_source(100)).unwrap();
+
+    c.bench_function("plan 100 rules for 1000 windows", |b| {
+        b.iter(|| rules::plan(black_box(&windows), &config, Trigger::Windows).unwrap())
+    });
+    c.bench_function("plan 100 rules for 1 window", |b| {
+        b.iter(|| rules::plan(black_box(&windows[..1]), &config, Trigger::Windows).unwrap())
+    });
+
+    let index = rules::RuleIndex::new(&config);
+    let context = rules::EvalContext::new(Trigger::TitleChanged);
+    c.bench_function("plan 100 indexed title rules for 1 window", |b| {
+        b.iter(|| rules::plan_indexed(black_box(&windows[..1]), &config, &index, &context).unwrap())
+    });
+}
+
+fn serialization(c: &mut Criterion) {
+    let response = Response::Windows(windows(1000));
+    let json = serde_json::to_string(&response).unwrap();
+
+    c.bench_function("serialize 1000 windows", |b| {
+        b.iter(|| serde_json::to_string(black_box(&response)).unwrap())
+    });
+    c.bench_function("deserialize 1000 windows", |b| {
+        b.iter(|| serde_json::from_str::<Response>(black_box(&json)).unwrap())
+    });
+}
+
+criterion_group!(benches, parsing, evaluation, serialization);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+//! `cargo fuzz run condition`: conditions come from config files users write by hand,
+//! so any string must either parse or fail cleanly, and parsed conditions must
+//! evaluate without panicking.
+
+#![no_main]
+
+use aerospace_rules::condition::Condition;
+use aerospace_rules::testing::window;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(condition) = Condition::parse(source) {
+        let window = window("Slack").title("general").size(1200.0, 800.0).build();
+        let _ = condition.matches(&window);
+        let _ = condition.clauses(&window);
+        // What a condition prints must parse again
+        assert!(Condition::parse(&condition.to_string()).is_ok());
+    }
+});
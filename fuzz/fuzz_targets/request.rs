@@ -0,0 +1,14 @@
+//! `cargo fuzz run request`: everything a client sends to the socket goes through
+//! this decoder first.
+
+#![no_main]
+
+use aerospace_rules::Request;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(request) = serde_json::from_slice::<Request>(data) {
+        // Whatever decodes must encode again
+        serde_json::to_vec(&request).unwrap();
+    }
+});
@@ -0,0 +1,36 @@
+/*
+ * C API of the aerospace-rules engine, built with `cargo build --release --features ffi`
+ * into libaerospace_rules.dylib. See src/ffi.rs for details.
+ *
+ * Arguments are NUL-terminated UTF-8 strings. Every function returns a JSON document,
+ * {"ok": <result>} or {"error": "<message>"}, that must be freed with ar_string_free.
+ */
+
+#ifndef AEROSPACE_RULES_H
+#define AEROSPACE_RULES_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Parses a TOML config and returns it as JSON. */
+char *ar_parse_config(const char *config);
+
+/* Checks a TOML config; the result is an array of diagnostics, empty if it's valid. */
+char *ar_validate_config(const char *config);
+
+/*
+ * Evaluates the rules of a TOML config against a JSON array of windows and returns
+ * the actions they call for as dry-run results. workspace may be NULL; otherwise the
+ * windows are that workspace's contents.
+ */
+char *ar_evaluate(const char *config, const char *windows, const char *workspace);
+
+/* Frees a string returned by this library. NULL is ignored. */
+void ar_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* AEROSPACE_RULES_H */
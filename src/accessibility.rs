@@ -0,0 +1,407 @@
+//! Window geometry and titles from the macOS CoreGraphics and Accessibility APIs.
+//!
+//! The aerospace CLI reports neither window frames nor always-current titles. Frames and
+//! owning PIDs come from `CGWindowListCopyWindowInfo`, titles from each app's AX window
+//! list. Titles need the Accessibility permission; without it they are left untouched.
+//!
+//! [`spawn_title_watcher`] additionally observes `AXTitleChanged` notifications so title
+//! changes are noticed as they happen rather than on the next poll.
+
+use crate::aerospace::{WindowFrame, WindowInfo};
+use std::collections::{HashMap, HashSet};
+use std::ffi::{c_char, c_void, CString};
+use std::thread::JoinHandle;
+
+type CFTypeRef = *const c_void;
+type CFStringRef = *const c_void;
+type CFArrayRef = *const c_void;
+type CFDictionaryRef = *const c_void;
+type AXUIElementRef = *const c_void;
+type AXObserverRef = *const c_void;
+type CFRunLoopRef = *const c_void;
+type CFRunLoopSourceRef = *const c_void;
+type AXObserverCallback = unsafe extern "C" fn(
+    observer: AXObserverRef,
+    element: AXUIElementRef,
+    notification: CFStringRef,
+    refcon: *mut c_void,
+);
+type AXError = i32;
+type CFIndex = isize;
+type Boolean = u8;
+
+#[repr(C)]
+#[derive(Default)]
+struct CGPoint {
+    x: f64,
+    y: f64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CGSize {
+    width: f64,
+    height: f64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CGRect {
+    origin: CGPoint,
+    size: CGSize,
+}
+
+const CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+const CF_NUMBER_SINT64_TYPE: CFIndex = 4;
+const CG_WINDOW_LIST_OPTION_ALL: u32 = 0;
+const CG_WINDOW_LIST_EXCLUDE_DESKTOP_ELEMENTS: u32 = 1 << 4;
+const CG_NULL_WINDOW_ID: u32 = 0;
+const AX_ERROR_SUCCESS: AXError = 0;
+/// How often the watcher picks up newly launched and terminated applications.
+const OBSERVER_RESCAN_SECONDS: f64 = 2.0;
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFStringCreateWithCString(
+        alloc: *const c_void,
+        c_str: *const c_char,
+        encoding: u32,
+    ) -> CFStringRef;
+    fn CFStringGetLength(string: CFStringRef) -> CFIndex;
+    fn CFStringGetMaximumSizeForEncoding(length: CFIndex, encoding: u32) -> CFIndex;
+    fn CFStringGetCString(
+        string: CFStringRef,
+        buffer: *mut c_char,
+        buffer_size: CFIndex,
+        encoding: u32,
+    ) -> Boolean;
+    fn CFArrayGetCount(array: CFArrayRef) -> CFIndex;
+    fn CFArrayGetValueAtIndex(array: CFArrayRef, index: CFIndex) -> *const c_void;
+    fn CFDictionaryGetValue(dict: CFDictionaryRef, key: *const c_void) -> *const c_void;
+    fn CFNumberGetValue(number: CFTypeRef, number_type: CFIndex, value: *mut c_void) -> Boolean;
+    fn CFRelease(cf: CFTypeRef);
+
+    static kCFRunLoopDefaultMode: CFStringRef;
+    fn CFRunLoopGetCurrent() -> CFRunLoopRef;
+    fn CFRunLoopAddSource(run_loop: CFRunLoopRef, source: CFRunLoopSourceRef, mode: CFStringRef);
+    fn CFRunLoopRemoveSource(run_loop: CFRunLoopRef, source: CFRunLoopSourceRef, mode: CFStringRef);
+    fn CFRunLoopRunInMode(
+        mode: CFStringRef,
+        seconds: f64,
+        return_after_source_handled: Boolean,
+    ) -> i32;
+}
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGWindowListCopyWindowInfo(option: u32, relative_to_window: u32) -> CFArrayRef;
+    fn CGRectMakeWithDictionaryRepresentation(dict: CFDictionaryRef, rect: *mut CGRect) -> bool;
+}
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrusted() -> Boolean;
+    fn AXUIElementCreateApplication(pid: i32) -> AXUIElementRef;
+    fn AXUIElementCopyAttributeValue(
+        element: AXUIElementRef,
+        attribute: CFStringRef,
+        value: *mut CFTypeRef,
+    ) -> AXError;
+    // Private but stable; the same call aerospace uses to map AX elements to window ids.
+    fn _AXUIElementGetWindow(element: AXUIElementRef, window_id: *mut u32) -> AXError;
+    fn AXObserverCreate(
+        pid: i32,
+        callback: AXObserverCallback,
+        observer: *mut AXObserverRef,
+    ) -> AXError;
+    fn AXObserverAddNotification(
+        observer: AXObserverRef,
+        element: AXUIElementRef,
+        notification: CFStringRef,
+        refcon: *mut c_void,
+    ) -> AXError;
+    fn AXObserverGetRunLoopSource(observer: AXObserverRef) -> CFRunLoopSourceRef;
+}
+
+/// Releases a CoreFoundation object obtained under the create/copy rule.
+struct Owned(CFTypeRef);
+
+impl Owned {
+    fn new(ptr: CFTypeRef) -> Option<Self> {
+        (!ptr.is_null()).then_some(Self(ptr))
+    }
+}
+
+impl Drop for Owned {
+    fn drop(&mut self) {
+        unsafe { CFRelease(self.0) }
+    }
+}
+
+fn cf_string(value: &str) -> Option<Owned> {
+    let c_string = CString::new(value).ok()?;
+    Owned::new(unsafe {
+        CFStringCreateWithCString(std::ptr::null(), c_string.as_ptr(), CF_STRING_ENCODING_UTF8)
+    })
+}
+
+unsafe fn string_from_cf(string: CFStringRef) -> Option<String> {
+    let capacity =
+        CFStringGetMaximumSizeForEncoding(CFStringGetLength(string), CF_STRING_ENCODING_UTF8) + 1;
+    let mut buffer = vec![0u8; capacity as usize];
+    if CFStringGetCString(
+        string,
+        buffer.as_mut_ptr() as *mut c_char,
+        capacity,
+        CF_STRING_ENCODING_UTF8,
+    ) == 0
+    {
+        return None;
+    }
+    let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    buffer.truncate(end);
+    String::from_utf8(buffer).ok()
+}
+
+unsafe fn number_from_dict(dict: CFDictionaryRef, key: &Owned) -> Option<i64> {
+    let number = CFDictionaryGetValue(dict, key.0);
+    if number.is_null() {
+        return None;
+    }
+    let mut value: i64 = 0;
+    (CFNumberGetValue(
+        number,
+        CF_NUMBER_SINT64_TYPE,
+        &mut value as *mut i64 as *mut c_void,
+    ) != 0)
+        .then_some(value)
+}
+
+/// Frames and owning PIDs of every on-screen and off-screen window, keyed by window id.
+fn window_list() -> HashMap<u32, (i32, WindowFrame)> {
+    let mut windows = HashMap::new();
+    let (Some(number_key), Some(pid_key), Some(bounds_key)) = (
+        cf_string("kCGWindowNumber"),
+        cf_string("kCGWindowOwnerPID"),
+        cf_string("kCGWindowBounds"),
+    ) else {
+        return windows;
+    };
+
+    unsafe {
+        let Some(list) = Owned::new(CGWindowListCopyWindowInfo(
+            CG_WINDOW_LIST_OPTION_ALL | CG_WINDOW_LIST_EXCLUDE_DESKTOP_ELEMENTS,
+            CG_NULL_WINDOW_ID,
+        )) else {
+            return windows;
+        };
+
+        for index in 0..CFArrayGetCount(list.0) {
+            let entry = CFArrayGetValueAtIndex(list.0, index);
+            let (Some(window_id), Some(pid)) = (
+                number_from_dict(entry, &number_key),
+                number_from_dict(entry, &pid_key),
+            ) else {
+                continue;
+            };
+
+            let bounds = CFDictionaryGetValue(entry, bounds_key.0);
+            let mut rect = CGRect::default();
+            if bounds.is_null() || !CGRectMakeWithDictionaryRepresentation(bounds, &mut rect) {
+                continue;
+            }
+
+            windows.insert(
+                window_id as u32,
+                (
+                    pid as i32,
+                    WindowFrame {
+                        x: rect.origin.x,
+                        y: rect.origin.y,
+                        width: rect.size.width,
+                        height: rect.size.height,
+                    },
+                ),
+            );
+        }
+    }
+
+    windows
+}
+
+unsafe fn element_title(element: AXUIElementRef, title_attr: &Owned) -> Option<String> {
+    let mut title: CFTypeRef = std::ptr::null();
+    if AXUIElementCopyAttributeValue(element, title_attr.0, &mut title) != AX_ERROR_SUCCESS {
+        return None;
+    }
+    let title = Owned::new(title)?;
+    string_from_cf(title.0)
+}
+
+/// Current AX titles of an application's windows, keyed by window id.
+pub(crate) fn window_titles(pid: i32) -> HashMap<u32, String> {
+    let mut titles = HashMap::new();
+    let (Some(windows_attr), Some(title_attr)) = (cf_string("AXWindows"), cf_string("AXTitle"))
+    else {
+        return titles;
+    };
+
+    unsafe {
+        let Some(app) = Owned::new(AXUIElementCreateApplication(pid)) else {
+            return titles;
+        };
+
+        let mut ax_windows: CFTypeRef = std::ptr::null();
+        if AXUIElementCopyAttributeValue(app.0, windows_attr.0, &mut ax_windows) != AX_ERROR_SUCCESS
+        {
+            return titles;
+        }
+        let Some(ax_windows) = Owned::new(ax_windows) else {
+            return titles;
+        };
+
+        for index in 0..CFArrayGetCount(ax_windows.0) {
+            let element = CFArrayGetValueAtIndex(ax_windows.0, index);
+            let mut window_id = 0;
+            if _AXUIElementGetWindow(element, &mut window_id) != AX_ERROR_SUCCESS {
+                continue;
+            }
+
+            if let Some(title) = element_title(element, &title_attr) {
+                titles.insert(window_id, title);
+            }
+        }
+    }
+
+    titles
+}
+
+pub fn is_trusted() -> bool {
+    unsafe { AXIsProcessTrusted() != 0 }
+}
+
+/// Adds frames to `windows` and replaces their titles with the live AX titles.
+pub fn enrich_windows(windows: &mut [WindowInfo]) {
+    if windows.is_empty() {
+        return;
+    }
+
+    let native_windows = window_list();
+    let trusted = is_trusted();
+    let mut titles_by_pid: HashMap<i32, HashMap<u32, String>> = HashMap::new();
+
+    for window in windows {
+        let Some((pid, frame)) = native_windows.get(&window.window_id) else {
+            continue;
+        };
+        window.frame = Some(*frame);
+
+        if trusted {
+            let titles = titles_by_pid
+                .entry(*pid)
+                .or_insert_with(|| window_titles(*pid));
+            if let Some(title) = titles.get(&window.window_id) {
+                window.window_title = title.clone();
+            }
+        }
+    }
+}
+
+/// A window whose title changed, as reported by [`spawn_title_watcher`].
+#[derive(Debug, Clone)]
+pub struct TitleChange {
+    pub window_id: u32,
+    pub title: String,
+}
+
+type TitleCallback = Box<dyn Fn(TitleChange) + Send>;
+
+unsafe extern "C" fn title_changed(
+    _observer: AXObserverRef,
+    element: AXUIElementRef,
+    _notification: CFStringRef,
+    refcon: *mut c_void,
+) {
+    let on_change = &*(refcon as *const TitleCallback);
+    let mut window_id = 0;
+    if _AXUIElementGetWindow(element, &mut window_id) != AX_ERROR_SUCCESS {
+        return;
+    }
+    let Some(title_attr) = cf_string("AXTitle") else {
+        return;
+    };
+    if let Some(title) = element_title(element, &title_attr) {
+        on_change(TitleChange { window_id, title });
+    }
+}
+
+/// Observes title changes of every application that owns a window, calling `on_change`
+/// from a dedicated run-loop thread. Returns `None` without the Accessibility permission.
+pub fn spawn_title_watcher(
+    on_change: impl Fn(TitleChange) + Send + 'static,
+) -> Option<JoinHandle<()>> {
+    if !is_trusted() {
+        return None;
+    }
+
+    Some(std::thread::spawn(move || {
+        // Boxed twice so the callback has a thin pointer to pass as refcon; it lives
+        // as long as this thread, which never returns.
+        let on_change: Box<TitleCallback> = Box::new(Box::new(on_change));
+        let refcon = &*on_change as *const TitleCallback as *mut c_void;
+        let Some(notification) = cf_string("AXTitleChanged") else {
+            return;
+        };
+        let mut observers: HashMap<i32, Owned> = HashMap::new();
+
+        unsafe {
+            let run_loop = CFRunLoopGetCurrent();
+            loop {
+                let pids: HashSet<i32> = window_list().values().map(|(pid, _)| *pid).collect();
+
+                observers.retain(|pid, observer| {
+                    let alive = pids.contains(pid);
+                    if !alive {
+                        CFRunLoopRemoveSource(
+                            run_loop,
+                            AXObserverGetRunLoopSource(observer.0),
+                            kCFRunLoopDefaultMode,
+                        );
+                    }
+                    alive
+                });
+
+                for pid in pids {
+                    if observers.contains_key(&pid) {
+                        continue;
+                    }
+
+                    let mut observer: AXObserverRef = std::ptr::null();
+                    if AXObserverCreate(pid, title_changed, &mut observer) != AX_ERROR_SUCCESS {
+                        continue;
+                    }
+                    let (Some(observer), Some(app)) = (
+                        Owned::new(observer),
+                        Owned::new(AXUIElementCreateApplication(pid)),
+                    ) else {
+                        continue;
+                    };
+                    if AXObserverAddNotification(observer.0, app.0, notification.0, refcon)
+                        != AX_ERROR_SUCCESS
+                    {
+                        continue;
+                    }
+
+                    CFRunLoopAddSource(
+                        run_loop,
+                        AXObserverGetRunLoopSource(observer.0),
+                        kCFRunLoopDefaultMode,
+                    );
+                    observers.insert(pid, observer);
+                }
+
+                CFRunLoopRunInMode(kCFRunLoopDefaultMode, OBSERVER_RESCAN_SECONDS, 0);
+            }
+        }
+    }))
+}
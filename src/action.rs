@@ -0,0 +1,87 @@
+use crate::compat;
+use std::fmt;
+
+/// What a window rule does to the windows it matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Maximize,
+    MoveToWorkspace(String),
+    /// Runs the named macOS Shortcut through `shortcuts run`.
+    Shortcut(String),
+    /// Runs an AppleScript snippet through `osascript`.
+    AppleScript(String),
+    /// Runs a command while the rules are planned and keeps what it prints, trimmed, as
+    /// the window's `captured` value for the rules after it in the same evaluation. Dry
+    /// runs and other evaluations outside the service capture nothing.
+    Capture(String),
+}
+
+impl Action {
+    /// Parses `maximize`, `move-to-workspace <workspace>`, `shortcut '<name>'`,
+    /// `applescript '<snippet>'` or `capture <command>`.
+    pub fn parse(action: &str) -> Result<Self, String> {
+        if let Some(action) = compat::action(action) {
+            return Self::parse(&action);
+        }
+        let action = action.trim();
+        if action == "maximize" {
+            return Ok(Action::Maximize);
+        }
+        if let Some(workspace) = action.strip_prefix("move-to-workspace ") {
+            let workspace = workspace.trim();
+            if workspace.is_empty() {
+                return Err("move-to-workspace needs a workspace".to_string());
+            }
+            return Ok(Action::MoveToWorkspace(workspace.to_string()));
+        }
+        if let Some(name) = action.strip_prefix("shortcut ") {
+            return match unquote(name) {
+                "" => Err("shortcut needs the name of a shortcut".to_string()),
+                name => Ok(Action::Shortcut(name.to_string())),
+            };
+        }
+        if let Some(snippet) = action.strip_prefix("applescript ") {
+            return match unquote(snippet) {
+                "" => Err("applescript needs a script".to_string()),
+                snippet => Ok(Action::AppleScript(snippet.to_string())),
+            };
+        }
+        if let Some(command) = action.strip_prefix("capture ") {
+            return match command.trim() {
+                "" => Err("capture needs a command".to_string()),
+                command => Ok(Action::Capture(command.to_string())),
+            };
+        }
+        Err(format!("Unknown action: {action}"))
+    }
+}
+
+/// `value` without the quotes around it, if it is quoted.
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['\'', '"']
+        .into_iter()
+        .find_map(|quote| value.strip_prefix(quote)?.strip_suffix(quote))
+        .unwrap_or(value)
+}
+
+/// `value` in single quotes, or double quotes if it contains a single quote.
+fn quote(value: &str) -> String {
+    if value.contains('\'') {
+        format!("\"{value}\"")
+    } else {
+        format!("'{value}'")
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Maximize => f.write_str("maximize"),
+            Action::MoveToWorkspace(workspace) => write!(f, "move-to-workspace {workspace}"),
+            Action::Shortcut(name) => write!(f, "shortcut {}", quote(name)),
+            Action::AppleScript(snippet) => write!(f, "applescript {}", quote(snippet)),
+            Action::Capture(command) => write!(f, "capture {command}"),
+        }
+    }
+}
@@ -1,8 +1,172 @@
-use serde::Deserialize;
+use crate::backend::MoveResult;
+use crate::config;
+use crate::metrics;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
-use std::process::Command;
+use std::fmt;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
 
-#[derive(serde::Serialize, Deserialize, Debug, Clone)]
+/// Environment variable overriding the aerospace binary, taking precedence over the config.
+pub const BINARY_ENV_VAR: &str = "AEROSPACE_RULES_AEROSPACE_BIN";
+
+/// First release whose `list-*` commands accept `--json`.
+const JSON_OUTPUT_VERSION: AerospaceVersion = AerospaceVersion::new(0, 15, 0);
+
+/// How long a workspace's window list is reused before aerospace is queried again.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_millis(250);
+/// How often a running move is checked for having exited.
+const MOVE_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+static CONFIGURED_BINARY: RwLock<Option<PathBuf>> = RwLock::new(None);
+static DETECTED_VERSION: RwLock<Option<AerospaceVersion>> = RwLock::new(None);
+static CACHE_TTL: RwLock<Duration> = RwLock::new(DEFAULT_CACHE_TTL);
+static MOVE_LIMITS: RwLock<(usize, Duration)> = RwLock::new((
+    config::DEFAULT_PARALLEL_ACTIONS,
+    Duration::from_millis(config::DEFAULT_ACTION_TIMEOUT_MS),
+));
+static WINDOW_CACHE: Mutex<Vec<CachedWorkspace>> = Mutex::new(Vec::new());
+
+struct CachedWorkspace {
+    workspace: String,
+    fetched_at: Instant,
+    windows: Vec<WindowInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AerospaceVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl AerospaceVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Extracts the first `major.minor.patch` triple from `aerospace --version` output.
+    pub fn parse(output: &str) -> Option<Self> {
+        output.split_whitespace().find_map(|word| {
+            let core = word.split(['-', '+']).next()?;
+            let mut parts = core.split('.').map(|part| part.parse::<u32>().ok());
+            let version = Self::new(parts.next()??, parts.next()??, parts.next()??);
+            parts.next().is_none().then_some(version)
+        })
+    }
+}
+
+impl fmt::Display for AerospaceVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Sets the binary configured via `[service] aerospace_bin`; `None` falls back to PATH lookup.
+pub fn set_binary(path: Option<&str>) {
+    *CONFIGURED_BINARY.write().unwrap() = path.map(PathBuf::from);
+}
+
+pub fn binary() -> PathBuf {
+    if let Some(path) = std::env::var_os(BINARY_ENV_VAR).filter(|path| !path.is_empty()) {
+        return PathBuf::from(path);
+    }
+
+    CONFIGURED_BINARY
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("aerospace"))
+}
+
+/// Sets how long query results are reused; a zero TTL disables caching.
+pub fn set_cache_ttl(ttl: Duration) {
+    *CACHE_TTL.write().unwrap() = ttl;
+    if ttl.is_zero() {
+        invalidate_cache();
+    }
+}
+
+/// Sets how many moves [`move_windows`] runs at once and how long each may take.
+pub fn set_move_limits(parallelism: usize, timeout: Duration) {
+    *MOVE_LIMITS.write().unwrap() = (parallelism, timeout);
+}
+
+/// Drops all cached query results, e.g. after windows were moved.
+pub fn invalidate_cache() {
+    WINDOW_CACHE.lock().unwrap().clear();
+}
+
+fn cached_windows(workspace: &str) -> Option<Vec<WindowInfo>> {
+    let ttl = *CACHE_TTL.read().unwrap();
+    WINDOW_CACHE
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|entry| entry.workspace == workspace && entry.fetched_at.elapsed() < ttl)
+        .map(|entry| entry.windows.clone())
+}
+
+fn store_cached_windows(workspace: &str, windows: &[WindowInfo]) {
+    if CACHE_TTL.read().unwrap().is_zero() {
+        return;
+    }
+
+    let mut cache = WINDOW_CACHE.lock().unwrap();
+    cache.retain(|entry| entry.workspace != workspace);
+    cache.push(CachedWorkspace {
+        workspace: workspace.to_string(),
+        fetched_at: Instant::now(),
+        windows: windows.to_vec(),
+    });
+}
+
+/// Builds a `Command` for the resolved aerospace binary.
+pub fn command() -> Command {
+    Command::new(binary())
+}
+
+/// PID of the running AeroSpace app, which changes whenever it restarts.
+pub fn server_pid() -> Option<u32> {
+    let output = Command::new("pgrep")
+        .args(["-x", "AeroSpace"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Runs `aerospace --version` and remembers the result for feature gating.
+pub fn detect_version() -> Result<AerospaceVersion, Box<dyn Error>> {
+    let output = execute_command(&["--version"])?;
+    let version = AerospaceVersion::parse(&output)
+        .ok_or_else(|| format!("Unrecognized aerospace version output: {}", output.trim()))?;
+
+    *DETECTED_VERSION.write().unwrap() = Some(version);
+    Ok(version)
+}
+
+pub fn version() -> Option<AerospaceVersion> {
+    *DETECTED_VERSION.read().unwrap()
+}
+
+/// Whether the detected aerospace supports a feature introduced in `since`.
+/// An undetected version is assumed to be recent.
+fn supports(since: AerospaceVersion) -> bool {
+    version().is_none_or(|version| version >= since)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct WindowInfo {
     #[serde(rename = "app-name")]
     pub app_name: String,
@@ -11,6 +175,23 @@ pub struct WindowInfo {
     #[serde(rename = "window-title")]
     pub window_title: String,
     pub workspace: String,
+    /// Name of the monitor the window is on, when the window manager reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+    /// Window geometry in screen points, when a geometry provider is available.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame: Option<WindowFrame>,
+    /// Whether the window has keyboard focus.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub focused: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WindowFrame {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
 }
 
 #[derive(Deserialize)]
@@ -21,14 +202,23 @@ struct AerospaceWindow {
     window_id: u32,
     #[serde(rename = "window-title")]
     window_title: String,
+    #[serde(rename = "monitor-name", default)]
+    monitor_name: Option<String>,
 }
 
+/// What `list-windows --json` reports per window.
+const WINDOW_FORMAT: &str = "%{window-id}%{app-name}%{window-title}%{monitor-name}";
+
 fn execute_command(args: &[&str]) -> Result<String, Box<dyn Error>> {
-    let output = Command::new("aerospace").args(args).output()?;
+    let started = Instant::now();
+    let output = command().args(args).output();
+    metrics::record_aerospace_call(args.first().copied().unwrap_or_default(), started.elapsed());
+    let output = output?;
 
     if !output.status.success() {
         return Err(format!(
-            "aerospace list-workspaces failed: {}",
+            "aerospace {} failed: {}",
+            args.first().copied().unwrap_or_default(),
             String::from_utf8_lossy(&output.stderr)
         )
         .into());
@@ -37,13 +227,73 @@ fn execute_command(args: &[&str]) -> Result<String, Box<dyn Error>> {
     Ok(String::from_utf8(output.stdout)?)
 }
 
-fn list_workspaces() -> Result<Vec<String>, Box<dyn Error>> {
-    execute_command(&["list-workspaces", "--all"]).map(|s| {
-        s.lines()
-            .map(|line| line.trim().to_string())
-            .filter(|line| !line.is_empty())
-            .collect()
-    })
+/// The non-empty lines of a `list-*` command's output, trimmed.
+fn parse_names(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+pub fn list_workspaces() -> Result<Vec<String>, Box<dyn Error>> {
+    execute_command(&["list-workspaces", "--all"]).map(|s| parse_names(&s))
+}
+
+pub fn focused_workspace() -> Result<String, Box<dyn Error>> {
+    let output = execute_command(&["list-workspaces", "--focused"])?;
+    parse_names(&output)
+        .into_iter()
+        .next()
+        .ok_or_else(|| "aerospace reported no focused workspace".into())
+}
+
+pub fn list_monitors() -> Result<Vec<String>, Box<dyn Error>> {
+    execute_command(&["list-monitors", "--format", "%{monitor-name}"]).map(|s| parse_names(&s))
+}
+
+/// ID of the focused window, if any window has focus.
+pub fn focused_window_id() -> Result<Option<u32>, Box<dyn Error>> {
+    let windows = if supports(JSON_OUTPUT_VERSION) {
+        serde_json::from_str::<Vec<AerospaceWindow>>(&execute_command(&[
+            "list-windows",
+            "--focused",
+            "--json",
+        ])?)?
+    } else {
+        parse_plain_windows(&execute_command(&["list-windows", "--focused"])?)?
+    };
+    Ok(windows.first().map(|window| window.window_id))
+}
+
+pub fn workspace_exists(workspace: &str) -> Result<bool, Box<dyn Error>> {
+    Ok(list_workspaces()?.iter().any(|name| name == workspace))
+}
+
+/// Focuses a workspace, creating it if it doesn't exist yet. Like every
+/// non-persistent aerospace workspace, it disappears again once empty and unfocused.
+pub fn focus_workspace(workspace: &str) -> Result<(), Box<dyn Error>> {
+    execute_command(&["workspace", workspace]).map(|_| ())
+}
+
+pub fn move_workspace_to_monitor(workspace: &str, monitor: &str) -> Result<(), Box<dyn Error>> {
+    execute_command(&[
+        "move-workspace-to-monitor",
+        "--workspace",
+        workspace,
+        monitor,
+    ])
+    .map(|_| ())
+}
+
+/// Moves a workspace to the focused monitor and focuses it.
+pub fn summon_workspace(workspace: &str) -> Result<(), Box<dyn Error>> {
+    execute_command(&["summon-workspace", workspace]).map(|_| ())
+}
+
+/// Switches between the focused and the previously focused workspace.
+pub fn workspace_back_and_forth() -> Result<(), Box<dyn Error>> {
+    execute_command(&["workspace-back-and-forth"]).map(|_| ())
 }
 
 pub fn list_windows() -> Result<Vec<WindowInfo>, Box<dyn Error>> {
@@ -51,32 +301,292 @@ pub fn list_windows() -> Result<Vec<WindowInfo>, Box<dyn Error>> {
     let mut all_windows = Vec::new();
 
     for workspace in workspaces {
-        let workspace_windows = list_windows_in_workspace(&workspace)?;
-        for window in workspace_windows {
-            all_windows.push(WindowInfo {
-                app_name: window.app_name,
-                window_id: window.window_id,
-                window_title: window.window_title,
-                workspace: workspace.clone(),
-            });
-        }
+        let workspace_windows = fetch_windows_in_workspace(&workspace)?;
+        store_cached_windows(&workspace, &workspace_windows);
+        all_windows.extend(workspace_windows);
     }
 
     Ok(all_windows)
 }
 
+/// Lists a workspace's windows, reusing a result younger than the cache TTL.
 pub fn list_windows_in_workspace(workspace: &str) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
-    execute_command(&["list-windows", "--workspace", workspace, "--json"])
+    if let Some(windows) = cached_windows(workspace) {
+        return Ok(windows);
+    }
+
+    let windows = fetch_windows_in_workspace(workspace)?;
+    store_cached_windows(workspace, &windows);
+    Ok(windows)
+}
+
+fn fetch_windows_in_workspace(workspace: &str) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
+    let windows = if supports(JSON_OUTPUT_VERSION) {
+        execute_command(&[
+            "list-windows",
+            "--workspace",
+            workspace,
+            "--json",
+            "--format",
+            WINDOW_FORMAT,
+        ])
         .and_then(|s| serde_json::from_str::<Vec<AerospaceWindow>>(&s).map_err(|e| e.into()))
-        .map(|windows| {
-            windows
+    } else {
+        execute_command(&["list-windows", "--workspace", workspace])
+            .and_then(|s| parse_plain_windows(&s))
+    };
+
+    windows.map(|windows| {
+        let mut windows: Vec<WindowInfo> = windows
+            .iter()
+            .map(|window| WindowInfo {
+                app_name: window.app_name.clone(),
+                window_id: window.window_id,
+                window_title: window.window_title.clone(),
+                workspace: workspace.to_string(),
+                monitor: window.monitor_name.clone(),
+                frame: None,
+                focused: false,
+            })
+            .collect();
+
+        enrich_windows(&mut windows);
+        windows
+    })
+}
+
+/// Fills in data the aerospace CLI doesn't expose when a native provider is compiled in.
+fn enrich_windows(_windows: &mut [WindowInfo]) {
+    #[cfg(all(target_os = "macos", feature = "accessibility"))]
+    crate::accessibility::enrich_windows(_windows);
+}
+
+/// Parses the default `window-id | app-name | window-title` output of older releases.
+fn parse_plain_windows(output: &str) -> Result<Vec<AerospaceWindow>, Box<dyn Error>> {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.splitn(3, " | ").map(str::trim);
+            let window_id = fields.next().unwrap_or_default().parse()?;
+            let app_name = fields.next().unwrap_or_default().to_string();
+            let window_title = fields.next().unwrap_or_default().to_string();
+            Ok(AerospaceWindow {
+                app_name,
+                window_id,
+                window_title,
+                monitor_name: None,
+            })
+        })
+        .collect()
+}
+
+/// Moves windows to workspaces, reporting each outcome separately.
+///
+/// The aerospace CLI has no multi-command mode, so every move is still its own
+/// process. Up to the configured number of them run at once, see [`set_move_limits`],
+/// and one still running after the timeout is killed.
+pub fn move_windows(moves: &[(u32, String)]) -> Vec<MoveResult> {
+    if !moves.is_empty() {
+        invalidate_cache();
+    }
+
+    let (parallelism, timeout) = *MOVE_LIMITS.read().unwrap();
+    moves
+        .chunks(parallelism.max(1))
+        .flat_map(|chunk| {
+            let spawned: Vec<_> = chunk
                 .iter()
-                .map(|window| WindowInfo {
-                    app_name: window.app_name.clone(),
-                    window_id: window.window_id,
-                    window_title: window.window_title.clone(),
-                    workspace: workspace.to_string(),
+                .map(|(window_id, workspace)| {
+                    let child = command()
+                        .args([
+                            "move",
+                            "--window-id",
+                            &window_id.to_string(),
+                            "--workspace",
+                            workspace,
+                        ])
+                        .stdout(Stdio::null())
+                        .stderr(Stdio::piped())
+                        .spawn();
+                    (*window_id, workspace, Instant::now(), child)
                 })
-                .collect()
+                .collect();
+            spawned
+                .into_iter()
+                .map(|(window_id, workspace, started, child)| {
+                    let result = child
+                        .map_err(|e| e.to_string())
+                        .and_then(|child| wait_for_move(child, started, timeout))
+                        .map_err(|e| {
+                            format!("Failed to move window to workspace {workspace}: {e}")
+                        });
+                    metrics::record_aerospace_call("move", started.elapsed());
+                    MoveResult {
+                        window_id,
+                        workspace: workspace.clone(),
+                        result,
+                    }
+                })
+                .collect::<Vec<_>>()
         })
+        .collect()
+}
+
+/// Waits until `child`, a move spawned at `started`, exits, killing it once `timeout`
+/// has passed since then.
+fn wait_for_move(mut child: Child, started: Instant, timeout: Duration) -> Result<(), String> {
+    loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) if status.success() => return Ok(()),
+            Some(_) => {
+                let output = child.wait_with_output().map_err(|e| e.to_string())?;
+                return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+            }
+            None if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}ms", timeout.as_millis()));
+            }
+            None => std::thread::sleep(MOVE_POLL_INTERVAL),
+        }
+    }
+}
+
+/// Sets the layout of the container holding `window_id`, e.g. `tiles horizontal`.
+pub fn set_layout(window_id: u32, layout: &str) -> Result<(), Box<dyn Error>> {
+    let window_id = window_id.to_string();
+    let mut args = vec!["layout"];
+    args.extend(layout.split_whitespace());
+    args.extend(["--window-id", &window_id]);
+    execute_command(&args).map(|_| ())
+}
+
+pub fn fullscreen_window(window_id: u32) -> Result<(), Box<dyn Error>> {
+    invalidate_cache();
+    let started = Instant::now();
+    let output = command()
+        .args(["fullscreen", "--window-id", &window_id.to_string()])
+        .output();
+    metrics::record_aerospace_call("fullscreen", started.elapsed());
+    let output = output?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to maximize window: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// The aerospace config key naming the command run whenever the focused workspace
+/// changes.
+pub const WORKSPACE_CHANGE_HOOK: &str = "exec-on-workspace-change";
+
+/// The config file aerospace reads: `~/.aerospace.toml`, unless only
+/// `$XDG_CONFIG_HOME/aerospace/aerospace.toml` exists.
+pub fn config_path() -> Option<PathBuf> {
+    let home = PathBuf::from(std::env::var_os("HOME")?);
+    let dotfile = home.join(".aerospace.toml");
+    let xdg = std::env::var_os("XDG_CONFIG_HOME")
+        .map_or_else(|| home.join(".config"), PathBuf::from)
+        .join("aerospace/aerospace.toml");
+    Some(if !dotfile.exists() && xdg.exists() {
+        xdg
+    } else {
+        dotfile
+    })
+}
+
+/// `source`, an aerospace config, with [`WORKSPACE_CHANGE_HOOK`] set to `command`.
+/// `None` when the hook already runs aerospace-rules, and an error when it runs
+/// something else, which is left for the user to combine by hand.
+pub fn with_workspace_change_hook(
+    source: &str,
+    command: &[&str],
+) -> Result<Option<String>, Box<dyn Error>> {
+    let mut document: toml_edit::DocumentMut = source.parse()?;
+    if let Some(existing) = document.get(WORKSPACE_CHANGE_HOOK) {
+        let runs_rules = existing.as_array().is_some_and(|args| {
+            args.iter()
+                .filter_map(|arg| arg.as_str())
+                .any(|arg| arg.contains("aerospace-rules"))
+        });
+        if runs_rules {
+            return Ok(None);
+        }
+        return Err(format!(
+            "{WORKSPACE_CHANGE_HOOK} already runs {}",
+            existing.to_string().trim()
+        )
+        .into());
+    }
+    let command: toml_edit::Array = command.iter().copied().collect();
+    document.insert(WORKSPACE_CHANGE_HOOK, toml_edit::value(command));
+    Ok(Some(document.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version_output() {
+        let output = "aerospace CLI client version: 0.15.2-Beta 7aa2a9b\n\
+                      AeroSpace.app server version: 0.15.2-Beta 7aa2a9b\n";
+        assert_eq!(
+            AerospaceVersion::parse(output),
+            Some(AerospaceVersion::new(0, 15, 2))
+        );
+        assert!(AerospaceVersion::new(0, 14, 9) < JSON_OUTPUT_VERSION);
+        assert_eq!(AerospaceVersion::parse("no version here"), None);
+    }
+
+    #[test]
+    fn test_parse_plain_windows() {
+        let output = "1234 | Ghostty      | ~/src | zsh\n42   | IntelliJ IDEA | Project\n";
+        let windows = parse_plain_windows(output).expect("Output should parse");
+
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].window_id, 1234);
+        assert_eq!(windows[0].app_name, "Ghostty");
+        assert_eq!(windows[0].window_title, "~/src | zsh");
+        assert_eq!(windows[1].app_name, "IntelliJ IDEA");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hung_moves_are_killed() {
+        let child = Command::new("sleep").arg("5").spawn().unwrap();
+        let started = Instant::now();
+        let result = wait_for_move(child, started, Duration::from_millis(50));
+        assert_eq!(result, Err("timed out after 50ms".to_string()));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_parse_names() {
+        assert_eq!(parse_names("1\n  web \n\nS\n"), ["1", "web", "S"]);
+        assert!(parse_names("\n").is_empty());
+    }
+
+    #[test]
+    fn test_workspace_change_hook() {
+        let command = ["/usr/local/bin/aerospace-rules", "on-workspace-change"];
+        let source = "# My config\nstart-at-login = true\n\n[gaps]\ninner.horizontal = 8\n";
+        let edited = with_workspace_change_hook(source, &command)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            edited,
+            "# My config\nstart-at-login = true\nexec-on-workspace-change = [\"/usr/local/bin/aerospace-rules\", \"on-workspace-change\"]\n\n[gaps]\ninner.horizontal = 8\n"
+        );
+        assert_eq!(with_workspace_change_hook(&edited, &command).unwrap(), None);
+
+        let taken = "exec-on-workspace-change = ['/bin/bash', '-c', 'sketchybar']\n";
+        assert!(with_workspace_change_hook(taken, &command).is_err());
+    }
 }
@@ -0,0 +1,305 @@
+use crate::config::{BackendKind, ServiceConfig};
+use crate::{aerospace, WindowInfo};
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+/// The window manager operations the rules engine depends on.
+pub trait WindowManager: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn list_windows(&self) -> Result<Vec<WindowInfo>, Box<dyn Error>>;
+
+    fn list_windows_in_workspace(&self, workspace: &str)
+        -> Result<Vec<WindowInfo>, Box<dyn Error>>;
+
+    fn move_window(&self, window_id: u32, workspace: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Moves several windows, reporting each outcome separately.
+    fn move_windows(&self, moves: &[(u32, String)]) -> Vec<MoveResult> {
+        moves
+            .iter()
+            .map(|(window_id, workspace)| MoveResult {
+                window_id: *window_id,
+                workspace: workspace.clone(),
+                result: self
+                    .move_window(*window_id, workspace)
+                    .map_err(|e| e.to_string()),
+            })
+            .collect()
+    }
+
+    fn fullscreen_window(&self, window_id: u32) -> Result<(), Box<dyn Error>>;
+
+    /// Arranges the container of `window_id` in `layout`, e.g. `tiles horizontal`.
+    fn set_layout(&self, _window_id: u32, _layout: &str) -> Result<(), Box<dyn Error>> {
+        Err(format!("{} doesn't support layouts", self.name()).into())
+    }
+
+    /// Moves `workspace` to `monitor`, given as `main`, `secondary`, a 1-based index or
+    /// a pattern matching the monitor name.
+    fn move_workspace_to_monitor(
+        &self,
+        _workspace: &str,
+        _monitor: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        Err(format!("{} doesn't support moving workspaces", self.name()).into())
+    }
+
+    /// Switches the focused monitor to `workspace`.
+    fn focus_workspace(&self, _workspace: &str) -> Result<(), Box<dyn Error>> {
+        Err(format!("{} doesn't support focusing workspaces", self.name()).into())
+    }
+
+    /// Starts the app named `app_name`, which opens its windows asynchronously.
+    fn launch_app(&self, app_name: &str) -> Result<(), Box<dyn Error>> {
+        let output = std::process::Command::new("open")
+            .args(["-a", app_name])
+            .output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to launch {app_name}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// ID of the window with keyboard focus, if it can be determined.
+    fn focused_window(&self) -> Option<u32> {
+        None
+    }
+
+    /// Name of the focused workspace, if it can be determined.
+    fn focused_workspace(&self) -> Option<String> {
+        None
+    }
+
+    /// Names of the connected monitors, empty if they can't be determined.
+    fn monitors(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Names of the workspaces the window manager has configured, empty if they can't be
+    /// determined.
+    fn workspaces(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// PID of the window manager process, if it can be determined. A changed PID
+    /// means the window manager restarted.
+    fn server_pid(&self) -> Option<u32> {
+        None
+    }
+}
+
+impl fmt::Debug for dyn WindowManager + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WindowManager")
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+/// Builds the backend selected by `[service] backend`.
+pub fn for_config(service: &ServiceConfig) -> Arc<dyn WindowManager> {
+    match service.backend.unwrap_or_default() {
+        BackendKind::Aerospace => Arc::new(Aerospace),
+        #[cfg(unix)]
+        BackendKind::Sway => Arc::new(crate::sway::Sway::new(crate::sway::Flavor::Sway)),
+        #[cfg(unix)]
+        BackendKind::I3 => Arc::new(crate::sway::Sway::new(crate::sway::Flavor::I3)),
+        #[cfg(all(windows, feature = "komorebi"))]
+        BackendKind::Komorebi => Arc::new(crate::komorebi::Komorebi::new()),
+        kind => Arc::new(Unavailable(kind)),
+    }
+}
+
+/// Stands in for a backend that isn't compiled into this build, failing every call.
+#[derive(Debug)]
+struct Unavailable(BackendKind);
+
+impl Unavailable {
+    fn error<T>(&self) -> Result<T, Box<dyn Error>> {
+        Err(format!("The {:?} backend is not available in this build", self.0).into())
+    }
+}
+
+impl WindowManager for Unavailable {
+    fn name(&self) -> &'static str {
+        "unavailable"
+    }
+
+    fn list_windows(&self) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
+        self.error()
+    }
+
+    fn list_windows_in_workspace(
+        &self,
+        _workspace: &str,
+    ) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
+        self.error()
+    }
+
+    fn move_window(&self, _window_id: u32, _workspace: &str) -> Result<(), Box<dyn Error>> {
+        self.error()
+    }
+
+    fn fullscreen_window(&self, _window_id: u32) -> Result<(), Box<dyn Error>> {
+        self.error()
+    }
+}
+
+/// Outcome of a single move issued through a [`MoveBatch`].
+#[derive(Debug, Clone)]
+pub struct MoveResult {
+    pub window_id: u32,
+    pub workspace: String,
+    pub result: Result<(), String>,
+}
+
+/// Collects window moves produced during an evaluation and issues them together,
+/// collapsing repeated moves of the same window into the last one.
+#[derive(Debug, Default)]
+pub struct MoveBatch {
+    moves: Vec<(u32, String)>,
+}
+
+impl MoveBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, window_id: u32, workspace: &str) {
+        match self.moves.iter_mut().find(|(id, _)| *id == window_id) {
+            Some(existing) => existing.1 = workspace.to_string(),
+            None => self.moves.push((window_id, workspace.to_string())),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    /// Issues the moves, at most `parallelism` at a time.
+    pub fn execute(self, wm: &dyn WindowManager, parallelism: usize) -> Vec<MoveResult> {
+        self.moves
+            .chunks(parallelism.max(1))
+            .flat_map(|chunk| wm.move_windows(chunk))
+            .collect()
+    }
+}
+
+/// Drives AeroSpace through its CLI.
+#[derive(Debug, Default)]
+pub struct Aerospace;
+
+impl WindowManager for Aerospace {
+    fn name(&self) -> &'static str {
+        "aerospace"
+    }
+
+    fn list_windows(&self) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
+        aerospace::list_windows()
+    }
+
+    fn list_windows_in_workspace(
+        &self,
+        workspace: &str,
+    ) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
+        aerospace::list_windows_in_workspace(workspace)
+    }
+
+    fn move_window(&self, window_id: u32, workspace: &str) -> Result<(), Box<dyn Error>> {
+        self.move_windows(&[(window_id, workspace.to_string())])
+            .remove(0)
+            .result
+            .map_err(|e| e.into())
+    }
+
+    fn move_windows(&self, moves: &[(u32, String)]) -> Vec<MoveResult> {
+        aerospace::move_windows(moves)
+    }
+
+    fn fullscreen_window(&self, window_id: u32) -> Result<(), Box<dyn Error>> {
+        aerospace::fullscreen_window(window_id)
+    }
+
+    fn set_layout(&self, window_id: u32, layout: &str) -> Result<(), Box<dyn Error>> {
+        aerospace::set_layout(window_id, layout)
+    }
+
+    fn move_workspace_to_monitor(
+        &self,
+        workspace: &str,
+        monitor: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        aerospace::move_workspace_to_monitor(workspace, monitor)
+    }
+
+    fn focus_workspace(&self, workspace: &str) -> Result<(), Box<dyn Error>> {
+        aerospace::focus_workspace(workspace)
+    }
+
+    fn focused_window(&self) -> Option<u32> {
+        aerospace::focused_window_id().ok().flatten()
+    }
+
+    fn focused_workspace(&self) -> Option<String> {
+        aerospace::focused_workspace().ok()
+    }
+
+    fn monitors(&self) -> Vec<String> {
+        aerospace::list_monitors().unwrap_or_default()
+    }
+
+    fn workspaces(&self) -> Vec<String> {
+        aerospace::list_workspaces().unwrap_or_default()
+    }
+
+    fn server_pid(&self) -> Option<u32> {
+        aerospace::server_pid()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{window, Call, FakeWindowManager};
+
+    #[test]
+    fn test_move_batch_keeps_the_last_move_of_each_window() {
+        let wm = FakeWindowManager::new([window("Ghostty").id(1), window("Safari").id(2)]);
+        let mut moves = MoveBatch::new();
+        moves.push(1, "2");
+        moves.push(2, "3");
+        moves.push(1, "4");
+        assert_eq!(moves.len(), 2);
+
+        let results = moves.execute(&wm, 1);
+        let moved: Vec<_> = results
+            .iter()
+            .map(|moved| (moved.window_id, moved.workspace.as_str()))
+            .collect();
+        // A window keeps the place of its first move
+        assert_eq!(moved, [(1, "4"), (2, "3")]);
+        assert_eq!(
+            wm.calls(),
+            [
+                Call::Move {
+                    window_id: 1,
+                    workspace: "4".to_string()
+                },
+                Call::Move {
+                    window_id: 2,
+                    workspace: "3".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! Starting and stopping the service, through launchd when it's installed as a
+//! LaunchAgent and by spawning or signalling the binary otherwise.
+
+use crate::query_service;
+use aerospace_rules::{Pong, Request, Response, PID_PATH, SOCKET_PATH};
+use std::error::Error;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const SERVICE_BINARY: &str = "aerospace-rules-service";
+const LAUNCH_AGENT_LABEL: &str = "com.github.kantis.aerospace-rules";
+/// The service lists windows every few seconds, so older state means its refreshes
+/// are failing.
+const STALE_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(clap::Subcommand, Clone, Copy)]
+pub enum ServiceCommand {
+    /// Start the service if it isn't running
+    Start,
+    /// Stop the running service
+    Stop,
+    /// Stop and start the service
+    Restart,
+    /// Show whether the service is running and responding
+    Status,
+}
+
+pub async fn run(command: ServiceCommand, config_path: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let launch_agent = launch_agent_plist().filter(|plist| plist.exists());
+
+    match command {
+        ServiceCommand::Start => start(launch_agent.as_deref(), config_path).await,
+        ServiceCommand::Stop => stop(launch_agent.as_deref()).await,
+        ServiceCommand::Restart => {
+            if let Some(plist) = &launch_agent {
+                // Kickstarting fails when the agent isn't loaded
+                if launchctl(&["kickstart", "-k", &launchd_target()?]).is_err() {
+                    launchctl(&["bootstrap", &launchd_domain()?, path_str(plist)?])?;
+                }
+                wait_until(true).await?;
+                println!("Service restarted");
+                return Ok(());
+            }
+            if is_responding().await {
+                stop(None).await?;
+            }
+            start(None, config_path).await
+        }
+        ServiceCommand::Status => {
+            let manager = if launch_agent.is_some() {
+                "launchd"
+            } else {
+                "no service manager"
+            };
+            let started = Instant::now();
+            let pong = ping().await;
+            let latency = started.elapsed();
+            match (pong, service_pid()) {
+                (Some(pong), pid) => {
+                    let pid = pid.map(|pid| format!("pid {pid}, ")).unwrap_or_default();
+                    println!(
+                        "Service is running ({pid}{manager}), answered in {} ms",
+                        latency.as_millis()
+                    );
+                    match pong.windows_age_ms.map(Duration::from_millis) {
+                        None => println!("Windows haven't been listed yet"),
+                        Some(age) if age > STALE_AFTER => {
+                            println!("Window state is stale: last listed {} s ago", age.as_secs())
+                        }
+                        Some(age) => println!("Windows listed {} ms ago", age.as_millis()),
+                    }
+                }
+                (None, Some(pid)) => {
+                    println!("Service process {pid} exists but isn't responding on {SOCKET_PATH}")
+                }
+                (None, None) => println!("Service is not running"),
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn start(
+    launch_agent: Option<&Path>,
+    config_path: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    if is_responding().await {
+        println!("Service is already running");
+        return Ok(());
+    }
+
+    if let Some(plist) = launch_agent {
+        // Bootstrapping fails when the agent is already loaded but stopped
+        if launchctl(&["bootstrap", &launchd_domain()?, path_str(plist)?]).is_err() {
+            launchctl(&["kickstart", &launchd_target()?])?;
+        }
+    } else {
+        let mut command = Command::new(service_binary());
+        if let Some(path) = config_path {
+            command.args(["--config", path]);
+        }
+        // Own process group, so the service outlives the terminal that started it
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()?;
+    }
+
+    wait_until(true).await?;
+    println!("Service started");
+    Ok(())
+}
+
+async fn stop(launch_agent: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    if launch_agent.is_some() {
+        launchctl(&["bootout", &launchd_target()?])?;
+    } else {
+        // A stale PID file may name an unrelated process, so only signal a live service
+        if !is_responding().await {
+            println!("Service is not running");
+            return Ok(());
+        }
+        let pid =
+            service_pid().ok_or_else(|| format!("Service is running but {PID_PATH} is missing"))?;
+        let status = Command::new("kill").arg(pid.to_string()).status()?;
+        if !status.success() {
+            return Err(format!("Failed to stop service process {pid}").into());
+        }
+    }
+
+    wait_until(false).await?;
+    let _ = std::fs::remove_file(PID_PATH);
+    println!("Service stopped");
+    Ok(())
+}
+
+async fn is_responding() -> bool {
+    ping().await.is_some()
+}
+
+async fn ping() -> Option<Pong> {
+    match query_service(&Request::Ping).await {
+        Ok(Response::Pong(pong)) => Some(pong),
+        _ => None,
+    }
+}
+
+/// Polls the socket until the service is (or is no longer) responding.
+async fn wait_until(responding: bool) -> Result<(), Box<dyn Error>> {
+    for _ in 0..50 {
+        if is_responding().await == responding {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    Err(if responding {
+        "Service did not come up within 5 seconds".into()
+    } else {
+        "Service did not stop within 5 seconds".into()
+    })
+}
+
+/// The service binary next to this one, falling back to a `PATH` lookup.
+fn service_binary() -> PathBuf {
+    std::env::current_exe()
+        .map(|exe| exe.with_file_name(SERVICE_BINARY))
+        .ok()
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(SERVICE_BINARY))
+}
+
+/// PID of the running service, from the file it writes on startup.
+fn service_pid() -> Option<u32> {
+    let pid: u32 = std::fs::read_to_string(PID_PATH)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    // The file outlives a crashed service
+    let alive = Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    alive.then_some(pid)
+}
+
+fn launch_agent_plist() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(
+        PathBuf::from(home)
+            .join("Library/LaunchAgents")
+            .join(format!("{LAUNCH_AGENT_LABEL}.plist")),
+    )
+}
+
+fn launchd_domain() -> Result<String, Box<dyn Error>> {
+    let output = Command::new("id").arg("-u").output()?;
+    Ok(format!("gui/{}", String::from_utf8(output.stdout)?.trim()))
+}
+
+fn launchd_target() -> Result<String, Box<dyn Error>> {
+    Ok(format!("{}/{LAUNCH_AGENT_LABEL}", launchd_domain()?))
+}
+
+fn launchctl(args: &[&str]) -> Result<(), Box<dyn Error>> {
+    let output = Command::new("launchctl").args(args).output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "launchctl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn path_str(path: &Path) -> Result<&str, Box<dyn Error>> {
+    path.to_str()
+        .ok_or_else(|| format!("Non UTF-8 path: {}", path.display()).into())
+}
@@ -0,0 +1,1843 @@
+use aerospace_rules::client::{Client, ClientError};
+use aerospace_rules::rules::{ActionResult, PlannedAction, Trigger, WindowExplanation};
+use aerospace_rules::session::{self, Session};
+use aerospace_rules::suggest;
+use aerospace_rules::validate::{self, Diagnostic, Severity};
+use aerospace_rules::{
+    aerospace, backend, config, logging, rules, ErrorKind, Event, HistoryEntry, Report, Request,
+    Response, RuleStatus, WindowFilter, WindowFrame, WindowInfo, WindowSort,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::io::IsTerminal;
+use std::process::ExitCode;
+use std::time::Duration;
+
+mod daemon;
+#[cfg(all(target_os = "macos", feature = "menubar"))]
+mod menubar;
+mod raycast;
+mod setup;
+#[cfg(feature = "tui")]
+mod tui;
+
+async fn query_service(request: &Request) -> Result<Response, ClientError> {
+    Client::new().request(request).await
+}
+
+/// Subscribes to service events and calls `on_event` for each until the service goes away.
+async fn subscribe(mut on_event: impl FnMut(Event)) -> Result<(), ClientError> {
+    let mut subscription = Client::new().subscribe().await?;
+    while let Some(event) = subscription.next_event().await? {
+        on_event(event);
+    }
+    Ok(())
+}
+
+const ACTION_ENV_HELP: &str = "\
+Programs run by actions, empty-workspace and startup commands get these variables:
+  AR_RULE_NAME   Name of the rule the action belongs to
+  AR_TRIGGER     Why rules ran: workspace, windows, title-changed or startup
+  AR_WINDOW_ID   ID of the targeted window, if any
+  AR_APP_NAME    App of the targeted window, if any
+  AR_WORKSPACE   Workspace of the targeted window, or the evaluated workspace";
+
+#[derive(Parser)]
+#[command(name = "aerospace-rules")]
+#[command(about = "A CLI client for aerospace window rules")]
+#[command(after_help = ACTION_ENV_HELP)]
+struct Args {
+    /// Path to config file
+    #[arg(short, long, global = true)]
+    config: Option<String>,
+
+    /// Output format
+    #[arg(
+        short,
+        long,
+        alias = "format",
+        global = true,
+        value_enum,
+        default_value_t = OutputFormat::Plain
+    )]
+    output: OutputFormat,
+
+    /// Shorthand for `--output json`
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Disable colored output (also disabled by a non-empty NO_COLOR)
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Command to execute (defaults to `windows`)
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// Human-readable lines
+    Plain,
+    /// Aligned columns
+    Table,
+    /// JSON for scripts and integrations
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List windows known to the service
+    Windows {
+        #[command(flatten)]
+        filter: WindowFilterArgs,
+    },
+    /// Show the rules loaded by the service
+    Config {
+        #[command(subcommand)]
+        command: Option<ConfigCommand>,
+    },
+    /// List every rule with its state, tags and hit count
+    Rules,
+    /// Reload the config and window state
+    Reload,
+    /// Evaluate rules for a workspace
+    #[command(alias = "on-workspace-change")]
+    Evaluate {
+        /// Workspace to evaluate; defaults to the focused one
+        #[arg(long, env = "AEROSPACE_FOCUSED_WORKSPACE")]
+        workspace: Option<String>,
+
+        /// Show what would happen without performing any action
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Only match rules against windows that are new to the workspace or changed app
+        /// or title since its last evaluation, e.g. from `exec-on-workspace-change`
+        #[arg(long)]
+        changed_only: bool,
+    },
+    /// Apply window rules to a newly opened window, for AeroSpace's `on-window-detected`
+    HandleWindowDetected {
+        /// The detected window
+        #[arg(long, env = "AEROSPACE_WINDOW_ID")]
+        window_id: u32,
+
+        /// Show what would happen without performing any action
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Suspend all rule actions, e.g. before sharing your screen
+    Pause,
+    /// Resume rule actions after `pause`
+    Resume,
+    /// Pause or resume rule actions, for binding to a single key
+    Toggle,
+    /// Show the actions rules performed recently
+    History {
+        /// Only actions from this long ago or later, e.g. `30m`, `1h` or `2d`
+        #[arg(long, value_parser = parse_age)]
+        since: Option<Duration>,
+
+        /// Only actions of this rule
+        #[arg(long)]
+        rule: Option<String>,
+
+        /// Only failed actions
+        #[arg(long)]
+        failed: bool,
+    },
+    /// Move windows back to the workspaces their rules assign them to, e.g. after
+    /// unplugging a monitor
+    Gather {
+        /// Only gather windows assigned to this workspace
+        #[arg(long)]
+        workspace: Option<String>,
+
+        /// Show what would move without moving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Set up the workspaces defined under `[workspaces]`: launch missing apps, move
+    /// their windows there and apply the layout
+    Bootstrap {
+        /// Only set up this workspace
+        #[arg(long)]
+        workspace: Option<String>,
+
+        /// Show what would be launched and moved without doing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Move windows that rules moved back to where they were
+    Undo {
+        /// Number of moves to undo, most recent first
+        #[arg(long, default_value_t = 1)]
+        last: usize,
+    },
+    /// Save which workspace every window is on, or put windows back where a saved
+    /// session had them
+    Session {
+        #[command(subcommand)]
+        command: SessionCommand,
+    },
+    /// Summarize which workspaces apps spent their time on since the service started
+    /// and which rules acted most
+    Report,
+    /// Print rules, as TOML, that would keep apps on the workspaces they're on now
+    Suggest,
+    /// Check the config file for errors without loading it into the service
+    Validate,
+    /// Check that the service answers and how fresh its window state is
+    Ping,
+    /// Show the service's recent log lines
+    Logs {
+        /// Number of lines to show
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+
+        /// Keep printing new lines as the service logs them
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Print window changes, rule firings and config reloads as they happen
+    Watch {
+        /// Include the service's log lines
+        #[arg(long)]
+        logs: bool,
+    },
+    /// Add the `exec-on-workspace-change` hook that evaluates rules to aerospace's
+    /// config
+    SetupHooks {
+        /// Only print the snippet to add
+        #[arg(long)]
+        print: bool,
+    },
+    /// Start, stop or check on the service
+    Service {
+        #[command(subcommand)]
+        command: daemon::ServiceCommand,
+    },
+    /// Interactive dashboard of workspaces, windows, rules and recent actions
+    #[cfg(feature = "tui")]
+    Tui,
+    /// Menu bar item showing the service's status and recent actions
+    #[cfg(all(target_os = "macos", feature = "menubar"))]
+    Menubar,
+    /// Show which rules match a window, and which condition failed for the rest
+    Explain {
+        /// Window to explain
+        #[arg(long, required_unless_present = "app")]
+        window_id: Option<u32>,
+
+        /// Explain every window of this app
+        #[arg(long)]
+        app: Option<String>,
+    },
+    /// Run a made-up window through the config's rules without performing any action
+    Test {
+        /// App name of the window
+        #[arg(long)]
+        app: String,
+
+        /// Window title
+        #[arg(long, default_value = "")]
+        title: String,
+
+        /// Workspace the window is on
+        #[arg(long, default_value = "1")]
+        workspace: String,
+
+        /// Monitor the window is on
+        #[arg(long)]
+        monitor: Option<String>,
+
+        /// Window ID
+        #[arg(long, default_value_t = 0)]
+        window_id: u32,
+
+        /// Window width in points
+        #[arg(long, requires = "height")]
+        width: Option<f64>,
+
+        /// Window height in points
+        #[arg(long, requires = "width")]
+        height: Option<f64>,
+    },
+    /// Manage individual rules
+    Rule {
+        #[command(subcommand)]
+        command: RuleCommand,
+    },
+    /// Windows or rules as JSON props of Raycast list items
+    Raycast {
+        #[command(subcommand)]
+        command: RaycastCommand,
+    },
+}
+
+#[derive(clap::Args, Default)]
+struct WindowFilterArgs {
+    /// Only show windows on this workspace
+    #[arg(long)]
+    workspace: Option<String>,
+
+    /// Only show windows of this app (case-insensitive)
+    #[arg(long)]
+    app: Option<String>,
+
+    /// Only show windows whose title contains this text (case-insensitive)
+    #[arg(long)]
+    title_contains: Option<String>,
+
+    /// Order windows by this key instead of the window manager's order
+    #[arg(long, value_enum)]
+    sort: Option<WindowSort>,
+}
+
+impl WindowFilterArgs {
+    fn filter(&self) -> WindowFilter {
+        WindowFilter {
+            workspace: self.workspace.clone(),
+            app_name: self.app.clone(),
+            title_contains: self.title_contains.clone(),
+            sort: self.sort,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the config the service loaded, as TOML
+    Show {
+        /// Include every default and setting the service resolved itself
+        #[arg(long)]
+        effective: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionCommand {
+    /// Save the workspace of every window under a name
+    Save { name: String },
+    /// Move windows back to the workspaces they were on in a saved session
+    Restore {
+        name: String,
+
+        /// Show what would move without moving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Clone, Copy)]
+enum RaycastCommand {
+    /// Every window, titled by app
+    Windows,
+    /// Every rule with its tags and hit count
+    Rules,
+}
+
+#[derive(Subcommand)]
+enum RuleCommand {
+    /// Add a window rule to the config file
+    Add {
+        /// Name of the new rule
+        #[arg(long)]
+        name: String,
+
+        /// Match windows of this app
+        #[arg(
+            long,
+            required_unless_present = "condition",
+            conflicts_with = "condition"
+        )]
+        app: Option<String>,
+
+        /// Match windows with this condition, e.g. "window-title contains 'Meet'"
+        #[arg(long)]
+        condition: Option<String>,
+
+        /// Move matching windows to this workspace
+        #[arg(long, required_unless_present = "action", conflicts_with = "action")]
+        workspace: Option<String>,
+
+        /// Perform this action on matching windows, e.g. "maximize"
+        #[arg(long)]
+        action: Option<String>,
+
+        /// Only apply the rule to windows on this workspace; repeat for several
+        #[arg(long = "only-on", value_name = "WORKSPACE")]
+        only_on: Vec<String>,
+    },
+    /// Remove a rule from the config file
+    #[command(alias = "remove")]
+    Rm { name: String },
+    /// Enable a rule in the config file
+    Enable { name: String },
+    /// Disable a rule in the config file, keeping it around
+    Disable { name: String },
+}
+
+impl Command {
+    /// The request to send to the service; `None` for commands that run locally or
+    /// only subscribe to events.
+    fn request(&self) -> Option<Request> {
+        let request = match self {
+            Command::Windows { filter } => Request::GetWindows {
+                filter: filter.filter(),
+            },
+            Command::Config {
+                command: Some(ConfigCommand::Show { effective: true }),
+            } => Request::GetEffectiveConfig,
+            Command::Config { .. } => Request::GetConfig,
+            Command::Rules => Request::GetRules,
+            Command::Reload => Request::Reload,
+            Command::Evaluate {
+                workspace,
+                dry_run,
+                changed_only,
+            } => Request::EvaluateRules {
+                // Resolved to the focused workspace before the request is built
+                workspace: workspace.clone().unwrap_or_default(),
+                dry_run: *dry_run,
+                changed_only: *changed_only,
+            },
+            Command::HandleWindowDetected { window_id, dry_run } => Request::EvaluateWindow {
+                window_id: *window_id,
+                dry_run: *dry_run,
+            },
+            Command::Pause => Request::SetPaused { paused: true },
+            Command::Resume => Request::SetPaused { paused: false },
+            Command::Toggle => Request::TogglePaused,
+            Command::History {
+                since,
+                rule,
+                failed,
+            } => Request::GetHistory {
+                since: since.and_then(|since| {
+                    chrono::Utc::now().checked_sub_signed(chrono::TimeDelta::from_std(since).ok()?)
+                }),
+                rule: rule.clone(),
+                failed: *failed,
+            },
+            Command::Gather { workspace, dry_run } => Request::Gather {
+                workspace: workspace.clone(),
+                dry_run: *dry_run,
+            },
+            Command::Bootstrap { workspace, dry_run } => Request::Bootstrap {
+                workspace: workspace.clone(),
+                dry_run: *dry_run,
+            },
+            Command::Undo { last } => Request::Undo { last: *last },
+            Command::Report => Request::GetReport,
+            Command::Ping => Request::Ping,
+            Command::Logs { lines, .. } => Request::GetLogs { lines: *lines },
+            Command::Explain { window_id, app } => Request::ExplainWindow {
+                window_id: *window_id,
+                app_name: app.clone(),
+            },
+            Command::Rule { command } => match command {
+                RuleCommand::Add {
+                    name,
+                    app,
+                    condition,
+                    workspace,
+                    action,
+                    only_on,
+                } => Request::AddRule {
+                    rule: config::Rule {
+                        name: name.clone(),
+                        enabled: true,
+                        tags: Vec::new(),
+                        timeout_ms: None,
+                        log: Default::default(),
+                        trace: false,
+                        on_error: Default::default(),
+                        follow: false,
+                        rule_type: config::RuleType::Window {
+                            condition: condition
+                                .clone()
+                                .or_else(|| app.as_ref().map(|app| format!("app-name = '{app}'")))
+                                .unwrap_or_default()
+                                .into(),
+                            action: action
+                                .clone()
+                                .or_else(|| {
+                                    workspace
+                                        .as_ref()
+                                        .map(|workspace| format!("move-to-workspace {workspace}"))
+                                })
+                                .unwrap_or_default(),
+                            workspaces: only_on.clone(),
+                        },
+                        compiled: None,
+                    },
+                },
+                RuleCommand::Rm { name } => Request::RemoveRule { name: name.clone() },
+                RuleCommand::Enable { name } => Request::SetRuleEnabled {
+                    name: name.clone(),
+                    enabled: true,
+                },
+                RuleCommand::Disable { name } => Request::SetRuleEnabled {
+                    name: name.clone(),
+                    enabled: false,
+                },
+            },
+            Command::Raycast {
+                command: RaycastCommand::Windows,
+            } => Request::GetWindows {
+                filter: WindowFilter::default(),
+            },
+            Command::Raycast {
+                command: RaycastCommand::Rules,
+            } => Request::GetRules,
+            Command::Test { .. }
+            | Command::Suggest
+            | Command::Validate
+            | Command::Watch { .. }
+            | Command::Session { .. }
+            | Command::SetupHooks { .. }
+            | Command::Service { .. } => return None,
+            #[cfg(feature = "tui")]
+            Command::Tui => return None,
+            #[cfg(all(target_os = "macos", feature = "menubar"))]
+            Command::Menubar => return None,
+        };
+        Some(request)
+    }
+}
+
+/// Renders responses in the format selected with `--output`.
+struct Output {
+    format: OutputFormat,
+    /// Whether to use ANSI colors.
+    color: bool,
+}
+
+// SGR codes for `Output::paint`
+const BOLD: &str = "1";
+const DIM: &str = "2";
+const RED: &str = "31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+/// Workspaces get a color picked from their name, so the same workspace always
+/// has the same color.
+const WORKSPACE_COLORS: [&str; 5] = ["36", "35", "34", "33", "32"];
+
+impl Output {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn workspace(&self, workspace: &str) -> String {
+        let index = workspace.bytes().map(usize::from).sum::<usize>() % WORKSPACE_COLORS.len();
+        self.paint(WORKSPACE_COLORS[index], workspace)
+    }
+
+    /// Highlights `text` when it belongs to the focused window.
+    fn focused(&self, window: &WindowInfo, text: &str) -> String {
+        if window.focused {
+            self.paint(BOLD, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// A result's description, green when it succeeded and red when it failed.
+    fn result_line(&self, result: &ActionResult, dry_run: bool) -> String {
+        let line = result.to_string();
+        match (&result.error, dry_run) {
+            (Some(_), _) => self.paint(RED, &line),
+            (None, true) => line,
+            (None, false) => self.paint(GREEN, &line),
+        }
+    }
+
+    /// Green for a successful action, yellow for a planned one and red for a failure.
+    fn result_status(&self, result: &ActionResult, dry_run: bool) -> String {
+        match (&result.error, dry_run) {
+            (Some(error), _) => self.paint(RED, &format!("failed: {error}")),
+            (None, true) => self.paint(YELLOW, "planned"),
+            (None, false) => self.paint(GREEN, "ok"),
+        }
+    }
+
+    fn json(&self, value: &impl Serialize) {
+        match serde_json::to_string_pretty(value) {
+            Ok(json) => println!("{json}"),
+            Err(e) => eprintln!("Failed to serialize output: {e}"),
+        }
+    }
+
+    fn windows(&self, windows: &[WindowInfo]) {
+        match self.format {
+            OutputFormat::Json => self.json(&windows),
+            OutputFormat::Table => print_table(
+                &["WORKSPACE", "APP", "ID", "TITLE"],
+                windows
+                    .iter()
+                    .map(|window| {
+                        let cells = [
+                            window.app_name.as_str(),
+                            &window.window_id.to_string(),
+                            &window.window_title,
+                        ]
+                        .map(|cell| self.focused(window, cell));
+                        let mut row = vec![self.workspace(&window.workspace)];
+                        row.extend(cells);
+                        row
+                    })
+                    .collect(),
+            ),
+            OutputFormat::Plain => {
+                println!("Found {} windows:", windows.len());
+                let rows = windows
+                    .iter()
+                    .map(|window| {
+                        let marker = if window.focused { "*" } else { " " };
+                        vec![
+                            format!("{marker} [{}]", self.workspace(&window.workspace)),
+                            self.focused(window, &window.app_name),
+                            self.focused(window, &format!("(ID: {})", window.window_id)),
+                            self.focused(window, &window.window_title),
+                        ]
+                    })
+                    .collect();
+                for line in align(rows) {
+                    println!(" {line}");
+                }
+            }
+        }
+    }
+
+    fn rules(&self, config: &config::Config) {
+        match self.format {
+            OutputFormat::Json => self.json(config),
+            OutputFormat::Table => print_table(
+                &["NAME", "TYPE", "ENABLED", "DETAILS"],
+                config
+                    .rules
+                    .iter()
+                    .map(|rule| {
+                        let (rule_type, details) = describe_rule(rule);
+                        vec![
+                            rule.name.clone(),
+                            rule_type.to_string(),
+                            rule.enabled.to_string(),
+                            details,
+                        ]
+                    })
+                    .collect(),
+            ),
+            OutputFormat::Plain => {
+                println!("Loaded {} rules", config.rules.len());
+                for rule in &config.rules {
+                    let disabled = if rule.enabled { "" } else { " (disabled)" };
+                    match &rule.rule_type {
+                        config::RuleType::Window { condition, .. } => {
+                            println!("Rule: {}{disabled} - {}", rule.name, condition);
+                        }
+                        config::RuleType::EmptyWorkspace {
+                            workspace, command, ..
+                        } => {
+                            println!(
+                                "Rule: {}{disabled} - empty workspace {} -> {}",
+                                rule.name, workspace, command
+                            );
+                        }
+                        config::RuleType::Startup { command } => {
+                            println!("Rule: {}{disabled} - on startup -> {}", rule.name, command);
+                        }
+                        config::RuleType::Script { script } => {
+                            println!(
+                                "Rule: {}{disabled} - script ({} lines)",
+                                rule.name,
+                                script.lines().count()
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn config(&self, config: &config::Config) {
+        if self.format == OutputFormat::Json {
+            return self.json(config);
+        }
+        match toml::to_string_pretty(config) {
+            Ok(toml) => print!("{toml}"),
+            Err(e) => eprintln!("Failed to serialize config: {e}"),
+        }
+    }
+
+    fn rule_statuses(&self, statuses: &[RuleStatus]) {
+        if self.format == OutputFormat::Json {
+            return self.json(&statuses);
+        }
+
+        print_table(
+            &[
+                "NAME", "TYPE", "MATCH", "ACTION", "ENABLED", "TAGS", "HITS", "LAST HIT",
+            ],
+            statuses
+                .iter()
+                .map(|status| {
+                    let rule = &status.rule;
+                    let (rule_type, matcher, action) = match &rule.rule_type {
+                        config::RuleType::Window {
+                            condition, action, ..
+                        } => ("window", condition.to_string(), action.clone()),
+                        config::RuleType::EmptyWorkspace {
+                            workspace, command, ..
+                        } => (
+                            "empty-workspace",
+                            format!("workspace = '{workspace}'"),
+                            command.clone(),
+                        ),
+                        config::RuleType::Startup { command } => {
+                            ("startup", "-".to_string(), command.clone())
+                        }
+                        config::RuleType::Script { script } => (
+                            "script",
+                            "-".to_string(),
+                            format!("{} lines", script.lines().count()),
+                        ),
+                    };
+                    vec![
+                        rule.name.clone(),
+                        rule_type.to_string(),
+                        matcher,
+                        action,
+                        if rule.enabled {
+                            self.paint(GREEN, "yes")
+                        } else {
+                            self.paint(DIM, "no")
+                        },
+                        rule.tags.join(","),
+                        {
+                            let failed = status.hits.saturating_sub(status.executed);
+                            let hits = if failed > 0 {
+                                format!("{} ({failed} failed)", status.hits)
+                            } else {
+                                status.hits.to_string()
+                            };
+                            if status.rate_alarm {
+                                self.paint(YELLOW, &format!("{hits} (too frequent)"))
+                            } else {
+                                hits
+                            }
+                        },
+                        match status.last_hit {
+                            Some(at) => at
+                                .with_timezone(&chrono::Local)
+                                .format("%Y-%m-%d %H:%M")
+                                .to_string(),
+                            None => self.paint(DIM, "never"),
+                        },
+                    ]
+                })
+                .collect(),
+        );
+    }
+
+    fn results(&self, results: &[ActionResult], dry_run: bool) {
+        match self.format {
+            OutputFormat::Json => self.json(&results),
+            OutputFormat::Table => print_table(
+                &["RULE", "WINDOW", "ACTION", "STATUS"],
+                results
+                    .iter()
+                    .map(|result| {
+                        let window = match (result.window_id, &result.app_name) {
+                            (Some(id), Some(app)) => format!("{app} ({id})"),
+                            _ => "-".to_string(),
+                        };
+                        vec![
+                            result.rule.clone(),
+                            window,
+                            result.action.clone(),
+                            self.result_status(result, dry_run),
+                        ]
+                    })
+                    .collect(),
+            ),
+            OutputFormat::Plain => {
+                if results.is_empty() {
+                    println!("No rules matched");
+                } else {
+                    println!(
+                        "{}",
+                        if dry_run {
+                            "Dry run, no actions performed:"
+                        } else {
+                            "Rules evaluated:"
+                        }
+                    );
+                    for result in results {
+                        println!("  {}", self.result_line(result, dry_run));
+                    }
+                }
+            }
+        }
+    }
+
+    fn history(&self, entries: &[HistoryEntry]) {
+        let time = |entry: &HistoryEntry| {
+            entry
+                .at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        };
+        match self.format {
+            OutputFormat::Json => self.json(&entries),
+            OutputFormat::Table => print_table(
+                &["TIME", "RULE", "WINDOW", "FROM", "ACTION", "STATUS"],
+                entries
+                    .iter()
+                    .map(|entry| {
+                        let result = &entry.result;
+                        let window = match (result.window_id, &result.app_name) {
+                            (Some(id), Some(app)) => format!("{app} ({id})"),
+                            _ => "-".to_string(),
+                        };
+                        vec![
+                            time(entry),
+                            result.rule.clone(),
+                            window,
+                            entry
+                                .from_workspace
+                                .as_deref()
+                                .map_or("-".to_string(), |workspace| self.workspace(workspace)),
+                            result.action.clone(),
+                            if entry.undone {
+                                self.paint(DIM, "undone")
+                            } else {
+                                self.result_status(result, false)
+                            },
+                        ]
+                    })
+                    .collect(),
+            ),
+            OutputFormat::Plain => {
+                if entries.is_empty() {
+                    println!("No actions recorded");
+                }
+                for entry in entries {
+                    let mut line = self.result_line(&entry.result, false);
+                    if entry.undone {
+                        line.push_str(&self.paint(DIM, " (undone)"));
+                    }
+                    match &entry.from_workspace {
+                        Some(workspace) => println!(
+                            "{}  [{}] {line}",
+                            self.paint(DIM, &time(entry)),
+                            self.workspace(workspace)
+                        ),
+                        None => println!("{}  {line}", self.paint(DIM, &time(entry))),
+                    }
+                }
+            }
+        }
+    }
+
+    fn report(&self, report: &Report) {
+        let since = report.since.with_timezone(&chrono::Local);
+        match self.format {
+            OutputFormat::Json => self.json(report),
+            OutputFormat::Table => {
+                print_table(
+                    &["APP", "WORKSPACE", "TIME"],
+                    report
+                        .placements
+                        .iter()
+                        .map(|placement| {
+                            vec![
+                                placement.app_name.clone(),
+                                self.workspace(&placement.workspace),
+                                format_seconds(placement.seconds),
+                            ]
+                        })
+                        .collect(),
+                );
+                println!();
+                print_table(
+                    &["RULE", "ACTIONS", "FAILED"],
+                    report
+                        .rules
+                        .iter()
+                        .map(|rule| {
+                            vec![
+                                rule.rule.clone(),
+                                rule.actions.to_string(),
+                                rule.failed.to_string(),
+                            ]
+                        })
+                        .collect(),
+                );
+            }
+            OutputFormat::Plain => {
+                println!("Since {}", since.format("%Y-%m-%d %H:%M:%S"));
+
+                println!("\nTime on workspaces:");
+                let mut apps: Vec<&str> = Vec::new();
+                for placement in &report.placements {
+                    if !apps.contains(&placement.app_name.as_str()) {
+                        apps.push(&placement.app_name);
+                    }
+                }
+                if apps.is_empty() {
+                    println!("  No windows seen yet");
+                }
+                let mut rows = Vec::new();
+                for app in apps {
+                    let placements: Vec<_> = report
+                        .placements
+                        .iter()
+                        .filter(|placement| placement.app_name == app)
+                        .collect();
+                    let total: u64 = placements.iter().map(|placement| placement.seconds).sum();
+                    for (i, placement) in placements.iter().enumerate() {
+                        let share = placement.seconds * 100 / total.max(1);
+                        rows.push(vec![
+                            format!("  {}", if i == 0 { app } else { "" }),
+                            self.workspace(&placement.workspace),
+                            format_seconds(placement.seconds),
+                            self.paint(DIM, &format!("{share}%")),
+                        ]);
+                    }
+                }
+                for line in align(rows) {
+                    println!("{line}");
+                }
+
+                println!("\nRule actions:");
+                if report.rules.is_empty() {
+                    println!("  No actions recorded");
+                }
+                let rows = report
+                    .rules
+                    .iter()
+                    .map(|rule| {
+                        let mut row = vec![format!("  {}", rule.rule), rule.actions.to_string()];
+                        if rule.failed > 0 {
+                            row.push(self.paint(RED, &format!("{} failed", rule.failed)));
+                        }
+                        row
+                    })
+                    .collect();
+                for line in align(rows) {
+                    println!("{line}");
+                }
+            }
+        }
+    }
+
+    fn undone(&self, results: &[ActionResult]) {
+        if self.format != OutputFormat::Plain {
+            return self.results(results, false);
+        }
+        if results.is_empty() {
+            println!("Nothing to undo");
+        } else {
+            println!("Undone:");
+            for result in results {
+                println!("  {}", self.result_line(result, false));
+            }
+        }
+    }
+
+    fn explanations(&self, explanations: &[WindowExplanation]) {
+        match self.format {
+            OutputFormat::Json => self.json(&explanations),
+            OutputFormat::Table => print_table(
+                &["WINDOW", "RULE", "MATCHED", "DETAILS"],
+                explanations
+                    .iter()
+                    .flat_map(|explanation| {
+                        let window = &explanation.window;
+                        explanation.rules.iter().map(move |rule| {
+                            vec![
+                                format!("{} ({})", window.app_name, window.window_id),
+                                rule.rule.clone(),
+                                if rule.matched {
+                                    self.paint(GREEN, "yes")
+                                } else {
+                                    self.paint(DIM, "no")
+                                },
+                                rule.reason
+                                    .clone()
+                                    .or_else(|| rule.action.clone())
+                                    .unwrap_or_default(),
+                            ]
+                        })
+                    })
+                    .collect(),
+            ),
+            OutputFormat::Plain => {
+                for explanation in explanations {
+                    let window = &explanation.window;
+                    println!(
+                        "[{}] {} (ID: {}) - {}",
+                        self.workspace(&window.workspace),
+                        window.app_name,
+                        window.window_id,
+                        window.window_title
+                    );
+                    if let Some(record) = &explanation.record {
+                        println!(
+                            "  first seen  {}",
+                            record
+                                .first_seen
+                                .with_timezone(&chrono::Local)
+                                .format("%H:%M:%S")
+                        );
+                        println!("  workspaces  {}", record.workspaces.join(" -> "));
+                        if record.titles.len() > 1 {
+                            println!("  titles      {}", record.titles.join(" -> "));
+                        }
+                    }
+                    for rule in &explanation.rules {
+                        match (rule.matched, &rule.action, &rule.reason) {
+                            (true, Some(action), _) => println!(
+                                "  {}     '{}' -> {action}",
+                                self.paint(GREEN, "matched"),
+                                rule.rule
+                            ),
+                            (_, _, Some(reason)) => {
+                                println!("  no match    '{}': {reason}", rule.rule)
+                            }
+                            _ => println!("  no match    '{}'", rule.rule),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn log_lines(&self, lines: &[String]) {
+        match self.format {
+            OutputFormat::Json => self.json(&lines),
+            OutputFormat::Plain | OutputFormat::Table => {
+                for line in lines {
+                    println!("{line}");
+                }
+            }
+        }
+    }
+
+    fn event(&self, event: &Event) {
+        if self.format == OutputFormat::Json {
+            match serde_json::to_string(event) {
+                Ok(json) => println!("{json}"),
+                Err(e) => eprintln!("Failed to serialize event: {e}"),
+            }
+            return;
+        }
+
+        let describe = |window: &WindowInfo| {
+            format!(
+                "{} (ID: {}) - {}",
+                window.app_name, window.window_id, window.window_title
+            )
+        };
+        match event {
+            Event::WindowCreated { window } => {
+                println!("opened   [{}] {}", window.workspace, describe(window))
+            }
+            Event::WindowClosed { window } => {
+                println!("closed   [{}] {}", window.workspace, describe(window))
+            }
+            Event::WindowMoved { window, from } => {
+                println!(
+                    "moved    [{from} -> {}] {}",
+                    window.workspace,
+                    describe(window)
+                )
+            }
+            Event::WorkspaceFocused { workspace, .. } => println!("focus    [{workspace}]"),
+            Event::RuleFired { result } | Event::ActionFailed { result } => {
+                println!("rule     {}", self.result_line(result, false))
+            }
+            Event::RuleRateExceeded { rule, per_minute } => println!(
+                "rule     {}",
+                self.paint(
+                    YELLOW,
+                    &format!("'{rule}' fired {per_minute} times within a minute")
+                )
+            ),
+            Event::ConfigReloaded {
+                rules,
+                warnings,
+                errors,
+            } => {
+                println!("config   reloaded, {rules} rules");
+                for error in errors {
+                    println!("config   {}", self.paint(RED, &format!("error: {error}")));
+                }
+                for warning in warnings {
+                    println!(
+                        "config   {}",
+                        self.paint(YELLOW, &format!("warning: {warning}"))
+                    );
+                }
+            }
+            Event::ConfigReloadFailed { error } => {
+                println!(
+                    "config   {}",
+                    self.paint(RED, &format!("reload failed: {error}"))
+                )
+            }
+            Event::WindowManagerRestarted { backend, pid } => match pid {
+                Some(pid) => println!("restart  {backend} restarted (pid {pid})"),
+                None => println!("restart  {backend} restarted"),
+            },
+            Event::Log { message } => println!("log      {message}"),
+        }
+    }
+
+    fn diagnostics(&self, path: &str, diagnostics: &[Diagnostic]) {
+        match self.format {
+            OutputFormat::Json => self.json(&diagnostics),
+            OutputFormat::Table => print_table(
+                &["LINE", "SEVERITY", "RULE", "FIELD", "MESSAGE"],
+                diagnostics
+                    .iter()
+                    .map(|diagnostic| {
+                        vec![
+                            diagnostic
+                                .line
+                                .map_or("-".to_string(), |line| line.to_string()),
+                            match diagnostic.severity {
+                                Severity::Error => self.paint(RED, "error"),
+                                Severity::Warning => self.paint(YELLOW, "warning"),
+                            },
+                            diagnostic.rule.clone().unwrap_or_default(),
+                            diagnostic.field.clone().unwrap_or_default(),
+                            diagnostic.message.clone(),
+                        ]
+                    })
+                    .collect(),
+            ),
+            OutputFormat::Plain => {
+                for diagnostic in diagnostics {
+                    let text = diagnostic.to_string();
+                    let text = match diagnostic.severity {
+                        Severity::Error => self.paint(RED, &text),
+                        Severity::Warning => self.paint(YELLOW, &text),
+                    };
+                    match diagnostic.line {
+                        Some(line) => println!("{path}:{line}: {text}"),
+                        None => println!("{path}: {text}"),
+                    }
+                }
+                let errors = diagnostics
+                    .iter()
+                    .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                    .count();
+                let warnings = diagnostics.len() - errors;
+                if diagnostics.is_empty() {
+                    println!("{path}: config is valid");
+                } else {
+                    println!("{path}: {errors} errors, {warnings} warnings");
+                }
+            }
+        }
+    }
+
+    fn paused(&self, paused: bool) {
+        match self.format {
+            OutputFormat::Json => self.json(&serde_json::json!({ "paused": paused })),
+            OutputFormat::Plain | OutputFormat::Table if paused => {
+                println!("{}", self.paint(YELLOW, "Rules paused"))
+            }
+            OutputFormat::Plain | OutputFormat::Table => {
+                println!("{}", self.paint(GREEN, "Rules resumed"))
+            }
+        }
+    }
+
+    fn suggestions(&self, rules: &[config::Rule]) {
+        #[derive(Serialize)]
+        struct Rules<'a> {
+            rules: &'a [config::Rule],
+        }
+
+        if self.format == OutputFormat::Json {
+            return self.json(&rules);
+        }
+        if rules.is_empty() {
+            eprintln!(
+                "No rules to suggest, every app is either spread out or already moved by a rule"
+            );
+            return;
+        }
+        match toml::to_string_pretty(&Rules { rules }) {
+            Ok(toml) => print!("{toml}"),
+            Err(e) => eprintln!("Failed to serialize rules: {e}"),
+        }
+    }
+
+    fn success(&self) {
+        match self.format {
+            OutputFormat::Json => self.json(&serde_json::json!({ "success": true })),
+            OutputFormat::Plain | OutputFormat::Table => {
+                println!("Command executed successfully")
+            }
+        }
+    }
+
+    fn error(&self, message: &str) {
+        match self.format {
+            OutputFormat::Json => self.json(&serde_json::json!({ "error": message })),
+            OutputFormat::Plain | OutputFormat::Table => {
+                eprintln!("{}", self.paint(RED, &format!("Service error: {message}")))
+            }
+        }
+    }
+}
+
+/// Parses an age like `90s`, `30m`, `1h` or `2d`.
+fn parse_age(age: &str) -> Result<Duration, String> {
+    let split = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+    let (amount, unit) = age.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("Expected a number followed by s, m, h or d, got '{age}'"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("Unknown unit '{unit}', expected s, m, h or d")),
+    };
+    let seconds = amount
+        .checked_mul(seconds)
+        .ok_or_else(|| format!("Age '{age}' is too long"))?;
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Formats a number of seconds like `45s`, `12m` or `3h 20m`.
+fn format_seconds(seconds: u64) -> String {
+    match seconds {
+        0..60 => format!("{seconds}s"),
+        60..3600 => format!("{}m", seconds / 60),
+        _ => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+/// Short type name and the type-specific details of a rule.
+fn describe_rule(rule: &config::Rule) -> (&'static str, String) {
+    match &rule.rule_type {
+        config::RuleType::Window {
+            condition, action, ..
+        } => ("window", format!("{condition} -> {action}")),
+        config::RuleType::EmptyWorkspace {
+            workspace, command, ..
+        } => (
+            "empty-workspace",
+            format!("workspace {workspace} -> {command}"),
+        ),
+        config::RuleType::Startup { command } => ("startup", command.clone()),
+        config::RuleType::Script { script } => {
+            ("script", format!("{} lines", script.lines().count()))
+        }
+    }
+}
+
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut lines = vec![headers.iter().map(|header| header.to_string()).collect()];
+    lines.extend(rows);
+    for line in align(lines) {
+        println!("{line}");
+    }
+}
+
+/// Pads every column but the last to its widest cell. Colored cells are measured
+/// without their escape codes.
+fn align(rows: Vec<Vec<String>>) -> Vec<String> {
+    let mut widths: Vec<usize> = Vec::new();
+    for row in &rows {
+        widths.resize(widths.len().max(row.len()), 0);
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(visible_width(cell));
+        }
+    }
+
+    rows.iter()
+        .map(|cells| {
+            let mut line = String::new();
+            for (index, (cell, width)) in cells.iter().zip(&widths).enumerate() {
+                line.push_str(cell);
+                if index + 1 < cells.len() {
+                    line.push_str(&" ".repeat(width - visible_width(cell) + 2));
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip to the end of the SGR sequence
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+/// Answers `command` from the config file and the window manager directly, for when
+/// the service isn't running. `None` for commands that need the service.
+async fn fallback_direct(
+    output: &Output,
+    config_path: Option<&str>,
+    command: &Command,
+) -> Option<Exit> {
+    match command {
+        Command::Windows { .. } | Command::Config { .. } => {}
+        Command::Evaluate { dry_run: true, .. } => {}
+        _ => return None,
+    }
+    eprintln!("Service unavailable, falling back to direct queries...");
+
+    let config = config::load_config_from_path(config_path);
+    let service_config = config
+        .as_ref()
+        .map(|config| config.service.clone())
+        .unwrap_or_default();
+    aerospace::set_binary(service_config.aerospace_bin.as_deref());
+    let wm = backend::for_config(&service_config);
+
+    let exit = match (command, &config) {
+        (Command::Windows { filter }, _) => {
+            if output.format == OutputFormat::Plain {
+                match &config {
+                    Some(config) => output.rules(config),
+                    None => println!("No config file found, running with defaults"),
+                }
+                println!();
+            }
+
+            match wm.list_windows() {
+                Ok(windows) => {
+                    output.windows(&filter.filter().apply(windows));
+                    Exit::Success
+                }
+                Err(e) => {
+                    eprintln!("Failed to list windows: {e}");
+                    Exit::Unreachable
+                }
+            }
+        }
+        (_, None) => {
+            eprintln!("No config file found");
+            Exit::InvalidConfig
+        }
+        (Command::Config { command }, Some(config)) => {
+            match command {
+                Some(ConfigCommand::Show { effective: true }) => output.config(&config.effective()),
+                Some(ConfigCommand::Show { effective: false }) => output.config(config),
+                None => output.rules(config),
+            }
+            Exit::Success
+        }
+        (Command::Evaluate { workspace, .. }, Some(config)) => {
+            let Some(workspace) = workspace.clone().or_else(|| wm.focused_workspace()) else {
+                eprintln!("{} reported no focused workspace", wm.name());
+                return Some(Exit::ActionsFailed);
+            };
+            let windows = match wm.list_windows_in_workspace(&workspace) {
+                Ok(windows) => windows,
+                Err(e) => {
+                    eprintln!("Failed to list windows: {e}");
+                    return Some(Exit::ActionsFailed);
+                }
+            };
+            let context = rules::EvalContext {
+                focused_workspace: wm.focused_workspace(),
+                ..rules::EvalContext::new(rules::Trigger::Workspace(&workspace))
+            };
+            match rules::evaluate_rules_for_workspace(
+                windows,
+                config,
+                &rules::RuleIndex::new(config),
+                &context,
+                wm.as_ref(),
+                true,
+            ) {
+                Ok(results) => {
+                    output.results(&results, true);
+                    Exit::Success
+                }
+                Err(e) => {
+                    eprintln!("Rule evaluation failed: {e}");
+                    Exit::InvalidConfig
+                }
+            }
+        }
+        _ => unreachable!("commands without a fallback return early"),
+    };
+    Some(exit)
+}
+
+/// Process exit codes, so shell hooks can branch on the outcome.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Exit {
+    Success = 0,
+    /// The service isn't running or stopped responding.
+    Unreachable = 1,
+    /// Rules were evaluated but at least one action failed.
+    ActionsFailed = 2,
+    /// The config is missing or contains a rule that can't be evaluated.
+    InvalidConfig = 3,
+    /// Invalid arguments, including names of rules or windows that don't exist.
+    BadArguments = 4,
+}
+
+impl From<ErrorKind> for Exit {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::InvalidConfig => Exit::InvalidConfig,
+            ErrorKind::InvalidRequest => Exit::BadArguments,
+            ErrorKind::Failed => Exit::ActionsFailed,
+        }
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit as u8)
+    }
+}
+
+/// Runs a synthetic window through the rules of the config file.
+fn test_window(output: &Output, config_path: Option<&str>, window: &WindowInfo) -> Exit {
+    let Some(config) = config::load_config_from_path(config_path) else {
+        eprintln!("No config file found");
+        return Exit::InvalidConfig;
+    };
+    match rules::plan(std::slice::from_ref(window), &config, Trigger::Windows) {
+        Ok(planned) => {
+            let results: Vec<_> = planned.iter().map(PlannedAction::to_result).collect();
+            output.results(&results, true);
+            Exit::Success
+        }
+        Err(e) => {
+            eprintln!("Rule evaluation failed: {e}");
+            Exit::InvalidConfig
+        }
+    }
+}
+
+/// Every window the service knows about, or how to exit if it couldn't tell.
+async fn all_windows(output: &Output) -> Result<Vec<WindowInfo>, Exit> {
+    let request = Request::GetWindows {
+        filter: Default::default(),
+    };
+    match query_service(&request).await {
+        Ok(Response::Windows(windows)) => Ok(windows),
+        Ok(Response::Error { kind, message }) => {
+            output.error(&message);
+            Err(kind.into())
+        }
+        Ok(_) => {
+            output.error("Unexpected response to window listing");
+            Err(Exit::ActionsFailed)
+        }
+        Err(e) => Err(unreachable(e)),
+    }
+}
+
+/// Suggests rules for the service's current windows that its config doesn't cover yet.
+async fn suggest_rules(output: &Output) -> Exit {
+    let windows = match all_windows(output).await {
+        Ok(windows) => windows,
+        Err(exit) => return exit,
+    };
+    // Without a loaded config every app gets a suggestion
+    let config = match query_service(&Request::GetConfig).await {
+        Ok(Response::Config(config)) => Some(*config),
+        _ => None,
+    };
+
+    output.suggestions(&suggest::suggest_rules(&windows, config.as_ref()));
+    Exit::Success
+}
+
+/// Saves sessions on this machine; restoring one sends it to the service.
+async fn session_command(output: &Output, command: &SessionCommand) -> Exit {
+    match command {
+        SessionCommand::Save { name } => {
+            let windows = match all_windows(output).await {
+                Ok(windows) => windows,
+                Err(exit) => return exit,
+            };
+            match session::save(name, &Session::capture(&windows)) {
+                Ok(path) => {
+                    if output.format == OutputFormat::Plain {
+                        println!(
+                            "Saved {} windows as session '{name}' to {}",
+                            windows.len(),
+                            path.display()
+                        );
+                    } else {
+                        output.success();
+                    }
+                    Exit::Success
+                }
+                Err(e) => {
+                    eprintln!("{e}");
+                    Exit::ActionsFailed
+                }
+            }
+        }
+        SessionCommand::Restore { name, dry_run } => {
+            let session = match session::load(name) {
+                Ok(session) => session,
+                Err(e) => {
+                    eprintln!("{e}");
+                    return Exit::BadArguments;
+                }
+            };
+            let request = Request::RestoreSession {
+                name: name.clone(),
+                session,
+                dry_run: *dry_run,
+            };
+            match query_service(&request).await {
+                Ok(Response::RulesEvaluated {
+                    results,
+                    partial_failure,
+                }) => {
+                    if results.is_empty() && output.format == OutputFormat::Plain {
+                        println!("All windows are where session '{name}' had them");
+                    } else {
+                        output.results(&results, *dry_run);
+                    }
+                    if partial_failure {
+                        if output.format == OutputFormat::Plain {
+                            let failed = results.iter().filter(|result| !result.success).count();
+                            eprintln!("{failed} of {} moves failed", results.len());
+                        }
+                        Exit::ActionsFailed
+                    } else {
+                        Exit::Success
+                    }
+                }
+                Ok(Response::Paused(_)) => {
+                    output.error("Rules are paused, the session wasn't restored");
+                    Exit::ActionsFailed
+                }
+                Ok(Response::Error { kind, message }) => {
+                    output.error(&message);
+                    kind.into()
+                }
+                Ok(_) => {
+                    output.error("Unexpected response to session restore");
+                    Exit::ActionsFailed
+                }
+                Err(e) => unreachable(e),
+            }
+        }
+    }
+}
+
+/// Checks the config file, printing a diagnostic per problem found.
+fn validate_config(output: &Output, config_path: Option<&str>) -> Exit {
+    let Some(path) = config::config_file_path(config_path) else {
+        eprintln!("No config file found");
+        return Exit::InvalidConfig;
+    };
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Failed to read {}: {e}", path.display());
+            return Exit::InvalidConfig;
+        }
+    };
+
+    // Only ask the window manager for its workspaces once the file itself parses
+    let workspaces = toml::from_str::<config::Config>(&source)
+        .map(|config| backend::for_config(&config.service).workspaces())
+        .unwrap_or_default();
+    let diagnostics = validate::validate_config_with_workspaces(&source, &workspaces);
+    output.diagnostics(&path.display().to_string(), &diagnostics);
+
+    if diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+    {
+        Exit::InvalidConfig
+    } else {
+        Exit::Success
+    }
+}
+
+/// Reports an error from talking to the service, which means it's unreachable.
+fn unreachable(e: impl std::fmt::Display) -> Exit {
+    eprintln!("Failed to connect to service: {e}");
+    Exit::Unreachable
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            let _ = e.print();
+            // --help and --version are reported as errors too
+            return if e.use_stderr() {
+                Exit::BadArguments
+            } else {
+                Exit::Success
+            }
+            .into();
+        }
+    };
+    run(args).await.into()
+}
+
+async fn run(args: Args) -> Exit {
+    // Commands that evaluate rules locally would otherwise mix the engine's log into their output
+    logging::quiet();
+    let mut command = args.command.unwrap_or(Command::Windows {
+        filter: WindowFilterArgs::default(),
+    });
+
+    let format = if args.json {
+        OutputFormat::Json
+    } else {
+        args.output
+    };
+    let output = Output {
+        format,
+        color: format != OutputFormat::Json
+            && !args.no_color
+            && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            && std::io::stdout().is_terminal(),
+    };
+
+    if let Command::Evaluate {
+        workspace: workspace @ None,
+        ..
+    } = &mut command
+    {
+        match query_service(&Request::GetFocusedWorkspace).await {
+            Ok(Response::FocusedWorkspace(focused)) => *workspace = Some(focused),
+            Ok(Response::Error { kind, message }) => {
+                output.error(&message);
+                return kind.into();
+            }
+            Ok(_) => {
+                output.error("Unexpected response to focused workspace query");
+                return Exit::ActionsFailed;
+            }
+            Err(e) => {
+                let exit = unreachable(e);
+                return fallback_direct(&output, args.config.as_deref(), &command)
+                    .await
+                    .unwrap_or(exit);
+            }
+        }
+    }
+
+    let Some(request) = command.request() else {
+        return match &command {
+            Command::Test {
+                app,
+                title,
+                workspace,
+                monitor,
+                window_id,
+                width,
+                height,
+            } => {
+                let window = WindowInfo {
+                    app_name: app.clone(),
+                    window_id: *window_id,
+                    window_title: title.clone(),
+                    workspace: workspace.clone(),
+                    monitor: monitor.clone(),
+                    frame: width.zip(*height).map(|(width, height)| WindowFrame {
+                        x: 0.0,
+                        y: 0.0,
+                        width,
+                        height,
+                    }),
+                    focused: false,
+                };
+                test_window(&output, args.config.as_deref(), &window)
+            }
+            Command::Suggest => suggest_rules(&output).await,
+            Command::Session { command } => session_command(&output, command).await,
+            Command::Validate => validate_config(&output, args.config.as_deref()),
+            Command::Watch { logs } => subscribe(|event| {
+                if *logs || !matches!(event, Event::Log { .. }) {
+                    output.event(&event);
+                }
+            })
+            .await
+            .map_or_else(unreachable, |()| Exit::Success),
+            Command::SetupHooks { print } => setup::run(*print).map_or_else(
+                |e| {
+                    eprintln!("{e}");
+                    Exit::InvalidConfig
+                },
+                |()| Exit::Success,
+            ),
+            Command::Service { command } => daemon::run(*command, args.config.as_deref())
+                .await
+                .map_or_else(
+                    |e| {
+                        eprintln!("{e}");
+                        Exit::Unreachable
+                    },
+                    |()| Exit::Success,
+                ),
+            #[cfg(feature = "tui")]
+            Command::Tui => tui::run()
+                .await
+                .map_or_else(unreachable, |()| Exit::Success),
+            #[cfg(all(target_os = "macos", feature = "menubar"))]
+            Command::Menubar => menubar::run(),
+            _ => Exit::Success,
+        };
+    };
+
+    let response = match query_service(&request).await {
+        Ok(response) => response,
+        Err(e) => {
+            let exit = unreachable(e);
+            return fallback_direct(&output, args.config.as_deref(), &command)
+                .await
+                .unwrap_or(exit);
+        }
+    };
+
+    match response {
+        Response::Windows(windows) if matches!(command, Command::Raycast { .. }) => {
+            output.json(&raycast::windows(&windows))
+        }
+        Response::Windows(windows) => output.windows(&windows),
+        Response::Config(config) => match command {
+            Command::Config {
+                command: Some(ConfigCommand::Show { .. }),
+            } => output.config(&config),
+            _ => output.rules(&config),
+        },
+        Response::Rules(statuses) if matches!(command, Command::Raycast { .. }) => {
+            output.json(&raycast::rules(&statuses))
+        }
+        Response::Rules(statuses) => output.rule_statuses(&statuses),
+        Response::Success => output.success(),
+        Response::RulesEvaluated {
+            results,
+            partial_failure,
+        } => {
+            let dry_run = matches!(
+                command,
+                Command::Evaluate { dry_run: true, .. }
+                    | Command::HandleWindowDetected { dry_run: true, .. }
+                    | Command::Gather { dry_run: true, .. }
+                    | Command::Bootstrap { dry_run: true, .. }
+            );
+            if results.is_empty()
+                && output.format == OutputFormat::Plain
+                && matches!(command, Command::Gather { .. })
+            {
+                println!("All windows are on their assigned workspaces");
+            } else if results.is_empty()
+                && output.format == OutputFormat::Plain
+                && matches!(command, Command::Bootstrap { .. })
+            {
+                println!("All workspaces are set up");
+            } else {
+                output.results(&results, dry_run);
+            }
+            if partial_failure {
+                if output.format == OutputFormat::Plain {
+                    let failed = results.iter().filter(|result| !result.success).count();
+                    eprintln!("{failed} of {} actions failed", results.len());
+                }
+                return Exit::ActionsFailed;
+            }
+        }
+        Response::Explained(explanations) => output.explanations(&explanations),
+        Response::Logs(lines) => {
+            output.log_lines(&lines);
+            if matches!(command, Command::Logs { follow: true, .. }) {
+                let followed = subscribe(|event| {
+                    if let Event::Log { message } = event {
+                        match output.format {
+                            OutputFormat::Json => println!("{}", serde_json::json!(message)),
+                            OutputFormat::Plain | OutputFormat::Table => println!("{message}"),
+                        }
+                    }
+                })
+                .await;
+                if let Err(e) = followed {
+                    return unreachable(e);
+                }
+            }
+        }
+        Response::PinnedWindows(window_ids) => output.json(&window_ids),
+        // No command asks for changes, which only pollers keeping their own copy want
+        response @ Response::WindowsSince { .. } => output.json(&response),
+        Response::Paused(paused) => output.paused(paused),
+        Response::History(entries) => output.history(&entries),
+        Response::FocusedWorkspace(workspace) => match output.format {
+            OutputFormat::Json => output.json(&workspace),
+            OutputFormat::Plain | OutputFormat::Table => println!("{workspace}"),
+        },
+        Response::Report(report) => output.report(&report),
+        Response::Pong(pong) => match output.format {
+            OutputFormat::Json => output.json(&pong),
+            OutputFormat::Plain | OutputFormat::Table => match pong.windows_age_ms {
+                Some(age) => println!("pong, windows listed {age} ms ago"),
+                None => println!("pong, windows not listed yet"),
+            },
+        },
+        Response::Undone(results) => {
+            output.undone(&results);
+            if results.iter().any(|result| !result.success) {
+                return Exit::ActionsFailed;
+            }
+        }
+        Response::Error { kind, message } => {
+            output.error(&message);
+            return kind.into();
+        }
+    }
+
+    Exit::Success
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_age("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_age("2d"), Ok(Duration::from_secs(2 * 24 * 60 * 60)));
+        assert!(parse_age("1w").is_err());
+        assert!(parse_age("h").is_err());
+        assert_eq!(
+            parse_age("999999999999999d"),
+            Err("Age '999999999999999d' is too long".to_string())
+        );
+    }
+}
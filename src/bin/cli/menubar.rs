@@ -0,0 +1,204 @@
+//! Menu bar item for the service: whether it's running or paused, the actions its
+//! rules performed recently, and menu items to pause, resume and reload it.
+
+use crate::{query_service, subscribe};
+use aerospace_rules::rules::ActionResult;
+use aerospace_rules::{Event as ServiceEvent, Request, Response};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::time::Duration;
+use tao::event::{Event, StartCause};
+use tao::event_loop::{ControlFlow, EventLoopBuilder, EventLoopProxy};
+use tao::platform::macos::{ActivationPolicy, EventLoopExtMacOS};
+use tokio::runtime::Handle;
+use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
+use tray_icon::{TrayIcon, TrayIconBuilder};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const RECENT_ACTIONS: usize = 10;
+
+/// What the event loop learns from the service and the menu.
+#[derive(Debug)]
+enum Update {
+    /// `None` while the service isn't reachable.
+    Status(Option<Status>),
+    Action(ActionResult),
+    Menu(MenuEvent),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Status {
+    paused: bool,
+    rules: usize,
+}
+
+struct Ui {
+    tray: TrayIcon,
+    status: MenuItem,
+    recent: Submenu,
+    toggle: MenuItem,
+    reload: MenuItem,
+    quit: MenuItem,
+}
+
+impl Ui {
+    fn new() -> Result<Self, Box<dyn Error>> {
+        let status = MenuItem::new("Connecting…", false, None);
+        let recent = Submenu::new("Recent actions", false);
+        let toggle = MenuItem::new("Pause rules", false, None);
+        let reload = MenuItem::new("Reload config", false, None);
+        let quit = MenuItem::new("Quit", true, None);
+
+        let menu = Menu::new();
+        let separator = PredefinedMenuItem::separator();
+        menu.append_items(&[
+            &status, &recent, &separator, &toggle, &reload, &separator, &quit,
+        ])?;
+        let tray = TrayIconBuilder::new()
+            .with_title("AR")
+            .with_tooltip("aerospace-rules")
+            .with_menu(Box::new(menu))
+            .build()?;
+
+        Ok(Self {
+            tray,
+            status,
+            recent,
+            toggle,
+            reload,
+            quit,
+        })
+    }
+
+    fn show_status(&self, status: Option<Status>) {
+        let (title, text) = match status {
+            None => ("AR ✕", "Service not running".to_string()),
+            Some(Status { paused: true, .. }) => ("AR ⏸", "Rules paused".to_string()),
+            Some(Status { rules, .. }) => ("AR", format!("Service running, {rules} rules")),
+        };
+        self.tray.set_title(Some(title));
+        self.status.set_text(text);
+        self.toggle.set_enabled(status.is_some());
+        self.toggle
+            .set_text(if status.is_some_and(|status| status.paused) {
+                "Resume rules"
+            } else {
+                "Pause rules"
+            });
+        self.reload.set_enabled(status.is_some());
+    }
+
+    /// Lists `actions`, most recent first.
+    fn show_actions(&self, actions: &VecDeque<ActionResult>) {
+        while self.recent.remove_at(0).is_some() {}
+        for action in actions {
+            let _ = self
+                .recent
+                .append(&MenuItem::new(action.to_string(), false, None));
+        }
+        self.recent.set_enabled(!actions.is_empty());
+    }
+}
+
+/// Shows the menu bar item until it's quit.
+pub fn run() -> ! {
+    let mut event_loop = EventLoopBuilder::<Update>::with_user_event().build();
+    // No Dock icon, just the menu bar item
+    event_loop.set_activation_policy(ActivationPolicy::Accessory);
+
+    let proxy = event_loop.create_proxy();
+    let runtime = Handle::current();
+    let menu_proxy = proxy.clone();
+    MenuEvent::set_event_handler(Some(move |event| {
+        let _ = menu_proxy.send_event(Update::Menu(event));
+    }));
+    runtime.spawn(poll_status(proxy.clone()));
+    runtime.spawn(watch_actions(proxy.clone()));
+
+    let mut ui = None;
+    let mut actions = VecDeque::new();
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            // The item can only be created once the application is running
+            Event::NewEvents(StartCause::Init) => match Ui::new() {
+                Ok(created) => ui = Some(created),
+                Err(e) => {
+                    eprintln!("Failed to create the menu bar item: {e}");
+                    *control_flow = ControlFlow::Exit;
+                }
+            },
+            Event::UserEvent(update) => {
+                let Some(ui) = &ui else {
+                    return;
+                };
+                match update {
+                    Update::Status(status) => ui.show_status(status),
+                    Update::Action(action) => {
+                        actions.push_front(action);
+                        actions.truncate(RECENT_ACTIONS);
+                        ui.show_actions(&actions);
+                    }
+                    Update::Menu(event) if event.id == *ui.quit.id() => {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    Update::Menu(event) => {
+                        let request = if event.id == *ui.toggle.id() {
+                            Request::TogglePaused
+                        } else if event.id == *ui.reload.id() {
+                            Request::Reload
+                        } else {
+                            return;
+                        };
+                        let proxy = proxy.clone();
+                        runtime.spawn(async move {
+                            if let Ok(Response::Error { message, .. }) =
+                                query_service(&request).await
+                            {
+                                eprintln!("{message}");
+                            }
+                            send_status(&proxy).await;
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    })
+}
+
+async fn send_status(proxy: &EventLoopProxy<Update>) {
+    let paused = query_service(&Request::GetPaused).await;
+    let rules = query_service(&Request::GetRules).await;
+    let status = match (paused, rules) {
+        (Ok(Response::Paused(paused)), Ok(Response::Rules(rules))) => Some(Status {
+            paused,
+            rules: rules.len(),
+        }),
+        _ => None,
+    };
+    let _ = proxy.send_event(Update::Status(status));
+}
+
+async fn poll_status(proxy: EventLoopProxy<Update>) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        send_status(&proxy).await;
+    }
+}
+
+/// Forwards the actions rules perform, resubscribing whenever the service went away.
+async fn watch_actions(proxy: EventLoopProxy<Update>) {
+    loop {
+        let _ = subscribe(|event| {
+            if let ServiceEvent::RuleFired { result } | ServiceEvent::ActionFailed { result } =
+                event
+            {
+                let _ = proxy.send_event(Update::Action(result));
+            }
+        })
+        .await;
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
@@ -0,0 +1,72 @@
+//! `aerospace-rules raycast …`: windows and rules as props of Raycast `List.Item`s,
+//! so an extension can render them as they are:
+//!
+//! ```tsx
+//! const items: List.Item.Props[] = JSON.parse(execSync("aerospace-rules raycast windows").toString());
+//! return <List>{items.map((item) => <List.Item key={item.id} {...item} />)}</List>;
+//! ```
+
+use crate::describe_rule;
+use aerospace_rules::{RuleStatus, WindowInfo};
+use serde::Serialize;
+
+/// The props of a `List.Item`.
+#[derive(Serialize)]
+pub struct Item {
+    id: String,
+    title: String,
+    subtitle: String,
+    accessories: Vec<Accessory>,
+    keywords: Vec<String>,
+}
+
+/// An entry of `List.Item`'s `accessories`.
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Accessory {
+    Text(String),
+    Tag(String),
+}
+
+pub fn windows(windows: &[WindowInfo]) -> Vec<Item> {
+    windows
+        .iter()
+        .map(|window| {
+            let mut accessories = vec![Accessory::Tag(format!("Workspace {}", window.workspace))];
+            if window.focused {
+                accessories.insert(0, Accessory::Text("Focused".to_string()));
+            }
+            Item {
+                id: window.window_id.to_string(),
+                title: window.app_name.clone(),
+                subtitle: window.window_title.clone(),
+                accessories,
+                keywords: vec![window.workspace.clone(), window.window_id.to_string()],
+            }
+        })
+        .collect()
+}
+
+pub fn rules(statuses: &[RuleStatus]) -> Vec<Item> {
+    statuses
+        .iter()
+        .map(|status| {
+            let rule = &status.rule;
+            let (kind, description) = describe_rule(rule);
+            let mut accessories: Vec<_> = rule.tags.iter().cloned().map(Accessory::Tag).collect();
+            if !rule.enabled {
+                accessories.push(Accessory::Tag("Disabled".to_string()));
+            }
+            accessories.push(Accessory::Text(format!("{} hits", status.hits)));
+            let mut keywords = rule.tags.clone();
+            keywords.push(kind.to_string());
+            Item {
+                id: rule.name.clone(),
+                title: rule.name.clone(),
+                subtitle: description,
+                accessories,
+                keywords,
+            }
+        })
+        .collect()
+}
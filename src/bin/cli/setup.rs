@@ -0,0 +1,60 @@
+//! Wiring aerospace up to call this CLI whenever the focused workspace changes, the
+//! one step the rules depend on that isn't done by installing them.
+
+use aerospace_rules::aerospace::{self, WORKSPACE_CHANGE_HOOK};
+use std::error::Error;
+use std::os::unix::fs::PermissionsExt;
+
+/// Adds the workspace change hook to aerospace's config, or only prints it.
+pub fn run(print: bool) -> Result<(), Box<dyn Error>> {
+    let binary = cli_binary()?;
+    let command = [binary.as_str(), "on-workspace-change"];
+    let snippet = aerospace::with_workspace_change_hook("", &command)?.unwrap_or_default();
+    let snippet = snippet.trim_end();
+    if binary.contains("/target/debug/") || binary.contains("/target/release/") {
+        eprintln!("Note: {binary} is a build output; the hook breaks when it's cleaned");
+    }
+    if print {
+        println!("{snippet}");
+        return Ok(());
+    }
+
+    let path = aerospace::config_path().ok_or("HOME is not set")?;
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read {}: {e}", path.display()).into()),
+    };
+    let edited = aerospace::with_workspace_change_hook(&source, &command).map_err(|e| {
+        format!(
+            "{e} in {}; combine it with this by hand:\n{snippet}",
+            path.display()
+        )
+    })?;
+    match edited {
+        None => println!(
+            "{WORKSPACE_CHANGE_HOOK} in {} already runs aerospace-rules",
+            path.display()
+        ),
+        Some(edited) => {
+            std::fs::write(&path, edited)
+                .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+            println!("Added to {}:\n{snippet}", path.display());
+            println!("Run `aerospace reload-config` to apply it");
+        }
+    }
+    Ok(())
+}
+
+/// Absolute path of this binary, checked to be executable, since aerospace runs
+/// hooks without the shell's `PATH`.
+fn cli_binary() -> Result<String, Box<dyn Error>> {
+    let exe = std::env::current_exe()?.canonicalize()?;
+    let metadata = std::fs::metadata(&exe)?;
+    if !metadata.is_file() || metadata.permissions().mode() & 0o111 == 0 {
+        return Err(format!("{} is not an executable file", exe.display()).into());
+    }
+    exe.to_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("Non UTF-8 path: {}", exe.display()).into())
+}
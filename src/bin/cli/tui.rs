@@ -0,0 +1,352 @@
+//! Interactive dashboard over the service socket: workspaces, their windows, the
+//! loaded rules and the actions rules performed while the dashboard is open.
+
+use crate::{query_service, subscribe};
+use aerospace_rules::{Event, Request, Response, RuleStatus, WindowInfo};
+use ratatui::crossterm::event::{self, Event as TerminalEvent, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{HashSet, VecDeque};
+use std::error::Error;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_ACTIONS: usize = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Workspaces,
+    Windows,
+    Rules,
+}
+
+impl Pane {
+    fn next(self) -> Self {
+        match self {
+            Pane::Workspaces => Pane::Windows,
+            Pane::Windows => Pane::Rules,
+            Pane::Rules => Pane::Workspaces,
+        }
+    }
+}
+
+struct App {
+    windows: Vec<WindowInfo>,
+    rules: Vec<RuleStatus>,
+    pinned: HashSet<u32>,
+    /// Most recent first.
+    actions: VecDeque<String>,
+    focus: Pane,
+    workspaces: ListState,
+    workspace_windows: ListState,
+    rule_list: ListState,
+    status: String,
+}
+
+impl App {
+    fn new() -> Self {
+        Self {
+            windows: Vec::new(),
+            rules: Vec::new(),
+            pinned: HashSet::new(),
+            actions: VecDeque::new(),
+            focus: Pane::Workspaces,
+            workspaces: ListState::default().with_selected(Some(0)),
+            workspace_windows: ListState::default().with_selected(Some(0)),
+            rule_list: ListState::default().with_selected(Some(0)),
+            status: "Connecting to service...".to_string(),
+        }
+    }
+
+    fn workspace_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .windows
+            .iter()
+            .map(|window| window.workspace.clone())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn selected_workspace(&self) -> Option<String> {
+        self.workspace_names()
+            .get(self.workspaces.selected().unwrap_or(0))
+            .cloned()
+    }
+
+    fn selected_workspace_windows(&self) -> Vec<&WindowInfo> {
+        let workspace = self.selected_workspace();
+        self.windows
+            .iter()
+            .filter(|window| Some(&window.workspace) == workspace.as_ref())
+            .collect()
+    }
+
+    async fn refresh(&mut self) {
+        let responses = (
+            query_service(&Request::GetWindows {
+                filter: Default::default(),
+            })
+            .await,
+            query_service(&Request::GetRules).await,
+            query_service(&Request::GetPinnedWindows).await,
+        );
+        match responses {
+            (Ok(Response::Windows(windows)), rules, pinned) => {
+                self.windows = windows;
+                if let Ok(Response::Rules(rules)) = rules {
+                    self.rules = rules;
+                }
+                if let Ok(Response::PinnedWindows(pinned)) = pinned {
+                    self.pinned = pinned.into_iter().collect();
+                }
+                if self.status.starts_with("Connecting") || self.status.starts_with("Service") {
+                    self.status.clear();
+                }
+            }
+            (Ok(Response::Error { message, .. }), _, _) => {
+                self.status = format!("Service error: {message}")
+            }
+            (Ok(_), _, _) => self.status = "Service sent an unexpected response".to_string(),
+            (Err(e), _, _) => self.status = format!("Service unavailable: {e}"),
+        }
+    }
+
+    /// Sends a request that answers with `Success`, reporting the outcome in the status line.
+    async fn send(&mut self, request: Request, done: String) {
+        self.status = match query_service(&request).await {
+            Ok(Response::Success) => done,
+            Ok(Response::RulesEvaluated { results, .. }) => {
+                format!("{done}: {} actions", results.len())
+            }
+            Ok(Response::Error { message, .. }) => format!("Service error: {message}"),
+            Ok(_) => "Service sent an unexpected response".to_string(),
+            Err(e) => format!("Service unavailable: {e}"),
+        };
+        self.refresh().await;
+    }
+
+    fn push_event(&mut self, event: Event) {
+        let line = match event {
+            Event::RuleFired { result } | Event::ActionFailed { result } => result.to_string(),
+            Event::ConfigReloaded {
+                rules,
+                warnings,
+                errors,
+            } => format!(
+                "Config reloaded: {rules} rules, {} errors, {} warnings",
+                errors.len(),
+                warnings.len()
+            ),
+            Event::WindowManagerRestarted { backend, .. } => format!("{backend} restarted"),
+            _ => return,
+        };
+        self.actions.push_front(line);
+        self.actions.truncate(MAX_ACTIONS);
+    }
+
+    fn move_selection(&mut self, down: bool) {
+        let len = match self.focus {
+            Pane::Workspaces => self.workspace_names().len(),
+            Pane::Windows => self.selected_workspace_windows().len(),
+            Pane::Rules => self.rules.len(),
+        };
+        let state = match self.focus {
+            Pane::Workspaces => &mut self.workspaces,
+            Pane::Windows => &mut self.workspace_windows,
+            Pane::Rules => &mut self.rule_list,
+        };
+        if len == 0 {
+            return;
+        }
+        let selected = state.selected().unwrap_or(0).min(len - 1);
+        state.select(Some(if down {
+            (selected + 1) % len
+        } else {
+            (selected + len - 1) % len
+        }));
+        if self.focus == Pane::Workspaces {
+            self.workspace_windows.select(Some(0));
+        }
+    }
+
+    /// Handles a key press, returning `false` when the dashboard should close.
+    async fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Tab => self.focus = self.focus.next(),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(true),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(false),
+            KeyCode::Char('r') => {
+                self.send(Request::Reload, "Reloaded".to_string()).await;
+            }
+            KeyCode::Char('e') => {
+                if let Some(workspace) = self.selected_workspace() {
+                    let done = format!("Evaluated workspace {workspace}");
+                    let request = Request::EvaluateRules {
+                        workspace,
+                        dry_run: false,
+                        changed_only: false,
+                    };
+                    self.send(request, done).await;
+                }
+            }
+            KeyCode::Char(' ') | KeyCode::Char('t') if self.focus == Pane::Rules => {
+                let selected = self.rule_list.selected().unwrap_or(0);
+                if let Some(status) = self.rules.get(selected) {
+                    let name = status.rule.name.clone();
+                    let enabled = !status.rule.enabled;
+                    let done = format!(
+                        "Rule '{name}' {}",
+                        if enabled { "enabled" } else { "disabled" }
+                    );
+                    self.send(Request::SetRuleEnabled { name, enabled }, done)
+                        .await;
+                }
+            }
+            KeyCode::Char('p') if self.focus == Pane::Windows => {
+                let selected = self.workspace_windows.selected().unwrap_or(0);
+                if let Some(window) = self.selected_workspace_windows().get(selected) {
+                    let window_id = window.window_id;
+                    let pinned = !self.pinned.contains(&window_id);
+                    let done = format!(
+                        "Window {window_id} {}",
+                        if pinned { "pinned" } else { "unpinned" }
+                    );
+                    self.send(Request::SetWindowPinned { window_id, pinned }, done)
+                        .await;
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, actions, help] = Layout::vertical([
+            Constraint::Min(8),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [workspaces, windows, rules] = Layout::horizontal([
+            Constraint::Percentage(15),
+            Constraint::Percentage(50),
+            Constraint::Percentage(35),
+        ])
+        .areas(main);
+
+        let block = |title: &str, pane: Pane| {
+            let block = Block::bordered().title(format!(" {title} "));
+            if self.focus == pane {
+                block.border_style(Style::new().add_modifier(Modifier::BOLD))
+            } else {
+                block
+            }
+        };
+        let highlight = Style::new().add_modifier(Modifier::REVERSED);
+
+        let workspace_list = List::new(self.workspace_names())
+            .block(block("Workspaces", Pane::Workspaces))
+            .highlight_style(highlight);
+
+        let window_items: Vec<ListItem> = self
+            .selected_workspace_windows()
+            .iter()
+            .map(|window| {
+                let pin = if self.pinned.contains(&window.window_id) {
+                    "[pinned] "
+                } else {
+                    ""
+                };
+                ListItem::new(format!(
+                    "{pin}{} ({}) - {}",
+                    window.app_name, window.window_id, window.window_title
+                ))
+            })
+            .collect();
+        let window_list = List::new(window_items)
+            .block(block("Windows", Pane::Windows))
+            .highlight_style(highlight);
+
+        let rule_items: Vec<ListItem> = self
+            .rules
+            .iter()
+            .map(|status| {
+                let enabled = if status.rule.enabled { "x" } else { " " };
+                ListItem::new(format!(
+                    "[{enabled}] {} ({} hits)",
+                    status.rule.name, status.hits
+                ))
+            })
+            .collect();
+        let rule_list = List::new(rule_items)
+            .block(block("Rules", Pane::Rules))
+            .highlight_style(highlight);
+
+        let action_list = List::new(self.actions.iter().map(String::as_str))
+            .block(Block::bordered().title(" Recent actions "));
+
+        let help_line = if self.status.is_empty() {
+            "q quit  tab switch pane  j/k move  e evaluate workspace  space toggle rule  p pin window  r reload"
+                .to_string()
+        } else {
+            self.status.clone()
+        };
+
+        frame.render_stateful_widget(workspace_list, workspaces, &mut self.workspaces);
+        frame.render_stateful_widget(window_list, windows, &mut self.workspace_windows);
+        frame.render_stateful_widget(rule_list, rules, &mut self.rule_list);
+        frame.render_widget(action_list, actions);
+        frame.render_widget(Paragraph::new(help_line), help);
+    }
+}
+
+pub async fn run() -> Result<(), Box<dyn Error>> {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        // The dashboard keeps working from polling alone if the subscription drops
+        let _ = subscribe(|event| {
+            let _ = tx.send(event);
+        })
+        .await;
+    });
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut events).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    events: &mut mpsc::UnboundedReceiver<Event>,
+) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new();
+    app.refresh().await;
+    let mut last_refresh = Instant::now();
+
+    loop {
+        if last_refresh.elapsed() >= REFRESH_INTERVAL {
+            app.refresh().await;
+            last_refresh = Instant::now();
+        }
+        while let Ok(event) = events.try_recv() {
+            app.push_event(event);
+        }
+
+        terminal.draw(|frame| app.draw(frame))?;
+
+        if event::poll(Duration::from_millis(200))? {
+            if let TerminalEvent::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !app.handle_key(key.code).await {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Setting up workspaces from their `[workspaces.<name>]` definitions: launching the apps
+//! missing from them, waiting for their windows to open, moving them there and arranging
+//! them in the workspace's layout.
+//!
+//! ```toml
+//! [workspaces.dev]
+//! apps = ["Ghostty", "IntelliJ IDEA"]
+//! layout = "tiles horizontal"
+//! ```
+
+use crate::backend::WindowManager;
+use crate::config::{Config, WorkspaceConfig};
+use crate::rules::ActionResult;
+use crate::{log_info, WindowInfo};
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long launched apps get to open their first window.
+pub const WINDOW_TIMEOUT: Duration = Duration::from_secs(15);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Sets up every workspace defined in `config`, or only `only`. A dry run reports the
+/// launches and moves it would make without making them.
+pub fn bootstrap(
+    config: &Config,
+    only: Option<&str>,
+    wm: &dyn WindowManager,
+    dry_run: bool,
+    timeout: Duration,
+) -> Result<Vec<ActionResult>, Box<dyn Error>> {
+    let workspaces: Vec<(&String, &WorkspaceConfig)> = match only {
+        Some(name) => vec![config
+            .workspaces
+            .get_key_value(name)
+            .ok_or_else(|| format!("No workspace named '{name}' in the config"))?],
+        None => config.workspaces.iter().collect(),
+    };
+    let mut results = Vec::new();
+    for (name, workspace) in workspaces {
+        log_info!("Bootstrapping workspace {name}");
+        results.extend(bootstrap_workspace(name, workspace, wm, dry_run, timeout)?);
+    }
+    Ok(results)
+}
+
+fn bootstrap_workspace(
+    name: &str,
+    workspace: &WorkspaceConfig,
+    wm: &dyn WindowManager,
+    dry_run: bool,
+    timeout: Duration,
+) -> Result<Vec<ActionResult>, Box<dyn Error>> {
+    let label = format!("bootstrap {name}");
+    let mut results = Vec::new();
+
+    let mut windows = wm.list_windows()?;
+    let missing: Vec<&String> = workspace
+        .apps
+        .iter()
+        .filter(|app| !windows.iter().any(|window| is_app(window, app)))
+        .collect();
+    let mut launched = Vec::new();
+    for app in missing {
+        let action = format!("launch {app}");
+        match if dry_run { Ok(()) } else { wm.launch_app(app) } {
+            Ok(()) => launched.push((app, action)),
+            Err(e) => results.push(launch_result(&label, action, app, Err(e.to_string()))),
+        }
+    }
+    if !dry_run && !launched.is_empty() {
+        windows = wait_for_windows(wm, &launched, timeout)?;
+    }
+    for (app, action) in launched {
+        let result = if dry_run || windows.iter().any(|window| is_app(window, app)) {
+            Ok(())
+        } else {
+            Err(format!(
+                "No window of {app} opened within {}s",
+                timeout.as_secs()
+            ))
+        };
+        results.push(launch_result(&label, action, app, result));
+    }
+
+    let action = format!("move-to-workspace {name}");
+    let strays: Vec<&WindowInfo> = windows
+        .iter()
+        .filter(|window| {
+            window.workspace != name && workspace.apps.iter().any(|app| is_app(window, app))
+        })
+        .collect();
+    let moves: Vec<(u32, String)> = strays
+        .iter()
+        .map(|window| (window.window_id, name.to_string()))
+        .collect();
+    let outcomes = if dry_run || moves.is_empty() {
+        Vec::new()
+    } else {
+        wm.move_windows(&moves)
+    };
+    for window in strays {
+        let result = outcomes
+            .iter()
+            .find(|outcome| outcome.window_id == window.window_id)
+            .map_or(Ok(()), |outcome| outcome.result.clone());
+        results.push(ActionResult::for_window(&label, &action, window, result));
+    }
+
+    // Any window of the workspace stands for its root container
+    let arranged = windows
+        .iter()
+        .find(|window| workspace.apps.iter().any(|app| is_app(window, app)));
+    if let (Some(layout), Some(window)) = (&workspace.layout, arranged) {
+        let result = if dry_run {
+            Ok(())
+        } else {
+            wm.set_layout(window.window_id, layout)
+                .map_err(|e| e.to_string())
+        };
+        results.push(ActionResult::for_window(
+            &label,
+            &format!("layout {layout}"),
+            window,
+            result,
+        ));
+    }
+    Ok(results)
+}
+
+fn is_app(window: &WindowInfo, app: &str) -> bool {
+    window.app_name.eq_ignore_ascii_case(app)
+}
+
+fn launch_result(
+    label: &str,
+    action: String,
+    app: &str,
+    result: Result<(), String>,
+) -> ActionResult {
+    ActionResult {
+        app_name: Some(app.to_string()),
+        ..ActionResult::for_workspace(label, &action, result)
+    }
+}
+
+/// Lists windows until every launched app has one or `timeout` passed.
+fn wait_for_windows(
+    wm: &dyn WindowManager,
+    launched: &[(&String, String)],
+    timeout: Duration,
+) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
+    let started = Instant::now();
+    loop {
+        let windows = wm.list_windows()?;
+        let all_open = launched
+            .iter()
+            .all(|(app, _)| windows.iter().any(|window| is_app(window, app)));
+        if all_open || started.elapsed() >= timeout {
+            return Ok(windows);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{window, Call, FakeWindowManager};
+
+    #[test]
+    fn test_bootstrap_launches_missing_apps_and_arranges_the_workspace() {
+        let config: Config = toml::from_str(
+            r#"
+rules = []
+
+[workspaces.dev]
+apps = ["Ghostty", "IntelliJ IDEA"]
+layout = "accordion"
+"#,
+        )
+        .unwrap();
+        let wm = FakeWindowManager::new([window("Ghostty").id(1).workspace("3")]);
+
+        let results = bootstrap(&config, None, &wm, false, Duration::ZERO).unwrap();
+        assert!(results.iter().all(|result| result.success));
+        assert_eq!(
+            wm.calls(),
+            [
+                Call::Launch {
+                    app_name: "IntelliJ IDEA".to_string()
+                },
+                Call::Move {
+                    window_id: 1,
+                    workspace: "dev".to_string()
+                },
+                Call::Move {
+                    window_id: 2,
+                    workspace: "dev".to_string()
+                },
+                Call::Layout {
+                    window_id: 1,
+                    layout: "accordion".to_string()
+                },
+            ]
+        );
+        assert!(wm.windows().iter().all(|window| window.workspace == "dev"));
+    }
+}
@@ -0,0 +1,196 @@
+//! Async client for the service's socket protocol, for tools that want to query or
+//! drive the service without reimplementing the protocol.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//! let client = aerospace_rules::client::Client::new();
+//! for window in client.get_windows().await? {
+//!     println!("{} on workspace {}", window.app_name, window.workspace);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::Config;
+use crate::rules::ActionResult;
+use crate::{
+    ErrorKind, Event, HistoryEntry, Pong, Request, Response, RuleStatus, WindowFilter, WindowInfo,
+    SOCKET_PATH,
+};
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::UnixStream;
+
+pub type ClientError = Box<dyn Error + Send + Sync>;
+
+/// The service answered with [`Response::Error`].
+#[derive(Debug, Clone)]
+pub struct ServiceError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for ServiceError {}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    socket_path: PathBuf,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    /// A client for the service listening on [`SOCKET_PATH`].
+    pub fn new() -> Self {
+        Self::with_socket(SOCKET_PATH)
+    }
+
+    pub fn with_socket(socket_path: impl Into<PathBuf>) -> Self {
+        Self {
+            socket_path: socket_path.into(),
+        }
+    }
+
+    /// Sends `request` and returns the service's response as is, including errors.
+    pub async fn request(&self, request: &Request) -> Result<Response, ClientError> {
+        let mut stream = UnixStream::connect(&self.socket_path).await?;
+        stream
+            .write_all(serde_json::to_string(request)?.as_bytes())
+            .await?;
+
+        // The service closes the connection after writing its response
+        let mut buffer = Vec::new();
+        stream.read_to_end(&mut buffer).await?;
+        Ok(serde_json::from_slice(&buffer)?)
+    }
+
+    pub async fn get_windows(&self) -> Result<Vec<WindowInfo>, ClientError> {
+        self.get_windows_matching(WindowFilter::default()).await
+    }
+
+    pub async fn get_windows_matching(
+        &self,
+        filter: WindowFilter,
+    ) -> Result<Vec<WindowInfo>, ClientError> {
+        match self
+            .checked_request(&Request::GetWindows { filter })
+            .await?
+        {
+            Response::Windows(windows) => Ok(windows),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn get_config(&self) -> Result<Config, ClientError> {
+        match self.checked_request(&Request::GetConfig).await? {
+            Response::Config(config) => Ok(*config),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn get_rules(&self) -> Result<Vec<RuleStatus>, ClientError> {
+        match self.checked_request(&Request::GetRules).await? {
+            Response::Rules(rules) => Ok(rules),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Evaluates the rules for `workspace`. While the service is paused no action is
+    /// performed and the result is empty.
+    pub async fn evaluate(&self, workspace: &str) -> Result<Vec<ActionResult>, ClientError> {
+        let request = Request::EvaluateRules {
+            workspace: workspace.to_string(),
+            dry_run: false,
+            changed_only: false,
+        };
+        match self.checked_request(&request).await? {
+            Response::RulesEvaluated { results, .. } => Ok(results),
+            Response::Paused(_) => Ok(Vec::new()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Actions rules performed, oldest first; see [`Request::GetHistory`] for the filters.
+    pub async fn get_history(
+        &self,
+        since: Option<chrono::DateTime<chrono::Utc>>,
+        rule: Option<String>,
+        failed: bool,
+    ) -> Result<Vec<HistoryEntry>, ClientError> {
+        let request = Request::GetHistory {
+            since,
+            rule,
+            failed,
+        };
+        match self.checked_request(&request).await? {
+            Response::History(entries) => Ok(entries),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Checks the service is up, and how fresh its window state is.
+    pub async fn ping(&self) -> Result<Pong, ClientError> {
+        match self.checked_request(&Request::Ping).await? {
+            Response::Pong(pong) => Ok(pong),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn reload(&self) -> Result<(), ClientError> {
+        match self.checked_request(&Request::Reload).await? {
+            Response::Success => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Opens a subscription to the service's events.
+    pub async fn subscribe(&self) -> Result<Subscription, ClientError> {
+        let mut stream = UnixStream::connect(&self.socket_path).await?;
+        stream
+            .write_all(serde_json::to_string(&Request::Subscribe)?.as_bytes())
+            .await?;
+        Ok(Subscription {
+            lines: BufReader::new(stream).lines(),
+        })
+    }
+
+    /// Like [`Client::request`], but turns [`Response::Error`] into a [`ServiceError`].
+    async fn checked_request(&self, request: &Request) -> Result<Response, ClientError> {
+        match self.request(request).await? {
+            Response::Error { kind, message } => Err(ServiceError { kind, message }.into()),
+            response => Ok(response),
+        }
+    }
+}
+
+/// Events streamed by the service after [`Client::subscribe`].
+#[derive(Debug)]
+pub struct Subscription {
+    lines: Lines<BufReader<UnixStream>>,
+}
+
+impl Subscription {
+    /// Waits for the next event; `None` once the service closed the connection.
+    pub async fn next_event(&mut self) -> Result<Option<Event>, ClientError> {
+        match self.lines.next_line().await? {
+            Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn unexpected(response: Response) -> ClientError {
+    format!("Unexpected response from service: {response:?}").into()
+}
@@ -0,0 +1,106 @@
+//! Older spellings of condition fields and actions that still load, mapped onto their
+//! current forms. Each use is a [`Deprecation`] that `validate` and config reloads
+//! report, so existing configs keep working while they're updated.
+
+use crate::condition;
+use crate::config::{Rule, RuleCondition, RuleType};
+use std::fmt;
+
+/// Condition fields that were renamed, with their current names.
+const FIELDS: [(&str, &str); 1] = [("app-id", "app-name")];
+
+/// Actions spelled like the aerospace commands they run, with their current spellings.
+const ACTIONS: [(&str, &str); 2] = [
+    ("move-node-to-workspace", "move-to-workspace"),
+    ("fullscreen", "maximize"),
+];
+
+/// An old spelling in a rule and the one that replaces it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+    /// The rule field it is in, `condition` or `action`.
+    pub field: &'static str,
+    pub old: &'static str,
+    pub new: &'static str,
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' is deprecated, use '{}'", self.old, self.new)
+    }
+}
+
+/// Current name of the renamed condition field `name`.
+pub fn field(name: &str) -> Option<&'static str> {
+    FIELDS
+        .iter()
+        .find(|(old, _)| *old == name)
+        .map(|(_, new)| *new)
+}
+
+/// `action` in its current spelling, if it starts with a renamed action.
+pub fn action(action: &str) -> Option<String> {
+    let action = action.trim();
+    let (word, rest) = action.split_once(' ').unwrap_or((action, ""));
+    let (_, new) = ACTIONS.iter().find(|(old, _)| *old == word)?;
+    Some(format!("{new} {rest}").trim_end().to_string())
+}
+
+/// The old spellings `rule` uses, in the order they appear.
+pub fn deprecations(rule: &Rule) -> Vec<Deprecation> {
+    let RuleType::Window {
+        condition, action, ..
+    } = &rule.rule_type
+    else {
+        return Vec::new();
+    };
+    // TOML conditions name their fields through serde, which only knows the current ones
+    let fields = match condition {
+        RuleCondition::Text(source) => condition::field_names(source),
+        _ => Vec::new(),
+    };
+    let fields = fields
+        .into_iter()
+        .filter_map(|name| FIELDS.iter().find(|(old, _)| *old == name))
+        .map(|&(old, new)| Deprecation {
+            field: "condition",
+            old,
+            new,
+        });
+    let word = action.split_whitespace().next().unwrap_or_default();
+    let actions = ACTIONS
+        .iter()
+        .filter(|(old, _)| *old == word)
+        .map(|&(old, new)| Deprecation {
+            field: "action",
+            old,
+            new,
+        });
+    fields.chain(actions).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Action;
+    use crate::condition::Condition;
+
+    #[test]
+    fn test_old_spellings_still_parse() {
+        assert_eq!(
+            Action::parse("move-node-to-workspace 3"),
+            Ok(Action::MoveToWorkspace("3".to_string()))
+        );
+        assert_eq!(Action::parse("fullscreen"), Ok(Action::Maximize));
+        assert_eq!(
+            Condition::parse("app-id = 'Slack'").unwrap(),
+            Condition::parse("app-name = 'Slack'").unwrap()
+        );
+        assert_eq!(action("maximize"), None);
+        // A quoted value that happens to be an old field name is left alone
+        assert_eq!(
+            condition::field_names("window-title = 'app-id'"),
+            ["window-title"]
+        );
+    }
+}
@@ -0,0 +1,1227 @@
+use crate::compat;
+use crate::tracking::WindowTracker;
+use crate::WindowInfo;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// A parsed window rule condition such as `app-name = 'Slack'`, or comparisons
+/// combined with `AND`, `OR`, `NOT` and parentheses, like
+/// `app-name = 'Slack' AND NOT window-title like '*Huddle*'`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Compare {
+        field: Field,
+        op: Operator,
+        value: Value,
+    },
+    /// Matches when every condition matches.
+    And(Vec<Condition>),
+    /// Matches when any condition matches.
+    Or(Vec<Condition>),
+    Not(Box<Condition>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Field {
+    AppName,
+    WindowTitle,
+    Workspace,
+    /// Name of the monitor the window is on.
+    Monitor,
+    WindowId,
+    WindowWidth,
+    WindowHeight,
+    /// Horizontal position of the window's left edge, in screen points.
+    WindowX,
+    /// Vertical position of the window's top edge, in screen points.
+    WindowY,
+    /// Number of windows on the window's workspace, itself included.
+    WorkspaceWindowCount,
+    /// Whether the window has keyboard focus, `true` or `false`.
+    IsFocused,
+    /// Name of a rule that acted on the window before, as tracked by the service.
+    PreviouslyMatched,
+    /// What the last `capture` action for the window printed, earlier in the same
+    /// evaluation.
+    Captured,
+}
+
+/// Written as a symbol or keyword in the condition DSL, e.g. `>=` or `starts-with`, and
+/// by name in TOML conditions, e.g. `at-least`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Operator {
+    /// Exact match.
+    Equals,
+    NotEquals,
+    /// Numeric comparisons, which never match when the number is unknown, like the
+    /// geometry of a window without a geometry provider.
+    GreaterThan,
+    LessThan,
+    AtLeast,
+    AtMost,
+    /// Regular expression search, anywhere in the value unless anchored with `^`/`$`.
+    Matches,
+    /// Wildcard match of the whole value: `*` stands for any text, `?` for any
+    /// single character.
+    Like,
+    Contains,
+    StartsWith,
+    EndsWith,
+    /// Exact match of any value in a list, e.g. `app-name in ['Slack', 'Discord']`.
+    In,
+}
+
+impl Operator {
+    /// The operators written as a word rather than a symbol, e.g. `contains`.
+    fn from_keyword(word: &str) -> Option<Self> {
+        [
+            Operator::Like,
+            Operator::Contains,
+            Operator::StartsWith,
+            Operator::EndsWith,
+            Operator::In,
+        ]
+        .into_iter()
+        .find(|op| word.eq_ignore_ascii_case(&op.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Number(i64),
+    Pattern(Pattern),
+    Glob(Glob),
+    List(Vec<String>),
+}
+
+/// A regular expression, compiled when its condition is parsed.
+#[derive(Debug, Clone)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn new(source: &str) -> Result<Self, ConditionError> {
+        Regex::new(source)
+            .map(Pattern)
+            .map_err(|e| ConditionError(format!("Invalid regex '{source}': {e}")))
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.0.is_match(haystack)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+/// A wildcard pattern such as `IntelliJ*`, compiled when its condition is parsed.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    source: String,
+    regex: Regex,
+}
+
+impl Glob {
+    pub fn new(source: &str) -> Self {
+        let mut pattern = String::from("^");
+        for c in source.chars() {
+            match c {
+                '*' => pattern.push_str(".*"),
+                '?' => pattern.push('.'),
+                c => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        pattern.push('$');
+        Self {
+            source: source.to_string(),
+            regex: Regex::new(&pattern).expect("escaped glob is a valid regex"),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.regex.is_match(haystack)
+    }
+}
+
+impl PartialEq for Glob {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionError(pub String);
+
+impl fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for ConditionError {}
+
+/// What a condition can know besides the window itself. Comparisons of a field whose
+/// source is missing don't match.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchContext<'a> {
+    /// Which rules acted on each window before, for `previously-matched`.
+    pub tracker: Option<&'a WindowTracker>,
+    /// How many windows each workspace holds, for `workspace-window-count`.
+    pub workspace_windows: Option<&'a HashMap<String, usize>>,
+    /// What capture actions printed for each window, by window ID, for `captured`.
+    pub captured: Option<&'a HashMap<u32, String>>,
+}
+
+/// How a single comparison in a condition fared against a window.
+#[derive(Debug, Clone)]
+pub struct ClauseResult {
+    pub clause: String,
+    /// The window's value for the compared field.
+    pub actual: String,
+    pub matched: bool,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        if let Some(name) = compat::field(name) {
+            return Self::parse(name);
+        }
+        Some(match name {
+            "app-name" => Field::AppName,
+            "window-title" => Field::WindowTitle,
+            "workspace" => Field::Workspace,
+            "monitor" => Field::Monitor,
+            "window-id" => Field::WindowId,
+            "window-width" => Field::WindowWidth,
+            "window-height" => Field::WindowHeight,
+            "window-x" => Field::WindowX,
+            "window-y" => Field::WindowY,
+            "workspace-window-count" => Field::WorkspaceWindowCount,
+            "is-focused" => Field::IsFocused,
+            "previously-matched" => Field::PreviouslyMatched,
+            "captured" => Field::Captured,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::AppName => "app-name",
+            Field::WindowTitle => "window-title",
+            Field::Workspace => "workspace",
+            Field::Monitor => "monitor",
+            Field::WindowId => "window-id",
+            Field::WindowWidth => "window-width",
+            Field::WindowHeight => "window-height",
+            Field::WindowX => "window-x",
+            Field::WindowY => "window-y",
+            Field::WorkspaceWindowCount => "workspace-window-count",
+            Field::IsFocused => "is-focused",
+            Field::PreviouslyMatched => "previously-matched",
+            Field::Captured => "captured",
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Field::WindowId
+                | Field::WindowWidth
+                | Field::WindowHeight
+                | Field::WindowX
+                | Field::WindowY
+                | Field::WorkspaceWindowCount
+        )
+    }
+
+    /// Fields compared with text operators like `contains`, rather than only `=`.
+    fn is_text(self) -> bool {
+        !self.is_numeric() && self != Field::IsFocused
+    }
+
+    fn actual(self, window: &WindowInfo, context: &MatchContext) -> String {
+        match self {
+            Field::AppName => window.app_name.clone(),
+            Field::WindowTitle => window.window_title.clone(),
+            Field::Workspace => window.workspace.clone(),
+            Field::Monitor => window.monitor.clone().unwrap_or_default(),
+            Field::IsFocused => window.focused.to_string(),
+            Field::WindowId => window.window_id.to_string(),
+            Field::WindowWidth
+            | Field::WindowHeight
+            | Field::WindowX
+            | Field::WindowY
+            | Field::WorkspaceWindowCount => numeric(self, window, context)
+                .ok()
+                .flatten()
+                .map_or("unknown".to_string(), |value| value.to_string()),
+            Field::PreviouslyMatched => context
+                .tracker
+                .and_then(|tracker| tracker.get(window.window_id))
+                .map_or("untracked".to_string(), |record| {
+                    record
+                        .matched_rules
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                }),
+            Field::Captured => {
+                captured(window, context).map_or("not captured".to_string(), str::to_string)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operator::Equals => "=",
+            Operator::NotEquals => "!=",
+            Operator::GreaterThan => ">",
+            Operator::LessThan => "<",
+            Operator::AtLeast => ">=",
+            Operator::AtMost => "<=",
+            Operator::Matches => "=~",
+            Operator::Like => "like",
+            Operator::Contains => "contains",
+            Operator::StartsWith => "starts-with",
+            Operator::EndsWith => "ends-with",
+            Operator::In => "in",
+        })
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(value) => write_quoted(f, value),
+            Value::Number(value) => write!(f, "{value}"),
+            Value::Pattern(pattern) => write_quoted(f, pattern.as_str()),
+            Value::Glob(glob) => write_quoted(f, glob.as_str()),
+            Value::List(values) => {
+                f.write_str("[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write_quoted(f, value)?;
+                }
+                f.write_str("]")
+            }
+        }
+    }
+}
+
+/// Single-quotes `value`, or double-quotes it when it contains a single quote.
+fn write_quoted(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    if value.contains('\'') {
+        write!(f, "\"{value}\"")
+    } else {
+        write!(f, "'{value}'")
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Combined conditions inside others are parenthesized, so the printed
+        // condition parses back to the same tree
+        let operand = |f: &mut fmt::Formatter<'_>, condition: &Condition| match condition {
+            Condition::And(_) | Condition::Or(_) => write!(f, "({condition})"),
+            _ => write!(f, "{condition}"),
+        };
+        match self {
+            Condition::Compare { field, op, value } => {
+                write!(f, "{} {op} {value}", field.name())
+            }
+            Condition::And(conditions) | Condition::Or(conditions) => {
+                let separator = if matches!(self, Condition::And(_)) {
+                    " AND "
+                } else {
+                    " OR "
+                };
+                for (i, condition) in conditions.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(separator)?;
+                    }
+                    operand(f, condition)?;
+                }
+                Ok(())
+            }
+            Condition::Not(condition) => {
+                f.write_str("NOT ")?;
+                operand(f, condition)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Word(&'a str),
+    Quoted(&'a str),
+    Op(Operator),
+    Open,
+    Close,
+    /// What is between the brackets of a list, still quoted.
+    List(&'a str),
+}
+
+fn tokenize(condition: &str) -> Result<Vec<Token<'_>>, ConditionError> {
+    let mut tokens = Vec::new();
+    let mut rest = condition.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let (token, len) = match c {
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            '=' if rest.starts_with("=~") => (Token::Op(Operator::Matches), 2),
+            '=' => (Token::Op(Operator::Equals), 1),
+            '>' if rest.starts_with(">=") => (Token::Op(Operator::AtLeast), 2),
+            '>' => (Token::Op(Operator::GreaterThan), 1),
+            '<' if rest.starts_with("<=") => (Token::Op(Operator::AtMost), 2),
+            '<' => (Token::Op(Operator::LessThan), 1),
+            '!' if rest.starts_with("!=") => (Token::Op(Operator::NotEquals), 2),
+            // Only after `in`, so other unquoted values can still start with a bracket
+            '[' if matches!(tokens.last(), Some(Token::Word(word)) if word.eq_ignore_ascii_case("in")) =>
+            {
+                let end = list_end(rest).ok_or_else(|| {
+                    ConditionError(format!("Unclosed list in condition: {condition}"))
+                })?;
+                (Token::List(&rest[1..end]), end + 1)
+            }
+            '\'' | '"' => {
+                let end = rest[1..].find(c).ok_or_else(|| {
+                    ConditionError(format!("Unclosed quote in condition: {condition}"))
+                })?;
+                (Token::Quoted(&rest[1..1 + end]), end + 2)
+            }
+            _ => {
+                let end = rest
+                    .char_indices()
+                    .find(|&(i, c)| {
+                        c.is_whitespace() || "()'\"=<>".contains(c) || rest[i..].starts_with("!=")
+                    })
+                    .map_or(rest.len(), |(i, _)| i);
+                (Token::Word(&rest[..end]), end)
+            }
+        };
+        tokens.push(token);
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Offset of the `]` closing the list `rest` starts with, skipping quoted values.
+fn list_end(rest: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in rest.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (None, ']') => return Some(i),
+            (Some(open), c) if c == open => quote = None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The values of a list, given what is between its brackets. Values are quoted or
+/// bare, separated by commas.
+fn parse_list(list: &str, condition: &str) -> Result<Vec<String>, ConditionError> {
+    let invalid = || ConditionError(format!("Invalid list in condition: {condition}"));
+    let mut values = Vec::new();
+    let mut rest = list.trim_start();
+    while !rest.is_empty() {
+        let (value, after) = match rest.chars().next() {
+            Some(quote @ ('\'' | '"')) => {
+                let end = rest[1..].find(quote).ok_or_else(invalid)?;
+                (&rest[1..1 + end], &rest[end + 2..])
+            }
+            _ => {
+                let end = rest.find(',').unwrap_or(rest.len());
+                match rest[..end].trim_end() {
+                    "" => return Err(invalid()),
+                    value => (value, &rest[end..]),
+                }
+            }
+        };
+        values.push(value.to_string());
+        rest = after.trim_start();
+        if !rest.is_empty() {
+            rest = rest.strip_prefix(',').ok_or_else(invalid)?.trim_start();
+        }
+    }
+    if values.is_empty() {
+        return Err(ConditionError(format!(
+            "Empty list in condition: {condition}"
+        )));
+    }
+    Ok(values)
+}
+
+/// The field names `condition` compares, as written. Empty if it doesn't tokenize.
+pub(crate) fn field_names(condition: &str) -> Vec<&str> {
+    let tokens = tokenize(condition).unwrap_or_default();
+    tokens
+        .windows(2)
+        .filter_map(|pair| match pair {
+            [Token::Word(field), Token::Op(_)] => Some(*field),
+            [Token::Word(field), Token::Word(op)] if Operator::from_keyword(op).is_some() => {
+                Some(*field)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Recursive descent over the tokens of a condition. `NOT` binds tightest, then
+/// `AND`, then `OR`; keywords are case-insensitive.
+struct Parser<'a> {
+    condition: &'a str,
+    tokens: Vec<Token<'a>>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn invalid(&self) -> ConditionError {
+        ConditionError(format!("Invalid condition format: {}", self.condition))
+    }
+
+    fn or(&mut self) -> Result<Condition, ConditionError> {
+        let mut conditions = vec![self.and()?];
+        while self.keyword("or") {
+            conditions.push(self.and()?);
+        }
+        Ok(match conditions.len() {
+            1 => conditions.remove(0),
+            _ => Condition::Or(conditions),
+        })
+    }
+
+    fn and(&mut self) -> Result<Condition, ConditionError> {
+        let mut conditions = vec![self.unary()?];
+        while self.keyword("and") {
+            conditions.push(self.unary()?);
+        }
+        Ok(match conditions.len() {
+            1 => conditions.remove(0),
+            _ => Condition::And(conditions),
+        })
+    }
+
+    fn unary(&mut self) -> Result<Condition, ConditionError> {
+        if self.keyword("not") {
+            return Ok(Condition::Not(Box::new(self.unary()?)));
+        }
+        if self.peek() == Some(Token::Open) {
+            self.position += 1;
+            let condition = self.or()?;
+            return match self.next() {
+                Some(Token::Close) => Ok(condition),
+                _ => Err(ConditionError(format!(
+                    "Unclosed parenthesis in condition: {}",
+                    self.condition
+                ))),
+            };
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Condition, ConditionError> {
+        let condition = self.condition;
+        let unsupported = || ConditionError(format!("Unsupported condition format: {condition}"));
+        let (field, op) = match (self.next(), self.next()) {
+            (Some(Token::Word(field)), Some(Token::Op(op))) => (field, op),
+            (Some(Token::Word(field)), Some(Token::Word(op))) => match Operator::from_keyword(op) {
+                Some(op) => (field, op),
+                None => return Err(unsupported()),
+            },
+            _ => return Err(unsupported()),
+        };
+        let value = match self.next() {
+            Some(Token::List(list)) if op == Operator::In => {
+                return list_condition(field, parse_list(list, condition)?);
+            }
+            _ if op == Operator::In => {
+                return Err(ConditionError(format!(
+                    "in needs a list of values, e.g. ['1', '2']: {condition}"
+                )))
+            }
+            Some(Token::Quoted(value)) => value.to_string(),
+            // Unquoted values run up to the next keyword or parenthesis
+            Some(Token::Word(word)) => {
+                let mut words = vec![word];
+                while let Some(Token::Word(word)) = self.peek() {
+                    if word.eq_ignore_ascii_case("and") || word.eq_ignore_ascii_case("or") {
+                        break;
+                    }
+                    words.push(word);
+                    self.position += 1;
+                }
+                words.join(" ")
+            }
+            _ => return Err(self.invalid()),
+        };
+        compare_condition(field, op, &value)
+    }
+}
+
+pub(crate) fn list_condition(
+    field: &str,
+    values: Vec<String>,
+) -> Result<Condition, ConditionError> {
+    let field = Field::parse(field)
+        .filter(|field| field.is_text())
+        .ok_or_else(|| ConditionError(format!("Unknown text field in condition: {field}")))?;
+    Ok(Condition::Compare {
+        field,
+        op: Operator::In,
+        value: Value::List(values),
+    })
+}
+
+pub(crate) fn compare_condition(
+    field: &str,
+    op: Operator,
+    value: &str,
+) -> Result<Condition, ConditionError> {
+    let (field, value) = match op {
+        Operator::Matches => {
+            let field = Field::parse(field)
+                .filter(|field| field.is_text())
+                .ok_or_else(|| {
+                    ConditionError(format!("Unknown text field in condition: {field}"))
+                })?;
+            (field, Value::Pattern(Pattern::new(value)?))
+        }
+        Operator::Like => {
+            let field = Field::parse(field)
+                .filter(|field| field.is_text())
+                .ok_or_else(|| {
+                    ConditionError(format!("Unknown text field in condition: {field}"))
+                })?;
+            (field, Value::Glob(Glob::new(value)))
+        }
+        Operator::Contains | Operator::StartsWith | Operator::EndsWith => {
+            let field = Field::parse(field)
+                .filter(|field| field.is_text())
+                .ok_or_else(|| {
+                    ConditionError(format!("Unknown text field in condition: {field}"))
+                })?;
+            (field, Value::String(value.to_string()))
+        }
+        Operator::Equals | Operator::NotEquals => {
+            let field = Field::parse(field)
+                .ok_or_else(|| ConditionError(format!("Unknown field in condition: {field}")))?;
+            if field.is_numeric() {
+                let value = value
+                    .parse()
+                    .map_err(|_| ConditionError(format!("Invalid number in condition: {value}")))?;
+                (field, Value::Number(value))
+            } else if field == Field::IsFocused {
+                let value = value.to_ascii_lowercase().parse::<bool>().map_err(|_| {
+                    ConditionError(format!("Invalid boolean in condition: {value}"))
+                })?;
+                (field, Value::String(value.to_string()))
+            } else {
+                (field, Value::String(value.to_string()))
+            }
+        }
+        Operator::In => unreachable!("lists are parsed by list_condition"),
+        Operator::GreaterThan | Operator::LessThan | Operator::AtLeast | Operator::AtMost => {
+            let value: i64 = value
+                .parse()
+                .map_err(|e| ConditionError(format!("Invalid number in condition: {e}")))?;
+            let field = Field::parse(field)
+                .filter(|field| field.is_numeric())
+                .ok_or_else(|| {
+                    ConditionError(format!("Unknown numeric field in condition: {field}"))
+                })?;
+            (field, Value::Number(value))
+        }
+    };
+    Ok(Condition::Compare { field, op, value })
+}
+
+impl Condition {
+    /// Parses comparisons (`field = 'value'`, `field =~ 'regex'`, `field > number` or
+    /// `field in ['value', 'other']`) combined with `AND`, `OR`, `NOT` and parentheses.
+    pub fn parse(condition: &str) -> Result<Self, ConditionError> {
+        let mut parser = Parser {
+            condition,
+            tokens: tokenize(condition)?,
+            position: 0,
+        };
+        let parsed = parser.or()?;
+        if parser.position != parser.tokens.len() {
+            return Err(parser.invalid());
+        }
+        Ok(parsed)
+    }
+
+    /// Whether any comparison in the condition looks at `field`.
+    pub fn mentions(&self, field: Field) -> bool {
+        match self {
+            Condition::Compare {
+                field: compared, ..
+            } => *compared == field,
+            Condition::And(conditions) | Condition::Or(conditions) => {
+                conditions.iter().any(|condition| condition.mentions(field))
+            }
+            Condition::Not(condition) => condition.mentions(field),
+        }
+    }
+
+    /// Whether `window` matches, as far as it can tell without the window's history:
+    /// `previously-matched` never matches.
+    pub fn matches(&self, window: &WindowInfo) -> Result<bool, ConditionError> {
+        self.matches_in(window, &MatchContext::default())
+    }
+
+    /// Whether `window` matches, with `context` telling what is known beyond it.
+    pub fn matches_in(
+        &self,
+        window: &WindowInfo,
+        context: &MatchContext,
+    ) -> Result<bool, ConditionError> {
+        match self {
+            Condition::Compare { field, op, value } => compare(*field, *op, value, window, context),
+            Condition::And(conditions) => {
+                for condition in conditions {
+                    if !condition.matches_in(window, context)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Condition::Or(conditions) => {
+                for condition in conditions {
+                    if condition.matches_in(window, context)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Condition::Not(condition) => Ok(!condition.matches_in(window, context)?),
+        }
+    }
+
+    /// Evaluates every comparison in the condition separately, for explaining matches.
+    /// A negated part counts as one clause, matched when its inner condition isn't.
+    pub fn clauses(
+        &self,
+        window: &WindowInfo,
+        context: &MatchContext,
+    ) -> Result<Vec<ClauseResult>, ConditionError> {
+        match self {
+            Condition::Compare { field, .. } => Ok(vec![ClauseResult {
+                clause: self.to_string(),
+                actual: field.actual(window, context),
+                matched: self.matches_in(window, context)?,
+            }]),
+            Condition::And(conditions) | Condition::Or(conditions) => {
+                let mut clauses = Vec::new();
+                for condition in conditions {
+                    clauses.extend(condition.clauses(window, context)?);
+                }
+                Ok(clauses)
+            }
+            Condition::Not(condition) => {
+                let actual: Vec<String> = condition
+                    .clauses(window, context)?
+                    .into_iter()
+                    .map(|clause| clause.actual)
+                    .collect();
+                Ok(vec![ClauseResult {
+                    clause: self.to_string(),
+                    actual: actual.join(", "),
+                    matched: self.matches_in(window, context)?,
+                }])
+            }
+        }
+    }
+}
+
+fn compare(
+    field: Field,
+    op: Operator,
+    value: &Value,
+    window: &WindowInfo,
+    context: &MatchContext,
+) -> Result<bool, ConditionError> {
+    match (op, value) {
+        (Operator::Equals, Value::String(value)) => Ok(match field {
+            Field::AppName => window.app_name == *value,
+            Field::WindowTitle => window.window_title == *value,
+            Field::Workspace => window.workspace == *value,
+            Field::Monitor => window.monitor.as_deref() == Some(value.as_str()),
+            Field::IsFocused => window.focused.to_string() == *value,
+            Field::PreviouslyMatched => context
+                .tracker
+                .is_some_and(|tracker| tracker.has_matched(window.window_id, value)),
+            Field::Captured => captured(window, context) == Some(value.as_str()),
+            _ => false,
+        }),
+        (Operator::NotEquals, value) => {
+            Ok(!compare(field, Operator::Equals, value, window, context)?)
+        }
+        (Operator::Contains, Value::String(value)) => {
+            Ok(text_matches(field, window, context, |text| {
+                text.contains(value.as_str())
+            }))
+        }
+        (Operator::StartsWith, Value::String(value)) => {
+            Ok(text_matches(field, window, context, |text| {
+                text.starts_with(value.as_str())
+            }))
+        }
+        (Operator::EndsWith, Value::String(value)) => {
+            Ok(text_matches(field, window, context, |text| {
+                text.ends_with(value.as_str())
+            }))
+        }
+        (
+            op @ (Operator::Equals
+            | Operator::GreaterThan
+            | Operator::LessThan
+            | Operator::AtLeast
+            | Operator::AtMost),
+            Value::Number(value),
+        ) => {
+            let Some(actual) = numeric(field, window, context)? else {
+                return Ok(false);
+            };
+            let value = *value as f64;
+            Ok(match op {
+                Operator::GreaterThan => actual > value,
+                Operator::LessThan => actual < value,
+                Operator::AtLeast => actual >= value,
+                Operator::AtMost => actual <= value,
+                _ => actual == value,
+            })
+        }
+        (Operator::Matches, Value::Pattern(pattern)) => {
+            Ok(text_matches(field, window, context, |text| {
+                pattern.is_match(text)
+            }))
+        }
+        (Operator::In, Value::List(values)) => Ok(text_matches(field, window, context, |text| {
+            values.iter().any(|value| value == text)
+        })),
+        (Operator::Like, Value::Glob(glob)) => Ok(text_matches(field, window, context, |text| {
+            glob.is_match(text)
+        })),
+        (Operator::GreaterThan, Value::String(_)) => Err(ConditionError(format!(
+            "Cannot compare {} with a string",
+            field.name()
+        ))),
+        (op, value) => Err(ConditionError(format!(
+            "Cannot compare {} {op} {value}",
+            field.name()
+        ))),
+    }
+}
+
+/// Whether the value of text `field` passes `test`. For `previously-matched`, whether
+/// the name of any rule that acted on the window does.
+fn text_matches(
+    field: Field,
+    window: &WindowInfo,
+    context: &MatchContext,
+    test: impl Fn(&str) -> bool,
+) -> bool {
+    match field {
+        Field::PreviouslyMatched => context
+            .tracker
+            .and_then(|tracker| tracker.get(window.window_id))
+            .is_some_and(|record| record.matched_rules.iter().any(|rule| test(rule))),
+        Field::Captured => captured(window, context).is_some_and(test),
+        _ => test(&field.actual(window, context)),
+    }
+}
+
+/// What a capture action printed for `window`, if one ran for it.
+fn captured<'a>(window: &WindowInfo, context: &MatchContext<'a>) -> Option<&'a str> {
+    context
+        .captured
+        .and_then(|captured| captured.get(&window.window_id))
+        .map(String::as_str)
+}
+
+fn numeric(
+    field: Field,
+    window: &WindowInfo,
+    context: &MatchContext,
+) -> Result<Option<f64>, ConditionError> {
+    match field {
+        Field::WindowId => Ok(Some(f64::from(window.window_id))),
+        Field::WindowWidth => Ok(window.frame.map(|frame| frame.width)),
+        Field::WindowHeight => Ok(window.frame.map(|frame| frame.height)),
+        Field::WindowX => Ok(window.frame.map(|frame| frame.x)),
+        Field::WindowY => Ok(window.frame.map(|frame| frame.y)),
+        Field::WorkspaceWindowCount => Ok(context
+            .workspace_windows
+            .map(|counts| counts.get(&window.workspace).copied().unwrap_or_default() as f64)),
+        _ => Err(ConditionError(format!(
+            "{} is not a numeric field",
+            field.name()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(app_name: &str, title: &str) -> WindowInfo {
+        WindowInfo {
+            app_name: app_name.to_string(),
+            window_id: 42,
+            window_title: title.to_string(),
+            workspace: "1".to_string(),
+            monitor: None,
+            frame: None,
+            focused: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_and_match() {
+        let condition = Condition::parse("app-name = 'Slack'").unwrap();
+        assert_eq!(condition.to_string(), "app-name = 'Slack'");
+        assert!(condition.matches(&window("Slack", "general")).unwrap());
+        assert!(!condition.matches(&window("Ghostty", "zsh")).unwrap());
+
+        let condition = Condition::parse("window-title = \"Pull Request\"").unwrap();
+        assert!(condition
+            .matches(&window("Safari", "Pull Request"))
+            .unwrap());
+        assert!(!condition
+            .matches(&window("Safari", "Pull Request #12"))
+            .unwrap());
+
+        assert!(Condition::parse("window-id > 40")
+            .unwrap()
+            .matches(&window("Safari", ""))
+            .unwrap());
+        assert!(Condition::parse("color = 'red'").is_err());
+        assert!(Condition::parse("app-name > 3").is_err());
+    }
+
+    #[test]
+    fn test_lists() {
+        let condition = Condition::parse("workspace in ['1', \"dev 2\", 3]").unwrap();
+        assert_eq!(condition.to_string(), "workspace in ['1', 'dev 2', '3']");
+        let mut on = window("Ghostty", "zsh");
+        for (workspace, matches) in [("1", true), ("dev 2", true), ("3", true), ("4", false)] {
+            on.workspace = workspace.to_string();
+            assert_eq!(condition.matches(&on).unwrap(), matches, "{workspace}");
+        }
+        assert!(Condition::parse("workspace =~ '^dev-'")
+            .unwrap()
+            .matches(&WindowInfo {
+                workspace: "dev-api".to_string(),
+                ..window("Ghostty", "zsh")
+            })
+            .unwrap());
+
+        let chat = Condition::parse("app-name in ['Slack', 'Discord', 'Telegram']").unwrap();
+        assert!(chat.matches(&window("Discord", "general")).unwrap());
+        assert!(!chat.matches(&window("Safari", "general")).unwrap());
+
+        for invalid in [
+            "workspace in []",
+            "workspace in ['1'",
+            "workspace in ['1' '2']",
+            "workspace in '1'",
+            "window-id in [1, 2]",
+            "app-name in ['Slack',, 'Discord']",
+        ] {
+            assert!(Condition::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_workspace_window_count() {
+        let condition = Condition::parse("workspace-window-count > 3").unwrap();
+        let crowded = HashMap::from([("1".to_string(), 4), ("2".to_string(), 3)]);
+        let context = MatchContext {
+            workspace_windows: Some(&crowded),
+            ..MatchContext::default()
+        };
+        let mut on = window("Ghostty", "zsh");
+        assert!(condition.matches_in(&on, &context).unwrap());
+        on.workspace = "2".to_string();
+        assert!(!condition.matches_in(&on, &context).unwrap());
+        // Without counts nothing is known to be crowded
+        assert!(!condition.matches(&window("Ghostty", "zsh")).unwrap());
+    }
+
+    #[test]
+    fn test_monitor_condition() {
+        let condition = Condition::parse("monitor = 'LG Ultrawide'").unwrap();
+        let mut external = window("Ghostty", "zsh");
+        external.monitor = Some("LG Ultrawide".to_string());
+        assert!(condition.matches(&external).unwrap());
+        // A window whose monitor isn't known is on none of them
+        assert!(!condition.matches(&window("Ghostty", "zsh")).unwrap());
+        assert!(Condition::parse("monitor != 'LG Ultrawide'")
+            .unwrap()
+            .matches(&window("Ghostty", "zsh"))
+            .unwrap());
+    }
+
+    #[test]
+    fn test_is_focused_condition() {
+        let condition = Condition::parse("app-name = 'Ghostty' and is-focused = false").unwrap();
+        let mut focused = window("Ghostty", "zsh");
+        focused.focused = true;
+        assert!(!condition.matches(&focused).unwrap());
+        assert!(condition.matches(&window("Ghostty", "zsh")).unwrap());
+        assert!(Condition::parse("is-focused = TRUE")
+            .unwrap()
+            .matches(&focused)
+            .unwrap());
+        assert!(Condition::parse("is-focused = yes").is_err());
+        assert!(Condition::parse("is-focused contains 'tr'").is_err());
+    }
+
+    #[test]
+    fn test_captured_condition() {
+        let captured = HashMap::from([(42, "web".to_string())]);
+        let context = MatchContext {
+            captured: Some(&captured),
+            ..MatchContext::default()
+        };
+        let mut uncaptured = window("Ghostty", "zsh");
+        uncaptured.window_id = 7;
+        for condition in ["captured = 'web'", "captured =~ '^w'", "captured =~ '.*'"] {
+            let condition = Condition::parse(condition).unwrap();
+            assert!(condition
+                .matches_in(&window("Ghostty", "zsh"), &context)
+                .unwrap());
+            assert!(!condition.matches_in(&uncaptured, &context).unwrap());
+        }
+        let condition = Condition::parse("captured =~ 'not'").unwrap();
+        assert!(!condition.matches_in(&uncaptured, &context).unwrap());
+    }
+
+    #[test]
+    fn test_previously_matched_condition() {
+        let chat = window("Slack", "general");
+        let mut tracker = WindowTracker::default();
+        tracker.observe_window(&chat, chrono::Utc::now());
+        tracker.record_match(42, "Chat");
+        tracker.record_match(42, "Maximize");
+        let context = MatchContext {
+            tracker: Some(&tracker),
+            ..MatchContext::default()
+        };
+        for condition in [
+            "previously-matched = 'Chat'",
+            "previously-matched =~ '^Chat$'",
+            "previously-matched like 'Max*'",
+        ] {
+            let condition = Condition::parse(condition).unwrap();
+            assert!(condition.matches_in(&chat, &context).unwrap());
+        }
+
+        let mut untracked = window("Slack", "random");
+        untracked.window_id = 7;
+        let condition = Condition::parse("previously-matched =~ 'tracked'").unwrap();
+        assert!(!condition.matches_in(&untracked, &context).unwrap());
+    }
+
+    #[test]
+    fn test_geometry_conditions() {
+        let mut placed = window("Ghostty", "zsh");
+        placed.frame = Some(crate::WindowFrame {
+            x: -1440.0,
+            y: 25.0,
+            width: 1440.0,
+            height: 875.0,
+        });
+        for (condition, matches) in [
+            ("window-width >= 1440", true),
+            ("window-width > 1440", false),
+            ("window-height<900", true),
+            ("window-x <= -1", true),
+            ("window-y = 25", true),
+        ] {
+            let parsed = Condition::parse(condition).unwrap();
+            assert_eq!(parsed.matches(&placed).unwrap(), matches, "{condition}");
+        }
+        assert_eq!(
+            Condition::parse("window-x<=-1").unwrap().to_string(),
+            "window-x <= -1"
+        );
+
+        // Without geometry no size or position comparison matches
+        let unplaced = window("Ghostty", "zsh");
+        assert!(!Condition::parse("window-width > 0")
+            .unwrap()
+            .matches(&unplaced)
+            .unwrap());
+        assert!(!Condition::parse("window-width < 100000")
+            .unwrap()
+            .matches(&unplaced)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_regex_conditions_are_compiled_when_parsed() {
+        let condition = Condition::parse(r"window-title =~ '.*\.pdf$'").unwrap();
+        assert_eq!(condition.to_string(), r"window-title =~ '.*\.pdf$'");
+        assert!(condition.matches(&window("Preview", "paper.pdf")).unwrap());
+        assert!(!condition
+            .matches(&window("Preview", "paper.pdf.png"))
+            .unwrap());
+
+        assert!(Condition::parse("window-title =~ '(unclosed'").is_err());
+        assert!(Condition::parse("window-id =~ '4.'").is_err());
+    }
+
+    #[test]
+    fn test_substring_operators() {
+        let title = window("Chrome", "Docs - Google Chrome");
+        for (condition, matches) in [
+            ("window-title contains 'Google'", true),
+            ("window-title starts-with 'Docs'", true),
+            ("window-title ends-with 'Chrome'", true),
+            ("window-title ends-with 'Docs'", false),
+            ("app-name CONTAINS hrom", true),
+        ] {
+            let parsed = Condition::parse(condition).unwrap();
+            assert_eq!(parsed.matches(&title).unwrap(), matches, "{condition}");
+        }
+        assert_eq!(
+            Condition::parse("window-title starts-with Docs")
+                .unwrap()
+                .to_string(),
+            "window-title starts-with 'Docs'"
+        );
+        assert!(Condition::parse("window-width contains '1'").is_err());
+        assert!(Condition::parse("app-name includes 'x'").is_err());
+    }
+
+    #[test]
+    fn test_not_equals() {
+        let condition = Condition::parse("app-name!=Finder AND window-id != 7").unwrap();
+        assert_eq!(
+            condition.to_string(),
+            "app-name != 'Finder' AND window-id != 7"
+        );
+        assert!(condition.matches(&window("Chrome", "")).unwrap());
+        assert!(!condition.matches(&window("Finder", "")).unwrap());
+    }
+
+    #[test]
+    fn test_like_matches_wildcards() {
+        let condition = Condition::parse("app-name like 'IntelliJ*'").unwrap();
+        assert_eq!(condition.to_string(), "app-name like 'IntelliJ*'");
+        assert!(condition.matches(&window("IntelliJ IDEA", "")).unwrap());
+        assert!(!condition.matches(&window("Open IntelliJ", "")).unwrap());
+
+        let condition = Condition::parse("window-title LIKE '*- Google Chrome'").unwrap();
+        assert!(condition
+            .matches(&window("Chrome", "Docs (1) - Google Chrome"))
+            .unwrap());
+        assert!(Condition::parse("workspace like '?'")
+            .unwrap()
+            .matches(&window("Mail", ""))
+            .unwrap());
+        assert!(Condition::parse("window-id like '4*'").is_err());
+    }
+
+    #[test]
+    fn test_combined_conditions() {
+        let condition = Condition::parse(
+            "app-name = 'Slack' and not (window-title contains Huddle or window-id > 50)",
+        )
+        .unwrap();
+        assert_eq!(
+            condition.to_string(),
+            "app-name = 'Slack' AND NOT (window-title contains 'Huddle' OR window-id > 50)"
+        );
+        assert!(condition.matches(&window("Slack", "general")).unwrap());
+        assert!(!condition
+            .matches(&window("Slack", "Huddle: general"))
+            .unwrap());
+        assert!(!condition.matches(&window("Mail", "Inbox")).unwrap());
+        assert!(condition.mentions(Field::WindowTitle));
+
+        // AND binds tighter than OR
+        let condition =
+            Condition::parse("app-name=Mail OR app-name=Slack AND workspace=2").unwrap();
+        assert!(condition.matches(&window("Mail", "")).unwrap());
+        assert!(!condition.matches(&window("Slack", "")).unwrap());
+
+        let clauses = Condition::parse("app-name = 'Slack' AND NOT window-title = 'zsh'")
+            .unwrap()
+            .clauses(&window("Ghostty", "zsh"), &MatchContext::default())
+            .unwrap();
+        let failed: Vec<_> = clauses
+            .iter()
+            .filter(|clause| !clause.matched)
+            .map(|clause| clause.clause.as_str())
+            .collect();
+        assert_eq!(failed, ["app-name = 'Slack'", "NOT window-title = 'zsh'"]);
+
+        let condition = Condition::parse("window-title = \"Don't Panic\" OR app-name = 'Mail'");
+        assert_eq!(
+            condition.unwrap().to_string(),
+            "window-title = \"Don't Panic\" OR app-name = 'Mail'"
+        );
+
+        assert!(Condition::parse("(app-name = 'Slack'").is_err());
+        assert!(Condition::parse("app-name = 'Slack' AND").is_err());
+        assert!(Condition::parse("app-name = 'Slack' workspace = 2").is_err());
+    }
+
+    #[test]
+    fn test_failed_clause_reports_actual_value() {
+        let clauses = Condition::parse("app-name = 'Slack'")
+            .unwrap()
+            .clauses(&window("Ghostty", "zsh"), &MatchContext::default())
+            .unwrap();
+        assert_eq!(clauses.len(), 1);
+        assert!(!clauses[0].matched);
+        assert_eq!(clauses[0].actual, "Ghostty");
+    }
+}
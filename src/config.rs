@@ -1,27 +1,679 @@
+use crate::action::Action;
+use crate::condition::{self, Condition, ConditionError, Field, Operator};
+use crate::validate::{line_of, Severity};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::env;
+use std::error::Error;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use toml_edit::{ArrayOfTables, DocumentMut, ImDocument};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
+    #[serde(default)]
+    pub service: ServiceConfig,
+    #[serde(default, skip_serializing_if = "HooksConfig::is_empty")]
+    pub hooks: HooksConfig,
+    #[serde(default, skip_serializing_if = "SecurityConfig::is_empty")]
+    pub security: SecurityConfig,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<Webhook>,
+    /// Workspaces `bootstrap` sets up, by name, see [`crate::bootstrap`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub workspaces: BTreeMap<String, WorkspaceConfig>,
+    /// Which monitor each workspace goes to for a number of connected monitors, by
+    /// profile name, see [`crate::displays`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub display_profiles: BTreeMap<String, DisplayProfile>,
     pub rules: Vec<Rule>,
 }
 
+/// Firings per minute above which a rule raises an alarm, unless configured otherwise.
+pub const DEFAULT_RATE_ALARM_PER_MINUTE: u32 = 30;
+pub const DEFAULT_PARALLEL_ACTIONS: usize = 8;
+pub const DEFAULT_ACTION_TIMEOUT_MS: u64 = 10_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ServiceConfig {
+    /// Which window manager the rules drive; aerospace when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend: Option<BackendKind>,
+    /// Path to the aerospace binary, for environments (like launchd) whose PATH lacks it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aerospace_bin: Option<String>,
+    /// How long aerospace query results are reused, in milliseconds (0 disables caching).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_cache_ttl_ms: Option<u64>,
+    /// A rule firing more often than this within a minute raises an alarm, as that
+    /// usually means rules undo each other or a condition is too broad. 0 disables it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_alarm_per_minute: Option<u32>,
+    /// Localhost port to serve Prometheus metrics on at `/metrics`, see [`crate::metrics`].
+    /// Read when the service starts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics_port: Option<u16>,
+    /// File the metrics are written to after every refresh, for node_exporter's
+    /// textfile collector.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics_textfile: Option<String>,
+    /// How many actions on different windows are performed at once. 1 performs them
+    /// one after another.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_actions: Option<usize>,
+    /// How long a command, shortcut or AppleScript action may run before it's killed, in
+    /// milliseconds, unless its rule sets `timeout_ms`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action_timeout_ms: Option<u64>,
+    /// File each rule's hit counters are kept in, so they survive restarts. Read when
+    /// the service starts; without it counting starts over with every start.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_hits_file: Option<String>,
+    /// How `validate` and reloads report a rule targeting a workspace the window manager
+    /// doesn't know. Defaults to a warning.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unknown_workspaces: Option<Severity>,
+    /// How long a window's title must stay the same before title-based rules act on
+    /// it, in milliseconds, for apps like Electron ones that briefly show a placeholder
+    /// title. 0, the default, acts on every change right away.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_settle_ms: Option<u64>,
+}
+
+/// Shell commands run around every action, see [`crate::hooks`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HooksConfig {
+    /// Runs before each action; a non-zero exit status vetoes the action.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before_action: Option<String>,
+    /// Runs after each action, whether it succeeded or not.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_action: Option<String>,
+}
+
+impl HooksConfig {
+    pub fn is_empty(&self) -> bool {
+        self.before_action.is_none() && self.after_action.is_none()
+    }
+}
+
+/// Limits on the programs the config can run: the commands of empty-workspace and
+/// startup rules, capture actions and hooks, and the `osascript` and `shortcuts` behind
+/// AppleScript and shortcut actions. The service runs whatever is written into the
+/// config files it watches.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecurityConfig {
+    /// Executables commands may run; any when unset. An entry with a `/` allows only
+    /// that path, one without allows the executable of that name found through PATH.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_commands: Option<Vec<String>>,
+    /// Refuses commands containing shell metacharacters, so allowing `sh` doesn't
+    /// allow chaining or substituting other commands.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub refuse_shell_metacharacters: bool,
+}
+
+/// Characters that make a shell run more than the command it was given.
+const SHELL_METACHARACTERS: &[char] = &[';', '|', '&', '$', '`', '<', '>', '(', ')', '\n'];
+
+impl SecurityConfig {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Whether `command` may run, and if not, why.
+    pub fn check(&self, command: &str) -> Result<(), String> {
+        if self.refuse_shell_metacharacters {
+            if let Some(found) = command.chars().find(|c| SHELL_METACHARACTERS.contains(c)) {
+                return Err(format!(
+                    "Command '{command}' contains the shell metacharacter {found:?}"
+                ));
+            }
+        }
+        let program = shlex::split(command)
+            .and_then(|parts| parts.into_iter().next())
+            .unwrap_or_default();
+        if self.allows(&program) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Command '{command}' runs '{program}', which is not in [security] allowed_commands"
+            ))
+        }
+    }
+
+    /// Whether window action `action` may run, and if not, why. Shortcuts and
+    /// AppleScript snippets run `shortcuts` and `osascript`, so with `allowed_commands`
+    /// set those must be allowed.
+    pub fn check_action(&self, action: &Action) -> Result<(), String> {
+        let program = match action {
+            Action::Shortcut(_) => "shortcuts",
+            Action::AppleScript(_) => "osascript",
+            Action::Capture(command) => return self.check(command),
+            _ => return Ok(()),
+        };
+        if self.allows(program) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Action '{action}' runs '{program}', which is not in [security] allowed_commands"
+            ))
+        }
+    }
+
+    /// Whether a rule may set the variables in `env` for its command, and if not, why.
+    /// With `allowed_commands` set, variables that change which program an allowed name
+    /// runs or what gets loaded into it are refused.
+    pub fn check_env(&self, env: &BTreeMap<String, String>) -> Result<(), String> {
+        if self.allowed_commands.is_none() {
+            return Ok(());
+        }
+        match env.keys().find(|name| {
+            *name == "PATH" || name.starts_with("DYLD_") || name.starts_with("LD_")
+        }) {
+            Some(name) => Err(format!(
+                "Environment variable '{name}' can change what allowed commands run, so [security] allowed_commands refuses it"
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn allows(&self, program: &str) -> bool {
+        self.allowed_commands
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|entry| entry == program))
+    }
+
+    /// Both `self` and `base` apply: only executables both allow may run, and
+    /// metacharacters are refused if either refuses them.
+    fn merged_over(self, base: SecurityConfig) -> SecurityConfig {
+        let allowed_commands = match (self.allowed_commands, base.allowed_commands) {
+            (Some(own), Some(base)) => Some(
+                own.into_iter()
+                    .filter(|entry| base.contains(entry))
+                    .collect(),
+            ),
+            (own, base) => own.or(base),
+        };
+        SecurityConfig {
+            allowed_commands,
+            refuse_shell_metacharacters: self.refuse_shell_metacharacters
+                || base.refuse_shell_metacharacters,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct WorkspaceConfig {
+    /// Apps that should have a window on the workspace, by app name. Missing ones are
+    /// launched.
+    #[serde(default)]
+    pub apps: Vec<String>,
+    /// Aerospace layout to arrange the windows in, e.g. `tiles horizontal` or `accordion`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+}
+
+/// Workspace placement for one monitor setup, e.g. the laptop alone or docked to one
+/// external display.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DisplayProfile {
+    /// The profile applies while this many monitors are connected.
+    pub monitors: usize,
+    /// The monitor each workspace goes to, by workspace. Monitors are given like to
+    /// aerospace's `move-workspace-to-monitor`: `main`, `secondary`, a 1-based index or
+    /// a pattern matching the monitor name.
+    #[serde(default)]
+    pub workspaces: BTreeMap<String, String>,
+}
+
+/// A URL the service POSTs JSON to when selected events happen, see [`crate::webhooks`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default = "default_webhook_events")]
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WebhookEvent {
+    /// A rule performed an action successfully.
+    RuleFired,
+    ActionFailed,
+    /// The config file changed but couldn't be loaded.
+    ConfigReloadFailed,
+}
+
+fn default_webhook_events() -> Vec<WebhookEvent> {
+    vec![WebhookEvent::ActionFailed, WebhookEvent::ConfigReloadFailed]
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Aerospace,
+    Sway,
+    I3,
+    /// Windows only; requires the `komorebi` cargo feature.
+    Komorebi,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Rule {
     pub name: String,
+    /// Disabled rules stay in the config but are skipped during evaluation.
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    /// Free-form labels for grouping and filtering rules.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// How long one of the rule's commands, shortcuts or AppleScripts may run before it's
+    /// killed, in milliseconds. Defaults to `[service] action_timeout_ms`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// How much the service logs about this rule's matching and actions.
+    #[serde(default, skip_serializing_if = "RuleLog::is_info")]
+    pub log: RuleLog,
+    /// Shorthand for `log = "trace"`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub trace: bool,
+    /// What happens to the rule's other actions in an evaluation when one of them fails.
+    #[serde(default, skip_serializing_if = "OnError::is_continue")]
+    pub on_error: OnError,
+    /// Focuses the workspace the rule moved the focused window to, like aerospace's
+    /// `--focus-follows-window`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub follow: bool,
     #[serde(flatten)]
     pub rule_type: RuleType,
+    /// The window rule's condition, compiled when the config file was read, see
+    /// [`parse_config`].
+    #[serde(skip)]
+    pub compiled: Option<CompiledCondition>,
+}
+
+/// A window rule's condition as compiled from the config file, or why it doesn't
+/// compile.
+#[derive(Debug, Clone)]
+pub struct CompiledCondition {
+    pub condition: Result<Condition, ConditionError>,
+    /// 1-based line of the condition in the config file, when known.
+    pub line: Option<usize>,
+}
+
+impl Rule {
+    /// The window rule's condition, compiled when its config file was read or, for a
+    /// rule that didn't come from one, parsed now. `None` for other kinds of rule.
+    pub fn condition(&self) -> Option<Result<Cow<'_, Condition>, ConditionError>> {
+        let RuleType::Window { condition, .. } = &self.rule_type else {
+            return None;
+        };
+        Some(match &self.compiled {
+            Some(compiled) => compiled
+                .condition
+                .as_ref()
+                .map(Cow::Borrowed)
+                .map_err(Clone::clone),
+            None => condition.to_condition().map(Cow::Owned),
+        })
+    }
+
+    /// The rule's log level, with `trace = true` overriding `log`.
+    pub fn log_level(&self) -> RuleLog {
+        if self.trace {
+            RuleLog::Trace
+        } else {
+            self.log
+        }
+    }
+}
+
+/// Per-rule logging on top of the service's own lines. `debug` logs which windows the
+/// rule matched and how its actions went; `trace` also logs every comparison of its
+/// condition against every window.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleLog {
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+/// How a rule that acts several times in one evaluation, e.g. on several windows,
+/// handles one of those actions failing.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnError {
+    /// Performs the other actions regardless.
+    #[default]
+    Continue,
+    /// Skips the rule's actions after the failed one. The rule's actions are then
+    /// performed one after another, in the order they were planned.
+    Abort,
+    /// Like `abort`, and moves the windows the rule moved before the failure back to
+    /// the workspace they came from, as `undo` would.
+    Rollback,
+}
+
+impl OnError {
+    fn is_continue(&self) -> bool {
+        *self == OnError::Continue
+    }
+}
+
+impl RuleLog {
+    fn is_info(&self) -> bool {
+        *self == RuleLog::Info
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum RuleType {
     #[serde(rename = "window")]
-    Window { condition: String, action: String },
+    Window {
+        condition: RuleCondition,
+        action: String,
+        /// Workspaces the rule is limited to: it only sees windows on them and is only
+        /// evaluated when one of them is. Empty means every workspace.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        workspaces: Vec<String>,
+    },
     #[serde(rename = "empty-workspace")]
-    EmptyWorkspace { workspace: String, command: String },
+    EmptyWorkspace {
+        workspace: String,
+        command: String,
+        /// Directory the command runs in; a leading `~` stands for the home directory.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cwd: Option<String>,
+        /// Environment variables set for the command, on top of the service's own.
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        env: BTreeMap<String, String>,
+        /// Whether the command is left running once it started, rather than waited for
+        /// until it exits; only failing to start it fails the action. Defaults to `true`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        detach: Option<bool>,
+    },
+    /// Runs when the service starts and whenever the window manager restarts.
+    #[serde(rename = "startup")]
+    Startup { command: String },
+    /// A Rhai script that plans actions for the evaluated windows, see [`crate::script`].
+    #[serde(rename = "script")]
+    Script { script: String },
+}
+
+/// A window rule's condition: the condition DSL, e.g. `"app-name = 'Ghostty'"`, or the
+/// same comparisons as TOML tables, e.g. `{ field = "app-name", op = "equals", value =
+/// "Ghostty" }`, combined with `{ all_of = [...] }`, `{ any_of = [...] }` and
+/// `{ not = ... }`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(
+    untagged,
+    deny_unknown_fields,
+    expecting = "a condition, or a table of either all_of, any_of, not, or field, op and value"
+)]
+pub enum RuleCondition {
+    Text(String),
+    AllOf {
+        all_of: Vec<RuleCondition>,
+    },
+    AnyOf {
+        any_of: Vec<RuleCondition>,
+    },
+    Not {
+        not: Box<RuleCondition>,
+    },
+    Compare {
+        field: Field,
+        op: Operator,
+        value: ConditionValue,
+    },
+}
+
+/// The value a TOML condition compares against; lists go with `op = "in"`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ConditionValue {
+    Bool(bool),
+    Number(i64),
+    Text(String),
+    List(Vec<String>),
+}
+
+impl RuleCondition {
+    /// The condition the rule evaluates, parsed from the DSL or checked from the tables.
+    pub fn to_condition(&self) -> Result<Condition, ConditionError> {
+        let all = |conditions: &[RuleCondition], key| {
+            if conditions.is_empty() {
+                return Err(ConditionError(format!(
+                    "{key} needs at least one condition"
+                )));
+            }
+            conditions.iter().map(RuleCondition::to_condition).collect()
+        };
+        match self {
+            RuleCondition::Text(source) => Condition::parse(source),
+            RuleCondition::AllOf { all_of } => all(all_of, "all_of").map(Condition::And),
+            RuleCondition::AnyOf { any_of } => all(any_of, "any_of").map(Condition::Or),
+            RuleCondition::Not { not } => Ok(Condition::Not(Box::new(not.to_condition()?))),
+            RuleCondition::Compare {
+                field,
+                op: Operator::In,
+                value: ConditionValue::List(values),
+            } => condition::list_condition(field.name(), values.clone()),
+            RuleCondition::Compare {
+                field,
+                op: Operator::In,
+                ..
+            } => Err(ConditionError(format!(
+                "in needs a list of values, e.g. ['1', '2']: {}",
+                field.name()
+            ))),
+            RuleCondition::Compare {
+                field,
+                op,
+                value: ConditionValue::List(_),
+            } => Err(ConditionError(format!(
+                "Only in compares against a list: {} {op}",
+                field.name()
+            ))),
+            RuleCondition::Compare {
+                field,
+                op,
+                value: ConditionValue::Bool(value),
+            } => condition::compare_condition(field.name(), *op, &value.to_string()),
+            RuleCondition::Compare {
+                field,
+                op,
+                value: ConditionValue::Number(value),
+            } => condition::compare_condition(field.name(), *op, &value.to_string()),
+            RuleCondition::Compare {
+                field,
+                op,
+                value: ConditionValue::Text(value),
+            } => condition::compare_condition(field.name(), *op, value),
+        }
+    }
+}
+
+impl From<String> for RuleCondition {
+    fn from(source: String) -> Self {
+        RuleCondition::Text(source)
+    }
+}
+
+impl PartialEq<str> for RuleCondition {
+    fn eq(&self, other: &str) -> bool {
+        matches!(self, RuleCondition::Text(source) if source == other)
+    }
+}
+
+/// The DSL source of a text condition, and the DSL equivalent of a TOML one.
+impl fmt::Display for RuleCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self, self.to_condition()) {
+            (RuleCondition::Text(source), _) => f.write_str(source),
+            (_, Ok(condition)) => write!(f, "{condition}"),
+            (_, Err(e)) => write!(f, "<{e}>"),
+        }
+    }
+}
+
+/// Overrides where the system-wide config is looked for; empty disables it.
+pub const SYSTEM_CONFIG_ENV_VAR: &str = "AEROSPACE_RULES_SYSTEM_CONFIG";
+
+/// Where a system-wide config shared by every user of the machine is looked for, in
+/// order, e.g. a baseline ruleset managed by a team.
+pub const SYSTEM_CONFIG_PATHS: [&str; 2] = [
+    "/etc/aerospace-rules/rules.toml",
+    "/opt/homebrew/etc/aerospace-rules/rules.toml",
+];
+
+impl Config {
+    /// `[service] parallel_actions`, or its default.
+    pub fn parallel_actions(&self) -> usize {
+        self.service
+            .parallel_actions
+            .unwrap_or(DEFAULT_PARALLEL_ACTIONS)
+    }
+
+    /// Describes each enabled window rule whose condition doesn't compile, with its name
+    /// and line, in config order.
+    pub fn condition_errors(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .filter_map(|rule| {
+                let error = rule.condition()?.err()?;
+                let line = rule.compiled.as_ref().and_then(|compiled| compiled.line);
+                Some(match line {
+                    Some(line) => format!(
+                        "Rule '{}' (line {line}) has an invalid condition: {error}",
+                        rule.name
+                    ),
+                    None => format!("Rule '{}' has an invalid condition: {error}", rule.name),
+                })
+            })
+            .collect()
+    }
+
+    /// How long a changed title must stay the same before title-based rules act on it.
+    pub fn title_settle(&self) -> Duration {
+        Duration::from_millis(self.service.title_settle_ms.unwrap_or(0))
+    }
+
+    /// How long an action of `rule` may run: its `timeout_ms`, or `[service]
+    /// action_timeout_ms`, or the default.
+    pub fn action_timeout(&self, rule: &Rule) -> Duration {
+        let millis = rule.timeout_ms.unwrap_or(
+            self.service
+                .action_timeout_ms
+                .unwrap_or(DEFAULT_ACTION_TIMEOUT_MS),
+        );
+        Duration::from_millis(millis)
+    }
+
+    /// This config with the settings otherwise resolved at runtime written out, such
+    /// as the aerospace binary found through the environment or PATH.
+    pub fn effective(&self) -> Config {
+        let mut config = self.clone();
+        config.service.aerospace_bin = Some(crate::aerospace::binary().display().to_string());
+        config
+            .service
+            .query_cache_ttl_ms
+            .get_or_insert(crate::aerospace::DEFAULT_CACHE_TTL.as_millis() as u64);
+        config
+            .service
+            .rate_alarm_per_minute
+            .get_or_insert(DEFAULT_RATE_ALARM_PER_MINUTE);
+        config
+            .service
+            .parallel_actions
+            .get_or_insert(DEFAULT_PARALLEL_ACTIONS);
+        config
+            .service
+            .action_timeout_ms
+            .get_or_insert(DEFAULT_ACTION_TIMEOUT_MS);
+        config
+    }
+
+    /// This config layered over `base`: settings it leaves unset come from `base`,
+    /// webhooks and security limits of both apply, and its rules replace those of `base`
+    /// with the same name. Its workspace definitions and display profiles replace those
+    /// of `base` with the same name, too. The rules of `base` come first, so where move
+    /// rules disagree this config's win.
+    pub fn merged_over(self, base: Config) -> Config {
+        let service = ServiceConfig {
+            backend: self.service.backend.or(base.service.backend),
+            aerospace_bin: self.service.aerospace_bin.or(base.service.aerospace_bin),
+            query_cache_ttl_ms: self
+                .service
+                .query_cache_ttl_ms
+                .or(base.service.query_cache_ttl_ms),
+            rate_alarm_per_minute: self
+                .service
+                .rate_alarm_per_minute
+                .or(base.service.rate_alarm_per_minute),
+            metrics_port: self.service.metrics_port.or(base.service.metrics_port),
+            metrics_textfile: self
+                .service
+                .metrics_textfile
+                .or(base.service.metrics_textfile),
+            parallel_actions: self
+                .service
+                .parallel_actions
+                .or(base.service.parallel_actions),
+            action_timeout_ms: self
+                .service
+                .action_timeout_ms
+                .or(base.service.action_timeout_ms),
+            rule_hits_file: self.service.rule_hits_file.or(base.service.rule_hits_file),
+            unknown_workspaces: self
+                .service
+                .unknown_workspaces
+                .or(base.service.unknown_workspaces),
+            title_settle_ms: self
+                .service
+                .title_settle_ms
+                .or(base.service.title_settle_ms),
+        };
+        let hooks = HooksConfig {
+            before_action: self.hooks.before_action.or(base.hooks.before_action),
+            after_action: self.hooks.after_action.or(base.hooks.after_action),
+        };
+        let security = self.security.merged_over(base.security);
+        let mut webhooks = base.webhooks;
+        webhooks.extend(self.webhooks);
+        let mut workspaces = base.workspaces;
+        workspaces.extend(self.workspaces);
+        let mut display_profiles = base.display_profiles;
+        display_profiles.extend(self.display_profiles);
+        let mut rules: Vec<Rule> = base
+            .rules
+            .into_iter()
+            .filter(|rule| !self.rules.iter().any(|own| own.name == rule.name))
+            .collect();
+        rules.extend(self.rules);
+        Config {
+            service,
+            hooks,
+            security,
+            webhooks,
+            workspaces,
+            display_profiles,
+            rules,
+        }
+    }
+}
+
+fn enabled_by_default() -> bool {
+    true
 }
 
 fn find_config_file() -> Option<PathBuf> {
@@ -49,15 +701,150 @@ pub fn load_config() -> Option<Config> {
     load_config_from_path(None)
 }
 
+/// The explicitly given config path, or else the first existing default location.
+pub fn config_file_path(explicit_path: Option<&str>) -> Option<PathBuf> {
+    match explicit_path {
+        Some(path) => Some(PathBuf::from(path)),
+        None => find_config_file(),
+    }
+}
+
+/// The system-wide config to layer the user's over, if one exists.
+pub fn system_config_path() -> Option<PathBuf> {
+    match env::var(SYSTEM_CONFIG_ENV_VAR) {
+        Ok(path) if path.is_empty() => None,
+        Ok(path) => Some(PathBuf::from(path)),
+        Err(_) => SYSTEM_CONFIG_PATHS
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.exists()),
+    }
+}
+
 pub fn load_config_from_path(explicit_path: Option<&str>) -> Option<Config> {
-    let config_path = if let Some(path) = explicit_path {
-        PathBuf::from(path)
-    } else {
-        find_config_file()?
+    read_config(explicit_path).ok().flatten()
+}
+
+/// Like [`load_config_from_path`], but tells a missing config file (`Ok(None)`) apart
+/// from one that can't be loaded. The config is merged over the system-wide one, if any,
+/// see [`Config::merged_over`].
+pub fn read_config(explicit_path: Option<&str>) -> Result<Option<Config>, Box<dyn Error>> {
+    let user = match config_file_path(explicit_path) {
+        Some(path) => read_config_file(&path)?,
+        None => None,
+    };
+    let system = match system_config_path() {
+        Some(path) => read_config_file(&path)?,
+        None => None,
     };
+    Ok(match (user, system) {
+        (Some(user), Some(system)) => Some(user.merged_over(system)),
+        (user, system) => user.or(system),
+    })
+}
+
+fn read_config_file(path: &Path) -> Result<Option<Config>, Box<dyn Error>> {
+    let config_content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {e}", path.display()).into()),
+    };
+    Ok(Some(parse_config(&config_content).map_err(|e| {
+        format!("Failed to parse {}: {e}", path.display())
+    })?))
+}
+
+/// Parses the contents of a config file and compiles its window rules' conditions, so
+/// evaluations don't parse them again and broken ones are known, with their line, as
+/// soon as the config loads. A broken condition doesn't fail the config: the rule is
+/// skipped by evaluations and reported through [`Config::condition_errors`].
+pub fn parse_config(source: &str) -> Result<Config, toml::de::Error> {
+    let mut config: Config = toml::from_str(source)?;
+    // Only used for locations, so a document that serde accepted is assumed to parse
+    let document = ImDocument::parse(source).ok();
+    let rule_tables = document
+        .as_ref()
+        .and_then(|document| document.get("rules"))
+        .and_then(|rules| rules.as_array_of_tables());
+    for (index, rule) in config.rules.iter_mut().enumerate() {
+        let RuleType::Window { condition, .. } = &rule.rule_type else {
+            continue;
+        };
+        let line = rule_tables
+            .and_then(|tables| tables.get(index))
+            .and_then(|table| table.get("condition"))
+            .and_then(|item| item.span())
+            .map(|span| line_of(source, span.start));
+        rule.compiled = Some(CompiledCondition {
+            condition: condition.to_condition(),
+            line,
+        });
+    }
+    Ok(config)
+}
+
+// Rule edits are made on the config file's text, so its comments and formatting survive
+
+/// Appends `rule` to the config in `source`.
+pub fn add_rule(source: &str, rule: &Rule) -> Result<String, Box<dyn Error>> {
+    let mut document: DocumentMut = source.parse()?;
+    if rule_tables(&mut document)?
+        .iter()
+        .any(|table| table.get("name").and_then(|name| name.as_str()) == Some(&rule.name))
+    {
+        return Err(format!("A rule named '{}' already exists", rule.name).into());
+    }
+
+    let mut table = toml::to_string(rule)?
+        .parse::<DocumentMut>()?
+        .as_table()
+        .clone();
+    if rule.enabled {
+        // Rules are enabled by default
+        table.remove("enabled");
+    }
+    rule_tables(&mut document)?.push(table);
+    Ok(document.to_string())
+}
+
+/// Removes the rule named `name` from the config in `source`.
+pub fn remove_rule(source: &str, name: &str) -> Result<String, Box<dyn Error>> {
+    let mut document: DocumentMut = source.parse()?;
+    let rules = rule_tables(&mut document)?;
+    let index = rule_index(rules, name)?;
+    rules.remove(index);
+    Ok(document.to_string())
+}
+
+/// Sets whether the rule named `name` is enabled in the config in `source`.
+pub fn set_rule_enabled(source: &str, name: &str, enabled: bool) -> Result<String, Box<dyn Error>> {
+    let mut document: DocumentMut = source.parse()?;
+    let rule = rule_tables(&mut document)?
+        .iter_mut()
+        .find(|table| table.get("name").and_then(|item| item.as_str()) == Some(name))
+        .ok_or_else(|| format!("No rule named '{name}'"))?;
+    if enabled {
+        // Rules are enabled by default
+        rule.remove("enabled");
+    } else {
+        rule.insert("enabled", toml_edit::value(false));
+    }
+    Ok(document.to_string())
+}
+
+fn rule_tables(document: &mut DocumentMut) -> Result<&mut ArrayOfTables, Box<dyn Error>> {
+    document
+        .entry("rules")
+        .or_insert_with(|| ArrayOfTables::new().into())
+        .as_array_of_tables_mut()
+        .ok_or_else(|| "`rules` is not an array of tables".into())
+}
 
-    let config_content = fs::read_to_string(&config_path).ok()?;
-    toml::from_str::<Config>(&config_content).ok()
+fn rule_index(rules: &ArrayOfTables, name: &str) -> Result<usize, Box<dyn Error>> {
+    rules
+        .iter()
+        .position(|table| table.get("name").and_then(|item| item.as_str()) == Some(name))
+        .ok_or_else(|| format!("No rule named '{name}'").into())
 }
 
 #[cfg(test)]
@@ -96,7 +883,10 @@ action = "move-to-workspace 2"
         assert_eq!(config.rules.len(), 2);
 
         assert_eq!(config.rules[0].name, "Test Rule");
-        if let RuleType::Window { condition, action } = &config.rules[0].rule_type {
+        if let RuleType::Window {
+            condition, action, ..
+        } = &config.rules[0].rule_type
+        {
             assert_eq!(condition, "app-name = 'TestApp'");
             assert_eq!(action, "maximize");
         } else {
@@ -104,7 +894,10 @@ action = "move-to-workspace 2"
         }
 
         assert_eq!(config.rules[1].name, "Another Rule");
-        if let RuleType::Window { condition, action } = &config.rules[1].rule_type {
+        if let RuleType::Window {
+            condition, action, ..
+        } = &config.rules[1].rule_type
+        {
             assert_eq!(condition, "workspace = '1'");
             assert_eq!(action, "move-to-workspace 2");
         } else {
@@ -138,7 +931,10 @@ action = "move-to-workspace 2"
             assert_eq!(config.rules.len(), 3);
 
             assert_eq!(config.rules[0].name, "Test Rule");
-            if let RuleType::Window { condition, action } = &config.rules[0].rule_type {
+            if let RuleType::Window {
+                condition, action, ..
+            } = &config.rules[0].rule_type
+            {
                 assert_eq!(condition, "app-name = 'Ghostty'");
                 assert_eq!(action, "maximize");
             } else {
@@ -146,7 +942,10 @@ action = "move-to-workspace 2"
             }
 
             assert_eq!(config.rules[1].name, "Move IntelliJ");
-            if let RuleType::Window { condition, action } = &config.rules[1].rule_type {
+            if let RuleType::Window {
+                condition, action, ..
+            } = &config.rules[1].rule_type
+            {
                 assert_eq!(condition, "app-name = 'IntelliJ IDEA'");
                 assert_eq!(action, "move-to-workspace 5");
             } else {
@@ -155,7 +954,10 @@ action = "move-to-workspace 2"
 
             // Test empty workspace rule
             assert_eq!(config.rules[2].name, "Terminal for Empty Workspace 99");
-            if let RuleType::EmptyWorkspace { workspace, command } = &config.rules[2].rule_type {
+            if let RuleType::EmptyWorkspace {
+                workspace, command, ..
+            } = &config.rules[2].rule_type
+            {
                 assert_eq!(workspace, "99");
                 assert_eq!(command, "open -a Terminal");
             } else {
@@ -164,6 +966,63 @@ action = "move-to-workspace 2"
         }
     }
 
+    #[test]
+    fn test_toml_conditions() {
+        let config: Config = toml::from_str(
+            r#"
+[[rules]]
+name = "Chat"
+type = "window"
+action = "move-to-workspace 3"
+
+[rules.condition]
+all_of = [
+    { field = "app-name", op = "in", value = ["Slack", "Discord"] },
+    { not = { field = "window-title", op = "contains", value = "Huddle" } },
+    { any_of = [
+        { field = "window-width", op = "at-least", value = 800 },
+        { field = "is-focused", op = "equals", value = false },
+    ] },
+]
+"#,
+        )
+        .unwrap();
+        let RuleType::Window { condition, .. } = &config.rules[0].rule_type else {
+            panic!("Expected Window rule type");
+        };
+        assert_eq!(
+            condition.to_condition().unwrap(),
+            Condition::parse(
+                "app-name in ['Slack', 'Discord'] AND NOT window-title contains 'Huddle' \
+                 AND (window-width >= 800 OR is-focused = false)"
+            )
+            .unwrap()
+        );
+
+        #[derive(Deserialize)]
+        struct Table {
+            condition: RuleCondition,
+        }
+        let table = |source: &str| {
+            toml::from_str::<Table>(&format!("condition = {source}")).map(|table| table.condition)
+        };
+        assert!(table(r#"{ field = "app-name", op = "equals", vaule = "Slack" }"#).is_err());
+        assert!(table(r#"{ field = "app-nmae", op = "equals", value = "Slack" }"#).is_err());
+        // A table is one kind of condition, not several at once
+        assert!(table(
+            r#"{ not = { field = "is-focused", op = "equals", value = true }, field = "app-name" }"#
+        )
+        .is_err());
+        for invalid in [
+            "{ all_of = [] }",
+            r#"{ field = "workspace", op = "in", value = "1" }"#,
+            r#"{ field = "workspace", op = "equals", value = ["1"] }"#,
+            r#"{ field = "window-width", op = "contains", value = 8 }"#,
+        ] {
+            assert!(table(invalid).unwrap().to_condition().is_err(), "{invalid}");
+        }
+    }
+
     #[test]
     fn test_config_with_empty_workspace_rule() {
         let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
@@ -193,7 +1052,10 @@ command = "open -a Terminal"
         assert_eq!(config.rules.len(), 2);
 
         // Check window rule
-        if let RuleType::Window { condition, action } = &config.rules[0].rule_type {
+        if let RuleType::Window {
+            condition, action, ..
+        } = &config.rules[0].rule_type
+        {
             assert_eq!(condition, "app-name = 'TestApp'");
             assert_eq!(action, "maximize");
         } else {
@@ -201,7 +1063,10 @@ command = "open -a Terminal"
         }
 
         // Check empty workspace rule
-        if let RuleType::EmptyWorkspace { workspace, command } = &config.rules[1].rule_type {
+        if let RuleType::EmptyWorkspace {
+            workspace, command, ..
+        } = &config.rules[1].rule_type
+        {
             assert_eq!(workspace, "5");
             assert_eq!(command, "open -a Terminal");
         } else {
@@ -209,6 +1074,225 @@ command = "open -a Terminal"
         }
     }
 
+    #[test]
+    fn test_config_with_service_section() {
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        writeln!(
+            temp_file,
+            r#"
+[service]
+backend = "sway"
+aerospace_bin = "/opt/homebrew/bin/aerospace"
+query_cache_ttl_ms = 100
+
+[[rules]]
+name = "Simple Rule"
+type = "window"
+condition = "app-name = 'TestApp'"
+action = "maximize"
+        "#
+        )
+        .expect("Failed to write to temp file");
+
+        let config_path = temp_file.path().to_str().unwrap();
+        let config = load_config_from_path(Some(config_path)).expect("Config should load");
+
+        assert_eq!(
+            config.service.aerospace_bin.as_deref(),
+            Some("/opt/homebrew/bin/aerospace")
+        );
+        assert_eq!(config.service.query_cache_ttl_ms, Some(100));
+        assert_eq!(config.service.backend, Some(BackendKind::Sway));
+        assert_eq!(config.rules.len(), 1);
+    }
+
+    #[test]
+    fn test_config_with_startup_rule() {
+        let config: Config = toml::from_str(
+            r#"
+[[rules]]
+name = "Borders"
+type = "startup"
+tags = ["cosmetic"]
+command = "borders active_color=0xffe1e3e4"
+            "#,
+        )
+        .expect("Config should parse");
+
+        assert!(config.rules[0].enabled);
+        assert_eq!(config.rules[0].tags, ["cosmetic"]);
+        if let RuleType::Startup { command } = &config.rules[0].rule_type {
+            assert_eq!(command, "borders active_color=0xffe1e3e4");
+        } else {
+            panic!("Expected Startup rule type");
+        }
+    }
+
+    #[test]
+    fn test_rule_log_levels() {
+        let config: Config = toml::from_str(
+            r#"
+[[rules]]
+name = "Quiet"
+type = "startup"
+command = "true"
+
+[[rules]]
+name = "Debugged"
+type = "startup"
+command = "true"
+log = "debug"
+
+[[rules]]
+name = "Traced"
+type = "startup"
+command = "true"
+log = "debug"
+trace = true
+            "#,
+        )
+        .expect("Config should parse");
+
+        let levels: Vec<_> = config.rules.iter().map(Rule::log_level).collect();
+        assert_eq!(levels, [RuleLog::Info, RuleLog::Debug, RuleLog::Trace]);
+        assert!(!toml::to_string(&config.rules[0]).unwrap().contains("log"));
+    }
+
+    #[test]
+    fn test_rule_edits_keep_comments() {
+        let source = r#"# My rules
+
+[[rules]]
+# Chat goes to 9
+name = "Slack"
+type = "window"
+condition = "app-name = 'Slack'"
+action = "move-to-workspace 9"
+"#;
+        let rule = Rule {
+            name: "Mail".to_string(),
+            enabled: true,
+            tags: Vec::new(),
+            timeout_ms: None,
+            log: RuleLog::Info,
+            trace: false,
+            on_error: OnError::Continue,
+            follow: false,
+            rule_type: RuleType::Window {
+                condition: "app-name = 'Mail'".to_string().into(),
+                action: "move-to-workspace 8".to_string(),
+                workspaces: Vec::new(),
+            },
+            compiled: None,
+        };
+
+        let added = add_rule(source, &rule).unwrap();
+        assert!(added.starts_with(source));
+        let config: Config = toml::from_str(&added).unwrap();
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.rules[1].name, "Mail");
+        assert!(add_rule(&added, &rule).is_err());
+
+        let disabled = set_rule_enabled(&added, "Slack", false).unwrap();
+        assert!(disabled.contains("# Chat goes to 9"));
+        let config: Config = toml::from_str(&disabled).unwrap();
+        assert!(!config.rules[0].enabled);
+        let enabled = set_rule_enabled(&disabled, "Slack", true).unwrap();
+        assert_eq!(enabled, added);
+
+        let removed = remove_rule(&added, "Mail").unwrap();
+        assert_eq!(removed.trim_end(), source.trim_end());
+        assert!(remove_rule(&removed, "Mail").is_err());
+    }
+
+    #[test]
+    fn test_user_config_is_merged_over_system_config() {
+        let system: Config = toml::from_str(
+            r#"
+[service]
+backend = "sway"
+rate_alarm_per_minute = 10
+parallel_actions = 2
+
+[[rules]]
+name = "Slack"
+type = "window"
+condition = "app-name = 'Slack'"
+action = "move-to-workspace 9"
+
+[[rules]]
+name = "Mail"
+type = "window"
+condition = "app-name = 'Mail'"
+action = "move-to-workspace 8"
+"#,
+        )
+        .unwrap();
+        let user: Config = toml::from_str(
+            r#"
+[service]
+parallel_actions = 4
+
+[[rules]]
+name = "Notes"
+type = "window"
+condition = "app-name = 'Notes'"
+action = "maximize"
+
+[[rules]]
+name = "Slack"
+type = "window"
+condition = "app-name = 'Slack'"
+action = "move-to-workspace 2"
+"#,
+        )
+        .unwrap();
+
+        let merged = user.merged_over(system);
+        assert_eq!(merged.service.backend, Some(BackendKind::Sway));
+        assert_eq!(merged.service.rate_alarm_per_minute, Some(10));
+        assert_eq!(merged.service.parallel_actions, Some(4));
+        let names: Vec<&str> = merged.rules.iter().map(|rule| rule.name.as_str()).collect();
+        assert_eq!(names, ["Mail", "Notes", "Slack"]);
+        assert!(matches!(
+            &merged.rules[2].rule_type,
+            RuleType::Window { action, .. } if action == "move-to-workspace 2"
+        ));
+    }
+
+    #[test]
+    fn test_security_limits_commands() {
+        let system = SecurityConfig {
+            allowed_commands: Some(vec!["open".to_string(), "sh".to_string()]),
+            refuse_shell_metacharacters: true,
+        };
+        let user = SecurityConfig {
+            allowed_commands: Some(vec!["open".to_string(), "rm".to_string()]),
+            refuse_shell_metacharacters: false,
+        };
+
+        let security = user.merged_over(system);
+        assert_eq!(security.allowed_commands, Some(vec!["open".to_string()]));
+        assert!(security.check("open -a 'Google Chrome'").is_ok());
+        assert!(security.check("/tmp/open -a Mail").is_err());
+        assert!(security.check("rm -rf ~").is_err());
+        assert_eq!(
+            security.check("open -a Mail; rm -rf ~"),
+            Err(
+                "Command 'open -a Mail; rm -rf ~' contains the shell metacharacter ';'".to_string()
+            )
+        );
+        assert!(SecurityConfig::default().check("rm -rf ~").is_ok());
+
+        for name in ["PATH", "DYLD_INSERT_LIBRARIES", "LD_PRELOAD"] {
+            let env = BTreeMap::from([(name.to_string(), "/tmp/x".to_string())]);
+            assert!(security.check_env(&env).is_err());
+            assert!(SecurityConfig::default().check_env(&env).is_ok());
+        }
+        let env = BTreeMap::from([("PROJECT".to_string(), "web".to_string())]);
+        assert!(security.check_env(&env).is_ok());
+    }
+
     #[test]
     fn test_load_config_fallback_to_discovery() {
         // Test that load_config_from_path(None) falls back to find_config_file
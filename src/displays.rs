@@ -0,0 +1,90 @@
+//! Display profiles: which monitor each workspace goes to for a monitor setup, reinstated
+//! by the service whenever the connected monitors change, e.g. when docking a laptop.
+//!
+//! ```toml
+//! [display_profiles.laptop]
+//! monitors = 1
+//!
+//! [display_profiles.docked]
+//! monitors = 2
+//! workspaces = { "1" = "secondary", "6" = "main" }
+//! ```
+
+use crate::backend::WindowManager;
+use crate::config::{Config, DisplayProfile};
+use crate::rules::ActionResult;
+
+/// The profile for a setup of `monitors` connected monitors, the first by name if
+/// several expect that many.
+pub fn profile_for(config: &Config, monitors: usize) -> Option<(&str, &DisplayProfile)> {
+    config
+        .display_profiles
+        .iter()
+        .find(|(_, profile)| profile.monitors == monitors)
+        .map(|(name, profile)| (name.as_str(), profile))
+}
+
+/// Moves every workspace of the profile `name` to its monitor.
+pub fn apply(name: &str, profile: &DisplayProfile, wm: &dyn WindowManager) -> Vec<ActionResult> {
+    let label = format!("display profile {name}");
+    profile
+        .workspaces
+        .iter()
+        .map(|(workspace, monitor)| {
+            let result = wm
+                .move_workspace_to_monitor(workspace, monitor)
+                .map_err(|e| e.to_string());
+            ActionResult::for_workspace(
+                &label,
+                &format!("move-workspace-to-monitor {workspace} {monitor}"),
+                result,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Call, FakeWindowManager};
+
+    #[test]
+    fn test_profile_matching_the_monitor_count_is_applied() {
+        let config: Config = toml::from_str(
+            r#"
+rules = []
+
+[display_profiles.laptop]
+monitors = 1
+
+[display_profiles.docked]
+monitors = 2
+workspaces = { "1" = "secondary", "6" = "main" }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            profile_for(&config, 1).map(|(name, _)| name),
+            Some("laptop")
+        );
+        assert!(profile_for(&config, 3).is_none());
+
+        let wm = FakeWindowManager::default();
+        let (name, profile) = profile_for(&config, 2).unwrap();
+        let results = apply(name, profile, &wm);
+        assert!(results.iter().all(|result| result.success));
+        assert_eq!(
+            wm.calls(),
+            [
+                Call::WorkspaceToMonitor {
+                    workspace: "1".to_string(),
+                    monitor: "secondary".to_string()
+                },
+                Call::WorkspaceToMonitor {
+                    workspace: "6".to_string(),
+                    monitor: "main".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! A C API for embedding the rules engine, e.g. in a Swift menu bar app, built from
+//! the `ffi` feature. Declarations are in `include/aerospace_rules.h`.
+//!
+//! Every function takes NUL-terminated UTF-8 strings and returns a JSON document that
+//! the caller owns and frees with [`ar_string_free`]:
+//!
+//! ```json
+//! {"ok": <result>}
+//! {"error": "<message>"}
+//! ```
+
+use crate::config::{self, Config};
+use crate::rules::{self, PlannedAction, Trigger};
+use crate::validate;
+use crate::WindowInfo;
+use serde::Serialize;
+use std::ffi::{c_char, CStr, CString};
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Envelope<T> {
+    Ok(T),
+    Error(String),
+}
+
+fn respond<T: Serialize>(result: Result<T, String>) -> *mut c_char {
+    let envelope = match result {
+        Ok(value) => Envelope::Ok(value),
+        Err(e) => Envelope::Error(e),
+    };
+    let json = serde_json::to_string(&envelope)
+        .unwrap_or_else(|e| format!(r#"{{"error": "Failed to serialize result: {e}"}}"#));
+    // JSON escapes NUL, so the string has no interior NUL bytes
+    CString::new(json).unwrap_or_default().into_raw()
+}
+
+/// # Safety
+///
+/// `string` must be null or point to a NUL-terminated string.
+unsafe fn read<'a>(string: *const c_char, name: &str) -> Result<&'a str, String> {
+    if string.is_null() {
+        return Err(format!("{name} is null"));
+    }
+    CStr::from_ptr(string)
+        .to_str()
+        .map_err(|e| format!("{name} isn't UTF-8: {e}"))
+}
+
+/// Parses the TOML config `config` and returns it as JSON.
+///
+/// # Safety
+///
+/// `config` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ar_parse_config(config: *const c_char) -> *mut c_char {
+    respond(read(config, "config").and_then(|source| {
+        toml::from_str::<Config>(source).map_err(|e| format!("Invalid config: {e}"))
+    }))
+}
+
+/// Checks the TOML config `config` and returns its diagnostics, an empty array for a
+/// valid config.
+///
+/// # Safety
+///
+/// `config` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ar_validate_config(config: *const c_char) -> *mut c_char {
+    respond(read(config, "config").map(validate::validate_config))
+}
+
+/// Evaluates the rules of the TOML config `config` against `windows`, a JSON array of
+/// windows as the service lists them, and returns the actions they call for as the
+/// results of a dry run. Nothing is performed. With a non-null `workspace`, the
+/// windows are that workspace's contents.
+///
+/// # Safety
+///
+/// Each argument must be null or point to a NUL-terminated string; `config` and
+/// `windows` must not be null.
+#[no_mangle]
+pub unsafe extern "C" fn ar_evaluate(
+    config: *const c_char,
+    windows: *const c_char,
+    workspace: *const c_char,
+) -> *mut c_char {
+    let evaluate = || {
+        let config = config::parse_config(read(config, "config")?)
+            .map_err(|e| format!("Invalid config: {e}"))?;
+        let windows: Vec<WindowInfo> = serde_json::from_str(read(windows, "windows")?)
+            .map_err(|e| format!("Invalid windows: {e}"))?;
+        let trigger = if workspace.is_null() {
+            Trigger::Windows
+        } else {
+            Trigger::Workspace(read(workspace, "workspace")?)
+        };
+        let planned = rules::plan(&windows, &config, trigger).map_err(|e| e.to_string())?;
+        Ok(planned
+            .iter()
+            .map(PlannedAction::to_result)
+            .collect::<Vec<_>>())
+    };
+    respond(evaluate())
+}
+
+/// Frees a string returned by this library. Null is ignored.
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by this library, not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn ar_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(result: *mut c_char) -> serde_json::Value {
+        let json = unsafe { CStr::from_ptr(result) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        unsafe { ar_string_free(result) };
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_evaluate_returns_planned_actions() {
+        let config = CString::new(
+            r#"
+[[rules]]
+name = "Chat"
+type = "window"
+condition = "app-name = 'Slack'"
+action = "move-to-workspace 9"
+"#,
+        )
+        .unwrap();
+        let windows = CString::new(
+            r#"[{"app-name": "Slack", "window-id": 1, "window-title": "", "workspace": "1"}]"#,
+        )
+        .unwrap();
+
+        let response =
+            call(unsafe { ar_evaluate(config.as_ptr(), windows.as_ptr(), std::ptr::null()) });
+        assert_eq!(response["ok"][0]["action"], "move-to-workspace 9");
+        assert_eq!(response["ok"][0]["window_id"], 1);
+
+        let response =
+            call(unsafe { ar_evaluate(config.as_ptr(), std::ptr::null(), std::ptr::null()) });
+        assert_eq!(response["error"], "windows is null");
+    }
+}
@@ -0,0 +1,234 @@
+//! Callbacks run before and after every action an [`Executor`](crate::rules::Executor)
+//! performs. A hook that runs before an action can veto it.
+//!
+//! Shell hooks are declared in the config's `[hooks]` table and get the action's
+//! context through environment variables:
+//!
+//! | Variable          | Value                                               |
+//! |-------------------|-----------------------------------------------------|
+//! | `AR_HOOK`         | `before` or `after`                                 |
+//! | `AR_RULE_NAME`    | Name of the rule the action belongs to              |
+//! | `AR_ACTION`       | The action, or the command of a workspace rule      |
+//! | `AR_WINDOW_ID`    | ID of the targeted window, if any                   |
+//! | `AR_APP_NAME`     | App of the targeted window, if any                  |
+//! | `AR_WINDOW_TITLE` | Title of the targeted window, if any                |
+//! | `AR_WORKSPACE`    | Workspace of the targeted window, if any            |
+//! | `AR_SUCCESS`      | `true` or `false`; only for `after`                 |
+//! | `AR_ERROR`        | Why the action failed; only for failed `after` runs |
+//!
+//! A `before_action` hook that exits with a non-zero status vetoes the action, with
+//! its stderr as the reason.
+
+use crate::config::{HooksConfig, SecurityConfig};
+use crate::log_error;
+use crate::rules::{ActionResult, PlannedAction};
+use std::fmt;
+use std::process::Command;
+
+type BeforeHook = Box<dyn Fn(&PlannedAction) -> Result<(), String> + Send + Sync>;
+type AfterHook = Box<dyn Fn(&PlannedAction, &ActionResult) + Send + Sync>;
+
+/// The hooks an executor runs around each action.
+#[derive(Default)]
+pub struct Hooks {
+    before: Vec<BeforeHook>,
+    after: Vec<AfterHook>,
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("before", &self.before.len())
+            .field("after", &self.after.len())
+            .finish()
+    }
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The shell hooks declared in `config`, within the limits of `security`. A
+    /// `before_action` hook that `security` refuses vetoes every action, as it can't
+    /// approve any; a refused `after_action` hook doesn't run.
+    pub fn from_config(config: &HooksConfig, security: &SecurityConfig) -> Self {
+        let mut hooks = Self::new();
+        if let Some(command) = config.before_action.clone() {
+            match security.check(&command) {
+                Ok(()) => {
+                    hooks.before_action(move |action| run_shell_hook(&command, action, None));
+                }
+                Err(e) => {
+                    hooks.before_action(move |_| Err(e.clone()));
+                }
+            }
+        }
+        if let Some(command) = config.after_action.clone() {
+            match security.check(&command) {
+                Ok(()) => {
+                    hooks.after_action(move |action, result| {
+                        if let Err(e) = run_shell_hook(&command, action, Some(result)) {
+                            log_error!("after_action hook failed: {e}");
+                        }
+                    });
+                }
+                Err(e) => log_error!("after_action hook refused: {e}"),
+            }
+        }
+        hooks
+    }
+
+    /// Registers `hook` to run before every action. Returning an error vetoes the
+    /// action, which is then reported as failed with that error.
+    pub fn before_action(
+        &mut self,
+        hook: impl Fn(&PlannedAction) -> Result<(), String> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.before.push(Box::new(hook));
+        self
+    }
+
+    /// Registers `hook` to run after every action, including failed and vetoed ones.
+    pub fn after_action(
+        &mut self,
+        hook: impl Fn(&PlannedAction, &ActionResult) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.after.push(Box::new(hook));
+        self
+    }
+
+    /// Runs the before-action hooks in registration order, stopping at the first veto.
+    pub(crate) fn run_before(&self, action: &PlannedAction) -> Result<(), String> {
+        self.before
+            .iter()
+            .try_for_each(|hook| hook(action))
+            .map_err(|reason| format!("Vetoed by hook: {reason}"))
+    }
+
+    pub(crate) fn run_after(&self, action: &PlannedAction, result: &ActionResult) {
+        for hook in &self.after {
+            hook(action, result);
+        }
+    }
+}
+
+fn run_shell_hook(
+    command: &str,
+    action: &PlannedAction,
+    result: Option<&ActionResult>,
+) -> Result<(), String> {
+    let parts = shlex::split(command).ok_or_else(|| format!("Failed to parse hook: {command}"))?;
+    let Some((program, args)) = parts.split_first() else {
+        return Err("Empty hook command".to_string());
+    };
+
+    let mut process = Command::new(program);
+    process
+        .args(args)
+        .env("AR_HOOK", if result.is_some() { "after" } else { "before" })
+        .env("AR_RULE_NAME", action.rule());
+    match action {
+        PlannedAction::Window { action, window, .. } => {
+            process
+                .env("AR_ACTION", action)
+                .env("AR_WINDOW_ID", window.window_id.to_string())
+                .env("AR_APP_NAME", &window.app_name)
+                .env("AR_WINDOW_TITLE", &window.window_title)
+                .env("AR_WORKSPACE", &window.workspace);
+        }
+        PlannedAction::Command { command, .. } => {
+            process.env("AR_ACTION", command);
+        }
+    }
+    if let Some(result) = result {
+        process.env("AR_SUCCESS", result.success.to_string());
+        if let Some(error) = &result.error {
+            process.env("AR_ERROR", error);
+        }
+    }
+
+    let output = process
+        .output()
+        .map_err(|e| format!("Failed to run hook '{command}': {e}"))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(match stderr.trim() {
+        "" => format!("'{command}' exited with {}", output.status),
+        reason => reason.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WindowInfo;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_first_veto_stops_later_hooks() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut hooks = Hooks::new();
+        hooks
+            .before_action(|action| match action {
+                PlannedAction::Window { window, .. } if window.app_name == "Slack" => {
+                    Err("no Slack today".to_string())
+                }
+                _ => Ok(()),
+            })
+            .before_action(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            });
+
+        let action = |app_name: &str| PlannedAction::Window {
+            rule: "Move".to_string(),
+            action: "move-to-workspace 9".to_string(),
+            window: WindowInfo {
+                app_name: app_name.to_string(),
+                window_id: 1,
+                window_title: String::new(),
+                workspace: "1".to_string(),
+                monitor: None,
+                frame: None,
+                focused: false,
+            },
+        };
+        assert_eq!(
+            hooks.run_before(&action("Slack")),
+            Err("Vetoed by hook: no Slack today".to_string())
+        );
+        assert_eq!(hooks.run_before(&action("Safari")), Ok(()));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_refused_hooks_do_not_run() {
+        let config = HooksConfig {
+            before_action: Some("sh -c 'exit 0'".to_string()),
+            after_action: Some("sh -c 'exit 0'".to_string()),
+        };
+        let security = SecurityConfig {
+            allowed_commands: Some(vec!["open".to_string()]),
+            refuse_shell_metacharacters: false,
+        };
+        let hooks = Hooks::from_config(&config, &security);
+        assert_eq!(hooks.after.len(), 0);
+
+        let action = PlannedAction::Command {
+            rule: "Terminal".to_string(),
+            command: "open -a Ghostty".to_string(),
+            cwd: None,
+            env: Default::default(),
+            detach: true,
+        };
+        assert_eq!(
+            hooks.run_before(&action),
+            Err("Vetoed by hook: Command 'sh -c 'exit 0'' runs 'sh', which is not in [security] allowed_commands".to_string())
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Stub backend for komorebi, the Windows tiling window manager.
+//!
+//! Commands go to komorebi's named pipe as `SocketMessage` JSON. Reading state back
+//! over the pipe requires hosting a reply pipe, so window listing shells out to
+//! `komorebic state` for now. komorebi only acts on the focused window, so moving
+//! or maximizing arbitrary windows is not supported yet.
+
+use crate::backend::WindowManager;
+use crate::WindowInfo;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::process::Command;
+
+const PIPE_PATH: &str = r"\\.\pipe\komorebi";
+
+#[derive(Debug, Default)]
+pub struct Komorebi;
+
+impl Komorebi {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Sends a `SocketMessage` to komorebi's command pipe.
+    pub fn send(&self, message: &Value) -> Result<(), Box<dyn Error>> {
+        let mut pipe = OpenOptions::new().write(true).open(PIPE_PATH)?;
+        pipe.write_all(serde_json::to_string(message)?.as_bytes())?;
+        Ok(())
+    }
+
+    fn state(&self) -> Result<Value, Box<dyn Error>> {
+        let output = Command::new("komorebic").arg("state").output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "komorebic state failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )
+            .into());
+        }
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+
+    /// Switches the focused monitor to the named workspace.
+    pub fn focus_named_workspace(&self, workspace: &str) -> Result<(), Box<dyn Error>> {
+        self.send(&json!({ "type": "FocusNamedWorkspace", "content": workspace }))
+    }
+}
+
+impl WindowManager for Komorebi {
+    fn name(&self) -> &'static str {
+        "komorebi"
+    }
+
+    fn list_windows(&self) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
+        Ok(windows_from_state(&self.state()?))
+    }
+
+    fn list_windows_in_workspace(
+        &self,
+        workspace: &str,
+    ) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
+        Ok(self
+            .list_windows()?
+            .into_iter()
+            .filter(|window| window.workspace == workspace)
+            .collect())
+    }
+
+    fn move_window(&self, window_id: u32, _workspace: &str) -> Result<(), Box<dyn Error>> {
+        Err(format!("komorebi backend cannot move unfocused window {window_id} yet").into())
+    }
+
+    fn fullscreen_window(&self, window_id: u32) -> Result<(), Box<dyn Error>> {
+        Err(format!("komorebi backend cannot maximize unfocused window {window_id} yet").into())
+    }
+
+    fn focus_workspace(&self, workspace: &str) -> Result<(), Box<dyn Error>> {
+        self.focus_named_workspace(workspace)
+    }
+}
+
+/// Flattens komorebi's monitor/workspace/container/window state into `WindowInfo`s.
+/// Unnamed workspaces are identified by their 1-based index.
+fn windows_from_state(state: &Value) -> Vec<WindowInfo> {
+    let elements = |value: &Value| value["elements"].as_array().cloned().unwrap_or_default();
+    let mut windows = Vec::new();
+
+    for monitor in elements(&state["monitors"]) {
+        for (index, workspace) in elements(&monitor["workspaces"]).iter().enumerate() {
+            let workspace_name = workspace["name"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| (index + 1).to_string());
+
+            for container in elements(&workspace["containers"]) {
+                for window in elements(&container["windows"]) {
+                    let Some(hwnd) = window["hwnd"].as_u64() else {
+                        continue;
+                    };
+                    let exe = window["exe"].as_str().unwrap_or_default();
+
+                    windows.push(WindowInfo {
+                        app_name: exe.trim_end_matches(".exe").to_string(),
+                        window_id: hwnd as u32,
+                        window_title: window["title"].as_str().unwrap_or_default().to_string(),
+                        workspace: workspace_name.clone(),
+                        monitor: monitor["name"].as_str().map(str::to_string),
+                        frame: None,
+                        focused: false,
+                    });
+                }
+            }
+        }
+    }
+
+    windows
+}
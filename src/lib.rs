@@ -1,32 +1,494 @@
+#[cfg(all(target_os = "macos", feature = "accessibility"))]
+pub mod accessibility;
+pub mod action;
 pub mod aerospace;
+pub mod backend;
+pub mod bootstrap;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod compat;
+pub mod condition;
 pub mod config;
+pub mod displays;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod hooks;
+#[cfg(all(windows, feature = "komorebi"))]
+pub mod komorebi;
+pub mod logging;
+pub mod metrics;
+#[cfg(feature = "python")]
+pub mod python;
 pub mod rules;
+#[cfg(feature = "scripting")]
+pub mod script;
+#[cfg(feature = "service")]
+pub mod service;
+pub mod session;
+pub mod suggest;
+#[cfg(unix)]
+pub mod sway;
+pub mod telemetry;
+pub mod testing;
+pub mod tracking;
+pub mod validate;
+pub mod webhooks;
+pub mod window_index;
 
-pub use aerospace::WindowInfo;
+pub use aerospace::{AerospaceVersion, WindowFrame, WindowInfo};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Request {
-    GetWindows,
+    /// Windows matching `filter`, in the order it asks for.
+    GetWindows {
+        #[serde(default)]
+        filter: WindowFilter,
+    },
+    /// How the windows changed since the service answered with sequence number `seq`,
+    /// for pollers that keep their own copy; 0 asks for all of them. Answered with
+    /// [`Response::WindowsSince`].
+    GetWindowsSince {
+        seq: u64,
+    },
     GetConfig,
+    /// The loaded config with every default the service applies filled in.
+    GetEffectiveConfig,
+    /// Loaded rules together with how often each fired.
+    GetRules,
     Reload,
-    EvaluateRules { workspace: String },
+    EvaluateRules {
+        workspace: String,
+        /// Report what would happen without performing any action.
+        #[serde(default)]
+        dry_run: bool,
+        /// Match rules only against the windows that appeared on the workspace or changed
+        /// app or title since its rules were last evaluated, rather than all of them.
+        #[serde(default)]
+        changed_only: bool,
+    },
+    /// Evaluates the window rules against a single window, e.g. one that just opened.
+    EvaluateWindow {
+        window_id: u32,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    /// Sets up the workspaces defined in the config, or only `workspace`: launches the
+    /// apps missing from them, moves their windows there and applies their layout.
+    /// Answered with [`Response::RulesEvaluated`].
+    Bootstrap {
+        #[serde(default)]
+        workspace: Option<String>,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    /// Moves windows that aren't on the workspace their move rule assigns them to
+    /// back there, optionally only those assigned to `workspace`.
+    Gather {
+        #[serde(default)]
+        workspace: Option<String>,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    /// Moves windows back to the workspaces they were on in `session`, saved as `name`,
+    /// in one batch. Answered with [`Response::RulesEvaluated`].
+    RestoreSession {
+        name: String,
+        session: session::Session,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    /// Name of the focused workspace; answered with [`Response::FocusedWorkspace`].
+    GetFocusedWorkspace,
+    /// Enables or disables a rule, saving the change to the config file.
+    SetRuleEnabled {
+        name: String,
+        enabled: bool,
+    },
+    /// Appends a rule to the config file.
+    AddRule {
+        rule: config::Rule,
+    },
+    /// Deletes a rule from the config file.
+    RemoveRule {
+        name: String,
+    },
+    /// Checks every rule against the windows with this ID or app name, without acting.
+    ExplainWindow {
+        window_id: Option<u32>,
+        app_name: Option<String>,
+    },
+    /// Pinned windows are left alone by every rule.
+    SetWindowPinned {
+        window_id: u32,
+        pinned: bool,
+    },
+    GetPinnedWindows,
+    /// Suspends or resumes all rule actions; answered with [`Response::Paused`].
+    SetPaused {
+        paused: bool,
+    },
+    TogglePaused,
+    /// Whether rule actions are suspended; answered with [`Response::Paused`].
+    GetPaused,
+    /// Actions rules performed, oldest first, optionally only those since a point in
+    /// time, of one rule, or that failed.
+    GetHistory {
+        #[serde(default)]
+        since: Option<chrono::DateTime<chrono::Utc>>,
+        #[serde(default)]
+        rule: Option<String>,
+        #[serde(default)]
+        failed: bool,
+    },
+    /// Moves the windows of the `last` most recent successful rule moves back to the
+    /// workspace they came from.
+    Undo {
+        last: usize,
+    },
+    /// Where apps' windows spent their time since the service started and how often
+    /// each rule acted in the recorded history; answered with [`Response::Report`].
+    GetReport,
+    /// The last `lines` lines the service logged.
+    GetLogs {
+        lines: usize,
+    },
+    /// Keeps the connection open and streams newline-delimited [`Event`]s.
+    Subscribe,
+    /// Answered right away with [`Response::Pong`], telling how fresh the service's
+    /// window state is.
+    Ping,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Response {
     Windows(Vec<WindowInfo>),
-    Config(config::Config),
+    /// The window changes since the sequence number asked for, and the one to ask with
+    /// next time. With `reset` the changes are every current window, and windows the
+    /// caller knew of that aren't among them are gone, as when the changes since then
+    /// are no longer kept.
+    WindowsSince {
+        seq: u64,
+        changes: Vec<WindowChange>,
+        #[serde(default)]
+        reset: bool,
+        /// The window with keyboard focus.
+        focused: Option<u32>,
+    },
+    Config(Box<config::Config>),
     Success,
-    Error(String),
-    RulesEvaluated { actions_performed: Vec<String> },
+    Error {
+        kind: ErrorKind,
+        message: String,
+    },
+    /// Every action an evaluation performed, failed ones with their error.
+    RulesEvaluated {
+        results: Vec<rules::ActionResult>,
+        /// Set when any of the actions failed, while the others still ran.
+        #[serde(default)]
+        partial_failure: bool,
+    },
+    Rules(Vec<RuleStatus>),
+    Explained(Vec<rules::WindowExplanation>),
+    Logs(Vec<String>),
+    PinnedWindows(Vec<u32>),
+    /// Whether rule actions are suspended. Also the answer to evaluation requests
+    /// that were skipped because of it.
+    Paused(bool),
+    History(Vec<HistoryEntry>),
+    FocusedWorkspace(String),
+    /// The moves performed to undo earlier ones.
+    Undone(Vec<rules::ActionResult>),
+    Report(Report),
+    Pong(Pong),
+}
+
+impl Response {
+    pub fn evaluated(results: Vec<rules::ActionResult>) -> Self {
+        Response::RulesEvaluated {
+            partial_failure: results.iter().any(|result| !result.success),
+            results,
+        }
+    }
+
+    pub fn error(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Response::Error {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+/// The answer to [`Request::Ping`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pong {
+    /// The service's clock when it answered.
+    pub at: chrono::DateTime<chrono::Utc>,
+    /// How long ago the windows were last listed, in milliseconds; `None` before the
+    /// first listing. A service whose refreshes keep failing answers with an old one.
+    pub windows_age_ms: Option<u64>,
+}
+
+/// How a window changed, as reported by [`Response::WindowsSince`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum WindowChange {
+    /// The window appeared or changed.
+    Updated(WindowInfo),
+    Closed(u32),
+}
+
+/// What went wrong when the service answers with [`Response::Error`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// No config is loaded, or a rule in it can't be evaluated.
+    InvalidConfig,
+    /// The request named a rule or window that doesn't exist.
+    InvalidRequest,
+    /// The window manager or another dependency failed.
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RuleStatus {
+    pub rule: config::Rule,
+    /// Actions the rule produced since the service started, or since counting began
+    /// with `[service] rule_hits_file`.
+    pub hits: u64,
+    /// How many of those actions succeeded.
+    #[serde(default)]
+    pub executed: u64,
+    /// When the rule last produced an action; never if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_hit: Option<chrono::DateTime<chrono::Utc>>,
+    /// Whether the rule fired more often within the last minute than
+    /// `[service] rate_alarm_per_minute` allows.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rate_alarm: bool,
+}
+
+/// Hit counters of one rule, as kept by the service and in `[service] rule_hits_file`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleHits {
+    /// Actions the rule produced.
+    pub matched: u64,
+    /// How many of those actions succeeded.
+    pub executed: u64,
+    pub last_hit: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// An action a rule performed, as kept by the service.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub result: rules::ActionResult,
+    /// Workspace the window was on before the action.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_workspace: Option<String>,
+    /// Set once the action was reversed with [`Request::Undo`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub undone: bool,
+}
+
+/// Summary of the service's observations, for tuning workspace assignments.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Report {
+    /// When the service started, which is where time on workspaces is counted from.
+    pub since: chrono::DateTime<chrono::Utc>,
+    /// Time spent on each workspace, longest first. Counted per window, so two
+    /// windows of an app on a workspace for a minute add up to two minutes.
+    pub placements: Vec<AppPlacement>,
+    /// Rules that acted in the recorded history, most actions first.
+    pub rules: Vec<RuleActivity>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AppPlacement {
+    pub app_name: String,
+    pub workspace: String,
+    pub seconds: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RuleActivity {
+    pub rule: String,
+    pub actions: usize,
+    pub failed: usize,
+}
+
+/// Narrows down and orders a window listing.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct WindowFilter {
+    pub workspace: Option<String>,
+    /// Matched case-insensitively.
+    pub app_name: Option<String>,
+    /// Case-insensitive substring of the window title.
+    pub title_contains: Option<String>,
+    pub sort: Option<WindowSort>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum WindowSort {
+    App,
+    Workspace,
+    Id,
+}
+
+impl WindowFilter {
+    /// Whether the filter keeps every window, in the window manager's order.
+    pub fn is_empty(&self) -> bool {
+        self.workspace.is_none()
+            && self.app_name.is_none()
+            && self.title_contains.is_none()
+            && self.sort.is_none()
+    }
+
+    pub fn matches(&self, window: &WindowInfo) -> bool {
+        self.workspace
+            .as_ref()
+            .is_none_or(|workspace| window.workspace == *workspace)
+            && self
+                .app_name
+                .as_ref()
+                .is_none_or(|app| window.app_name.eq_ignore_ascii_case(app))
+            && self.title_contains.as_ref().is_none_or(|title| {
+                window
+                    .window_title
+                    .to_lowercase()
+                    .contains(&title.to_lowercase())
+            })
+    }
+
+    /// Drops the windows that don't match and sorts the rest. Sorting is stable, so
+    /// windows that compare equal keep the window manager's order.
+    pub fn apply(&self, mut windows: Vec<WindowInfo>) -> Vec<WindowInfo> {
+        windows.retain(|window| self.matches(window));
+        match self.sort {
+            Some(WindowSort::App) => windows.sort_by_key(|window| window.app_name.to_lowercase()),
+            Some(WindowSort::Workspace) => windows.sort_by(|a, b| a.workspace.cmp(&b.workspace)),
+            Some(WindowSort::Id) => windows.sort_by_key(|window| window.window_id),
+            None => {}
+        }
+        windows
+    }
+}
+
+/// What happened in the service, pushed to clients that sent [`Request::Subscribe`] and
+/// delivered to the configured webhooks.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Event {
+    WindowManagerRestarted {
+        backend: String,
+        pid: Option<u32>,
+    },
+    WindowCreated {
+        window: WindowInfo,
+    },
+    WindowClosed {
+        window: WindowInfo,
+    },
+    /// `window` moved to its current workspace from `from`.
+    WindowMoved {
+        window: WindowInfo,
+        from: String,
+    },
+    /// The focused workspace changed to `workspace`.
+    WorkspaceFocused {
+        workspace: String,
+        previous: Option<String>,
+    },
+    /// A rule performed an action successfully.
+    RuleFired {
+        result: rules::ActionResult,
+    },
+    /// A rule's action failed; `result` holds the error.
+    ActionFailed {
+        result: rules::ActionResult,
+    },
+    /// The config was reloaded and now holds `rules` rules. `warnings` and `errors` are
+    /// what validating the config file found; rules with errors don't work.
+    ConfigReloaded {
+        rules: usize,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        errors: Vec<String>,
+    },
+    /// `rule` fired `per_minute` times within the last minute, more than
+    /// `[service] rate_alarm_per_minute` allows. Sent once each time the rate is exceeded.
+    RuleRateExceeded {
+        rule: String,
+        per_minute: usize,
+    },
+    /// The config changed but couldn't be loaded, leaving the service without rules.
+    ConfigReloadFailed {
+        error: String,
+    },
+    /// A line the service logged.
+    Log {
+        message: String,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub struct ServiceState {
-    pub windows: Vec<WindowInfo>,
+    pub backend: std::sync::Arc<dyn backend::WindowManager>,
+    /// Set when the backend was chosen by the embedding program, so `[service] backend`
+    /// doesn't replace it.
+    pub backend_fixed: bool,
+    /// Shared with requests reading it, so replacing it never waits for them to finish.
+    pub windows: std::sync::Arc<window_index::WindowIndex>,
+    /// When `windows` was last listed.
+    pub windows_listed_at: Option<std::time::Instant>,
+    /// Counts the listings of `windows` that changed it, numbering the answers to
+    /// [`Request::GetWindowsSince`].
+    pub window_seq: u64,
+    /// What changed in the most recent of those listings, oldest first, with the
+    /// `window_seq` each one brought.
+    pub window_changes: std::collections::VecDeque<(u64, Vec<WindowChange>)>,
+    /// The serialized answer to an unfiltered [`Request::GetWindows`], together with the
+    /// focused window it was made for. Cleared whenever `windows` changes.
+    pub windows_json: Option<(Option<u32>, std::sync::Arc<[u8]>)>,
     pub config: Option<config::Config>,
+    /// The rules of `config`, indexed; replaced together with it.
+    pub rule_index: std::sync::Arc<rules::RuleIndex>,
     pub config_path: Option<String>,
+    pub aerospace_version: Option<AerospaceVersion>,
+    /// PID of the window manager process last seen, used to detect restarts.
+    pub wm_pid: Option<u32>,
+    /// How often each rule produced and performed actions, by rule name.
+    pub rule_hits: std::collections::HashMap<String, RuleHits>,
+    /// When each rule fired within the last minute, oldest first, by rule name.
+    pub rule_firings:
+        std::collections::HashMap<String, std::collections::VecDeque<std::time::Instant>>,
+    /// Windows each workspace held when its rules were last evaluated, by workspace.
+    pub evaluated_windows: std::collections::HashMap<String, Vec<WindowInfo>>,
+    /// How long windows of each app were on each workspace, by app name and workspace,
+    /// up to when `windows` was last listed.
+    pub placement_time: std::collections::HashMap<(String, String), std::time::Duration>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    /// The workspace last seen focused, to announce when focus moves.
+    pub focused_workspace: Option<String>,
+    /// The workspace focused before `focused_workspace`.
+    pub previous_workspace: Option<String>,
+    /// The window with keyboard focus when `windows` was last listed.
+    pub focused_window: Option<u32>,
+    /// Names of the monitors connected when `windows` was last listed.
+    pub monitors: Vec<String>,
+    /// The display profile applied for those monitors.
+    pub display_profile: Option<String>,
+    /// What was seen of each window since it opened. Shared with evaluations reading
+    /// it, like `windows`.
+    pub tracker: std::sync::Arc<tracking::WindowTracker>,
+    /// Windows excluded from rule evaluation.
+    pub pinned_windows: std::collections::HashSet<u32>,
+    /// The most recent actions rules performed, oldest first.
+    pub history: std::collections::VecDeque<HistoryEntry>,
+    /// Rules perform no actions while set, though dry runs still report what they would do.
+    pub paused: bool,
 }
 
 pub const SOCKET_PATH: &str = "/tmp/aerospace-rules.sock";
+/// Holds the PID of the running service.
+pub const PID_PATH: &str = "/tmp/aerospace-rules.pid";
@@ -0,0 +1,51 @@
+//! Service log output. Lines go to stdout/stderr as before and to an optional sink,
+//! which the service uses to keep recent lines and stream them to subscribers.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+type Sink = Box<dyn Fn(&str) + Send + Sync>;
+
+static SINK: OnceLock<Sink> = OnceLock::new();
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Installs the function that receives every logged line. Only the first call has an effect.
+pub fn set_sink(sink: impl Fn(&str) + Send + Sync + 'static) {
+    let _ = SINK.set(Box::new(sink));
+}
+
+/// Keeps info lines off stdout, for programs whose stdout is their actual output.
+/// Errors still go to stderr.
+pub fn quiet() {
+    QUIET.store(true, Ordering::Relaxed);
+}
+
+#[doc(hidden)]
+pub fn write(error: bool, args: fmt::Arguments<'_>) {
+    let line = args.to_string();
+    if error {
+        eprintln!("{line}");
+    } else if !QUIET.load(Ordering::Relaxed) {
+        println!("{line}");
+    }
+    if let Some(sink) = SINK.get() {
+        sink(&line);
+    }
+}
+
+/// Logs a line to stdout and the installed sink.
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        $crate::logging::write(false, format_args!($($arg)*))
+    };
+}
+
+/// Logs a line to stderr and the installed sink.
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        $crate::logging::write(true, format_args!($($arg)*))
+    };
+}
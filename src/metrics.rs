@@ -0,0 +1,197 @@
+//! Counters and histograms of the service's work, rendered in the Prometheus text
+//! format. The service serves them on `http://127.0.0.1:<metrics_port>/metrics` and
+//! writes them to `metrics_textfile` (for node_exporter's textfile collector) when
+//! those `[service]` settings are given.
+//!
+//! | Metric                                            | Type      | Labels            |
+//! |---------------------------------------------------|-----------|-------------------|
+//! | `aerospace_rules_evaluations_total`               | counter   | `trigger`         |
+//! | `aerospace_rules_evaluation_duration_seconds`     | histogram | `trigger`         |
+//! | `aerospace_rules_actions_total`                   | counter   | `rule`, `outcome` |
+//! | `aerospace_rules_aerospace_call_duration_seconds` | histogram | `command`         |
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds of the histogram buckets, in seconds.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics::new());
+
+#[derive(Debug)]
+struct Metrics {
+    evaluations: BTreeMap<&'static str, Histogram>,
+    actions: BTreeMap<(String, &'static str), u64>,
+    aerospace_calls: BTreeMap<String, Histogram>,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative.
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            evaluations: BTreeMap::new(),
+            actions: BTreeMap::new(),
+            aerospace_calls: BTreeMap::new(),
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    fn render(&self, out: &mut String, name: &str, label: &str, value: &str) {
+        let value = escape(value);
+        let mut cumulative = 0;
+        for (bound, observed) in BUCKETS.iter().zip(self.buckets) {
+            cumulative += observed;
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{label}=\"{value}\",le=\"{bound}\"}} {cumulative}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{label}=\"{value}\",le=\"+Inf\"}} {}",
+            self.count
+        );
+        let _ = writeln!(out, "{name}_sum{{{label}=\"{value}\"}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{label}=\"{value}\"}} {}", self.count);
+    }
+}
+
+/// Records an evaluation of the rules for `trigger` that took `duration`.
+pub fn record_evaluation(trigger: &'static str, duration: Duration) {
+    METRICS
+        .lock()
+        .unwrap()
+        .evaluations
+        .entry(trigger)
+        .or_default()
+        .observe(duration);
+}
+
+/// Records an action of `rule` that was performed, or failed to be.
+pub fn record_action(rule: &str, success: bool) {
+    let outcome = if success { "success" } else { "failure" };
+    *METRICS
+        .lock()
+        .unwrap()
+        .actions
+        .entry((rule.to_string(), outcome))
+        .or_default() += 1;
+}
+
+/// Records a call of the aerospace CLI's `command` that took `duration`.
+pub fn record_aerospace_call(command: &str, duration: Duration) {
+    let mut metrics = METRICS.lock().unwrap();
+    match metrics.aerospace_calls.get_mut(command) {
+        Some(histogram) => histogram.observe(duration),
+        None => {
+            let mut histogram = Histogram::default();
+            histogram.observe(duration);
+            metrics
+                .aerospace_calls
+                .insert(command.to_string(), histogram);
+        }
+    }
+}
+
+/// Every metric recorded so far, in the Prometheus text exposition format.
+pub fn render() -> String {
+    let metrics = METRICS.lock().unwrap();
+    let mut out = String::new();
+
+    out.push_str("# HELP aerospace_rules_evaluations_total Rule evaluations by trigger.\n");
+    out.push_str("# TYPE aerospace_rules_evaluations_total counter\n");
+    for (trigger, histogram) in &metrics.evaluations {
+        let _ = writeln!(
+            out,
+            "aerospace_rules_evaluations_total{{trigger=\"{trigger}\"}} {}",
+            histogram.count
+        );
+    }
+
+    out.push_str(
+        "# HELP aerospace_rules_evaluation_duration_seconds Time to plan and perform an evaluation.\n",
+    );
+    out.push_str("# TYPE aerospace_rules_evaluation_duration_seconds histogram\n");
+    for (trigger, histogram) in &metrics.evaluations {
+        histogram.render(
+            &mut out,
+            "aerospace_rules_evaluation_duration_seconds",
+            "trigger",
+            trigger,
+        );
+    }
+
+    out.push_str("# HELP aerospace_rules_actions_total Actions performed, by rule and outcome.\n");
+    out.push_str("# TYPE aerospace_rules_actions_total counter\n");
+    for ((rule, outcome), count) in &metrics.actions {
+        let _ = writeln!(
+            out,
+            "aerospace_rules_actions_total{{rule=\"{}\",outcome=\"{outcome}\"}} {count}",
+            escape(rule)
+        );
+    }
+
+    out.push_str(
+        "# HELP aerospace_rules_aerospace_call_duration_seconds Latency of aerospace CLI calls.\n",
+    );
+    out.push_str("# TYPE aerospace_rules_aerospace_call_duration_seconds histogram\n");
+    for (command, histogram) in &metrics.aerospace_calls {
+        histogram.render(
+            &mut out,
+            "aerospace_rules_aerospace_call_duration_seconds",
+            "command",
+            command,
+        );
+    }
+
+    out
+}
+
+fn escape(label_value: &str) -> String {
+    label_value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::default();
+        histogram.observe(Duration::from_millis(3));
+        histogram.observe(Duration::from_millis(30));
+        histogram.observe(Duration::from_secs(60));
+
+        let mut out = String::new();
+        histogram.render(&mut out, "calls", "command", "say \"hi\"");
+        assert!(out.contains("calls_bucket{command=\"say \\\"hi\\\"\",le=\"0.005\"} 1\n"));
+        assert!(out.contains("calls_bucket{command=\"say \\\"hi\\\"\",le=\"0.05\"} 2\n"));
+        assert!(out.contains("calls_bucket{command=\"say \\\"hi\\\"\",le=\"10\"} 2\n"));
+        assert!(out.contains("calls_bucket{command=\"say \\\"hi\\\"\",le=\"+Inf\"} 3\n"));
+        assert!(out.contains("calls_count{command=\"say \\\"hi\\\"\"} 3\n"));
+    }
+}
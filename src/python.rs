@@ -0,0 +1,219 @@
+//! Python bindings, built with [maturin](https://www.maturin.rs) from the `python`
+//! feature: `maturin develop --features python`.
+//!
+//! ```python
+//! import aerospace_rules
+//!
+//! config = aerospace_rules.load_config()
+//! condition = aerospace_rules.Condition("app-name = 'Slack'")
+//! client = aerospace_rules.Client()
+//! slack = [w for w in client.windows() if condition.matches(w)]
+//! failures = client.history(failed=True)
+//! ```
+//!
+//! Configs, windows, results and history entries cross over as the dicts and lists
+//! of their JSON form on the socket, e.g. windows have `app-name`, `window-id`,
+//! `window-title` and `workspace` keys. Errors are raised as `ValueError`, or as
+//! `ConnectionError` when the service can't be reached.
+
+use crate::client::{ClientError, ServiceError};
+use crate::condition;
+use crate::config::{self, Config};
+use crate::rules::{self, PlannedAction, Trigger};
+use crate::WindowInfo;
+use pyo3::exceptions::{PyConnectionError, PyValueError};
+use pyo3::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Display;
+use std::future::Future;
+
+fn value_error(e: impl Display) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+fn client_error(e: ClientError) -> PyErr {
+    match e.downcast::<ServiceError>() {
+        Ok(e) => PyValueError::new_err(e.message),
+        Err(e) => PyConnectionError::new_err(e.to_string()),
+    }
+}
+
+fn to_python<'py, T: Serialize>(py: Python<'py>, value: &T) -> PyResult<Bound<'py, PyAny>> {
+    let json = serde_json::to_string(value).map_err(value_error)?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+fn from_python<T: DeserializeOwned>(value: &Bound<'_, PyAny>) -> PyResult<T> {
+    let json: String = value
+        .py()
+        .import("json")?
+        .call_method1("dumps", (value,))?
+        .extract()?;
+    serde_json::from_str(&json).map_err(value_error)
+}
+
+/// Loads the config from `path`, or the first default location that has one.
+/// Returns `None` if there is no config file.
+#[pyfunction]
+#[pyo3(signature = (path=None))]
+fn load_config<'py>(py: Python<'py>, path: Option<&str>) -> PyResult<Option<Bound<'py, PyAny>>> {
+    config::read_config(path)
+        .map_err(value_error)?
+        .map(|config| to_python(py, &config))
+        .transpose()
+}
+
+/// Parses a config from TOML source.
+#[pyfunction]
+fn parse_config<'py>(py: Python<'py>, source: &str) -> PyResult<Bound<'py, PyAny>> {
+    let config: Config = toml::from_str(source).map_err(value_error)?;
+    to_python(py, &config)
+}
+
+/// The actions the rules of `config` call for on `windows`, as the results a dry run
+/// reports. With `workspace`, the windows are that workspace's contents.
+#[pyfunction]
+#[pyo3(signature = (config, windows, workspace=None))]
+fn plan<'py>(
+    py: Python<'py>,
+    config: &Bound<'py, PyAny>,
+    windows: &Bound<'py, PyAny>,
+    workspace: Option<&str>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let config: Config = from_python(config)?;
+    let windows: Vec<WindowInfo> = from_python(windows)?;
+    let trigger = workspace.map_or(Trigger::Windows, Trigger::Workspace);
+    let planned = rules::plan(&windows, &config, trigger).map_err(value_error)?;
+    let results: Vec<_> = planned.iter().map(PlannedAction::to_result).collect();
+    to_python(py, &results)
+}
+
+/// A parsed condition, e.g. `Condition("app-name = 'Slack'")`.
+#[pyclass(name = "Condition", frozen)]
+struct PyCondition(condition::Condition);
+
+#[pymethods]
+impl PyCondition {
+    #[new]
+    fn new(source: &str) -> PyResult<Self> {
+        condition::Condition::parse(source)
+            .map(Self)
+            .map_err(value_error)
+    }
+
+    /// Whether `window` satisfies the condition.
+    fn matches(&self, window: &Bound<'_, PyAny>) -> PyResult<bool> {
+        let window: WindowInfo = from_python(window)?;
+        self.0.matches(&window).map_err(value_error)
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Condition({:?})", self.0.to_string())
+    }
+}
+
+/// A blocking client for a running service.
+#[pyclass(name = "Client", frozen)]
+struct PyClient {
+    client: crate::client::Client,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl PyClient {
+    /// Runs `request` with the GIL released.
+    fn call<T: Send>(
+        &self,
+        py: Python<'_>,
+        request: impl Future<Output = Result<T, ClientError>> + Send,
+    ) -> PyResult<T> {
+        py.detach(|| self.runtime.block_on(request))
+            .map_err(client_error)
+    }
+}
+
+#[pymethods]
+impl PyClient {
+    /// A client for the service on `socket_path`, by default the installed service's.
+    #[new]
+    #[pyo3(signature = (socket_path=None))]
+    fn new(socket_path: Option<&str>) -> PyResult<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self {
+            client: socket_path.map_or_else(
+                crate::client::Client::new,
+                crate::client::Client::with_socket,
+            ),
+            runtime,
+        })
+    }
+
+    /// Sends a raw request, e.g. `{"EvaluateRules": {"workspace": "1"}}`, and returns
+    /// the response as is.
+    fn request<'py>(
+        &self,
+        py: Python<'py>,
+        request: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let request = from_python(request)?;
+        let response = self.call(py, self.client.request(&request))?;
+        to_python(py, &response)
+    }
+
+    fn windows<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let windows = self.call(py, self.client.get_windows())?;
+        to_python(py, &windows)
+    }
+
+    fn config<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let config = self.call(py, self.client.get_config())?;
+        to_python(py, &config)
+    }
+
+    fn rules<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let rules = self.call(py, self.client.get_rules())?;
+        to_python(py, &rules)
+    }
+
+    /// Actions rules performed, oldest first. `since` is an RFC 3339 timestamp.
+    #[pyo3(signature = (since=None, rule=None, failed=false))]
+    fn history<'py>(
+        &self,
+        py: Python<'py>,
+        since: Option<&str>,
+        rule: Option<String>,
+        failed: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let since = since
+            .map(|since| since.parse::<chrono::DateTime<chrono::Utc>>())
+            .transpose()
+            .map_err(value_error)?;
+        let entries = self.call(py, self.client.get_history(since, rule, failed))?;
+        to_python(py, &entries)
+    }
+
+    fn evaluate<'py>(&self, py: Python<'py>, workspace: &str) -> PyResult<Bound<'py, PyAny>> {
+        let results = self.call(py, self.client.evaluate(workspace))?;
+        to_python(py, &results)
+    }
+
+    fn reload(&self, py: Python<'_>) -> PyResult<()> {
+        self.call(py, self.client.reload())
+    }
+}
+
+#[pymodule]
+fn aerospace_rules(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(load_config, module)?)?;
+    module.add_function(wrap_pyfunction!(parse_config, module)?)?;
+    module.add_function(wrap_pyfunction!(plan, module)?)?;
+    module.add_class::<PyCondition>()?;
+    module.add_class::<PyClient>()?;
+    Ok(())
+}
@@ -1,213 +1,2185 @@
 use crate::{
-    config::{Config, RuleType},
+    action::Action,
+    backend::{MoveBatch, WindowManager},
+    condition::{Condition, Field, MatchContext, Operator, Value},
+    config::{self, Config, OnError, Rule, RuleLog, RuleType, SecurityConfig},
+    hooks::Hooks,
+    log_error, log_info, metrics,
+    telemetry::Span,
+    tracking::{WindowRecord, WindowTracker},
     WindowInfo,
 };
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
-use std::process::Command;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::Read;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-pub fn evaluate_rules_for_workspace(
-    workspace: &str,
-    _windows: &[WindowInfo],
-    focused_workspace_windows: Vec<WindowInfo>,
-    config: &Config,
-) -> Result<Vec<String>, Box<dyn Error>> {
-    let mut actions_performed = Vec::new();
+/// How often a running command, shortcut or AppleScript is checked for having exited.
+const PROGRAM_POLL_INTERVAL: Duration = Duration::from_millis(5);
 
-    println!(
-        "Evaluating {} rules for workspace {workspace}",
-        config.rules.len()
-    );
-    println!(
-        "Found {} windows in workspace {workspace}",
-        focused_workspace_windows.len(),
-    );
+/// Outcome of one action performed during an evaluation.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ActionResult {
+    pub rule: String,
+    pub action: String,
+    /// The targeted window; `None` for workspace-level commands.
+    pub window_id: Option<u32>,
+    pub app_name: Option<String>,
+    pub success: bool,
+    /// Why the action failed, including any stderr from the invoked program.
+    pub error: Option<String>,
+}
 
-    for rule in &config.rules {
-        println!("Checking rule: {}", rule.name);
-
-        match &rule.rule_type {
-            RuleType::Window { condition, action } => {
-                // Only process window rules if there are windows in the workspace
-                if !focused_workspace_windows.is_empty() {
-                    for window in &focused_workspace_windows {
-                        if matches_condition(condition, window)? {
-                            println!(
-                                "Rule '{}' matches window: {} ({})",
-                                rule.name, window.app_name, window.window_id,
-                            );
-
-                            if let Err(e) = execute_action(action, window) {
-                                eprintln!(
-                                    "Failed to execute action '{action}' for window {}: {e}",
-                                    window.window_id,
-                                );
-                                continue;
-                            }
+impl ActionResult {
+    pub(crate) fn for_window(
+        rule: &str,
+        action: &str,
+        window: &WindowInfo,
+        result: Result<(), String>,
+    ) -> Self {
+        Self {
+            rule: rule.to_string(),
+            action: action.to_string(),
+            window_id: Some(window.window_id),
+            app_name: Some(window.app_name.clone()),
+            success: result.is_ok(),
+            error: result.err(),
+        }
+    }
+
+    pub(crate) fn for_workspace(rule: &str, command: &str, result: Result<(), String>) -> Self {
+        Self {
+            rule: rule.to_string(),
+            action: command.to_string(),
+            window_id: None,
+            app_name: None,
+            success: result.is_ok(),
+            error: result.err(),
+        }
+    }
+}
+
+/// How to reverse a move, as the service keeps it for `undo`: the moved window and the
+/// workspace it was on before.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoRecord {
+    pub window_id: u32,
+    pub from_workspace: String,
+}
+
+impl UndoRecord {
+    /// The record of `result`, if it's a successful move of a window that was on
+    /// `from_workspace`.
+    pub fn of(result: &ActionResult, from_workspace: Option<&str>) -> Option<Self> {
+        let is_move = matches!(
+            Action::parse(&result.action),
+            Ok(Action::MoveToWorkspace(_))
+        );
+        match (result.window_id, from_workspace) {
+            (Some(window_id), Some(from)) if is_move && result.success => Some(Self {
+                window_id,
+                from_workspace: from.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The action that moves the window back.
+    pub fn reverse(&self) -> String {
+        Action::MoveToWorkspace(self.from_workspace.clone()).to_string()
+    }
+}
+
+impl fmt::Display for ActionResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.window_id, &self.error) {
+            (Some(window_id), None) => write!(
+                f,
+                "Applied '{}' to {} (ID: {window_id}): {}",
+                self.rule,
+                self.app_name.as_deref().unwrap_or_default(),
+                self.action,
+            ),
+            (Some(window_id), Some(error)) => write!(
+                f,
+                "Failed to apply '{}' to {} (ID: {window_id}): {}: {error}",
+                self.rule,
+                self.app_name.as_deref().unwrap_or_default(),
+                self.action,
+            ),
+            (None, None) => write!(f, "Executed '{}': {}", self.rule, self.action),
+            (None, Some(error)) => write!(f, "Failed to execute '{}': {error}", self.rule),
+        }
+    }
+}
+
+/// An action the rules decided on, not performed yet.
+#[derive(Debug, Clone)]
+pub enum PlannedAction {
+    /// A window rule's action, applied to a matching window.
+    Window {
+        rule: String,
+        action: String,
+        window: WindowInfo,
+    },
+    /// A command run by an empty-workspace or startup rule, in `cwd` if set and with
+    /// `env` added to its environment. A detached command isn't waited for.
+    Command {
+        rule: String,
+        command: String,
+        cwd: Option<String>,
+        env: BTreeMap<String, String>,
+        detach: bool,
+    },
+}
+
+impl PlannedAction {
+    pub fn rule(&self) -> &str {
+        match self {
+            PlannedAction::Window { rule, .. } | PlannedAction::Command { rule, .. } => rule,
+        }
+    }
+
+    /// The window rule's action, or the command to run.
+    pub fn action(&self) -> &str {
+        match self {
+            PlannedAction::Window { action, .. } => action,
+            PlannedAction::Command { command, .. } => command,
+        }
+    }
+
+    /// The result of the action if it were performed successfully, as reported by dry runs.
+    pub fn to_result(&self) -> ActionResult {
+        self.result(Ok(()))
+    }
+
+    fn result(&self, result: Result<(), String>) -> ActionResult {
+        match self {
+            PlannedAction::Window {
+                rule,
+                action,
+                window,
+            } => ActionResult::for_window(rule, action, window, result),
+            PlannedAction::Command { rule, command, .. } => {
+                ActionResult::for_workspace(rule, command, result)
+            }
+        }
+    }
+}
+
+/// Why rules are being evaluated, which decides the rules that apply.
+#[derive(Debug, Clone, Copy)]
+pub enum Trigger<'a> {
+    /// The windows are the contents of this workspace. Window rules apply to populated
+    /// workspaces, empty-workspace rules only to empty ones.
+    Workspace(&'a str),
+    /// Window rules against the given windows, e.g. a single new window.
+    Windows,
+    /// Only the window rules whose condition depends on the window title.
+    TitleChanged,
+    /// Startup rules, e.g. when the service starts or the window manager restarted.
+    Startup,
+}
+
+impl Trigger<'_> {
+    /// Names the trigger in metrics and spans.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Trigger::Workspace(_) => "workspace",
+            Trigger::Windows => "windows",
+            Trigger::TitleChanged => "title-changed",
+            Trigger::Startup => "startup",
+        }
+    }
+}
+
+impl fmt::Display for Trigger<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Workspace(workspace) => write!(f, "workspace {workspace}"),
+            Trigger::Windows => write!(f, "windows"),
+            Trigger::TitleChanged => write!(f, "title change"),
+            Trigger::Startup => write!(f, "startup"),
+        }
+    }
+}
+
+/// What an evaluation knows besides the windows it's given: why it runs and the state
+/// of the desktop at the time. The service builds it from its state, so conditions that
+/// depend on more than the window itself all read from one place.
+#[derive(Debug, Clone)]
+pub struct EvalContext<'a> {
+    pub trigger: Trigger<'a>,
+    pub focused_workspace: Option<String>,
+    /// The workspace focused before `focused_workspace`.
+    pub previous_workspace: Option<String>,
+    /// Names of the connected monitors.
+    pub monitors: Vec<String>,
+    /// App of the window with keyboard focus.
+    pub frontmost_app: Option<String>,
+    pub now: chrono::DateTime<chrono::Local>,
+    /// The display profile in effect, if any.
+    pub profile: Option<String>,
+    /// What was seen of each window, for `previously-matched` conditions.
+    pub tracker: Option<Arc<WindowTracker>>,
+    /// How many windows each workspace holds, for `workspace-window-count` conditions.
+    /// Without it they are counted among the evaluated windows.
+    pub workspace_windows: Option<HashMap<String, usize>>,
+    /// Runs the commands of `capture` actions. Without it, as in dry runs, nothing is
+    /// captured and planning runs no command.
+    pub capture: Option<Arc<dyn CaptureRunner>>,
+}
+
+impl<'a> EvalContext<'a> {
+    /// A context knowing only the trigger and the time, e.g. for evaluations outside
+    /// the service.
+    pub fn new(trigger: Trigger<'a>) -> Self {
+        Self {
+            trigger,
+            focused_workspace: None,
+            previous_workspace: None,
+            monitors: Vec::new(),
+            frontmost_app: None,
+            now: chrono::Local::now(),
+            profile: None,
+            tracker: None,
+            workspace_windows: None,
+            capture: None,
+        }
+    }
+}
 
-                            actions_performed.push(format!(
-                                "Applied '{}' to {} (ID: {}): {action}",
-                                rule.name, window.app_name, window.window_id,
-                            ));
+/// Runs the command of a `capture` action while rules are planned and returns what it
+/// printed, or `None` if it captured nothing.
+pub trait CaptureRunner: fmt::Debug + Send + Sync {
+    fn capture(
+        &self,
+        rule: &Rule,
+        command: &str,
+        window: &WindowInfo,
+        config: &Config,
+        trigger: Trigger,
+    ) -> Option<String>;
+}
+
+/// Runs capture commands like the `exec` commands of empty-workspace rules, within the
+/// limits of `[security]` and the rule's timeout.
+#[derive(Debug, Default)]
+pub struct CommandCapture;
+
+/// The enabled rules of a config grouped by what can trigger them, so an evaluation only
+/// looks at the rules that may apply. Built once per loaded config.
+#[derive(Debug, Clone, Default)]
+pub struct RuleIndex {
+    /// Window rules that may match a window on any workspace.
+    window: Vec<usize>,
+    /// Window rules limited to some workspaces, by those workspaces: through their
+    /// `workspaces` or a condition that is `workspace = '…'` or `workspace in […]`.
+    window_by_workspace: HashMap<String, Vec<usize>>,
+    /// Window rules whose condition depends on the window title.
+    title: Vec<usize>,
+    empty_workspace: HashMap<String, Vec<usize>>,
+    startup: Vec<usize>,
+    script: Vec<usize>,
+}
+
+impl RuleIndex {
+    pub fn new(config: &Config) -> Self {
+        let mut index = Self::default();
+        for (position, rule) in config.rules.iter().enumerate() {
+            if !rule.enabled {
+                continue;
+            }
+            match &rule.rule_type {
+                RuleType::Window { workspaces, .. } => {
+                    let condition = rule.condition().and_then(Result::ok);
+                    let scope = match condition.as_deref() {
+                        _ if !workspaces.is_empty() => Some(workspaces.as_slice()),
+                        Some(Condition::Compare {
+                            field: Field::Workspace,
+                            op: Operator::Equals,
+                            value: Value::String(workspace),
+                        }) => Some(std::slice::from_ref(workspace)),
+                        Some(Condition::Compare {
+                            field: Field::Workspace,
+                            op: Operator::In,
+                            value: Value::List(workspaces),
+                        }) => Some(workspaces.as_slice()),
+                        _ => None,
+                    };
+                    match scope {
+                        Some(workspaces) => {
+                            for workspace in workspaces {
+                                index
+                                    .window_by_workspace
+                                    .entry(workspace.clone())
+                                    .or_default()
+                                    .push(position);
+                            }
                         }
+                        // Unparsable conditions are kept so evaluating them reports the error
+                        None => index.window.push(position),
+                    }
+                    if condition
+                        .as_deref()
+                        .is_some_and(|condition| condition.mentions(Field::WindowTitle))
+                    {
+                        index.title.push(position);
                     }
                 }
+                RuleType::EmptyWorkspace { workspace, .. } => index
+                    .empty_workspace
+                    .entry(workspace.clone())
+                    .or_default()
+                    .push(position),
+                RuleType::Startup { .. } => index.startup.push(position),
+                RuleType::Script { .. } => index.script.push(position),
+            }
+        }
+        index
+    }
+
+    /// Positions in the config of the rules that may apply to `windows` for `trigger`,
+    /// in config order.
+    fn candidates(&self, windows: &[WindowInfo], trigger: Trigger) -> Vec<usize> {
+        let mut positions = Vec::new();
+        let window_rules = |positions: &mut Vec<usize>| {
+            positions.extend(&self.window);
+            let mut workspaces: Vec<&str> = windows
+                .iter()
+                .map(|window| window.workspace.as_str())
+                .collect();
+            workspaces.sort_unstable();
+            workspaces.dedup();
+            for workspace in workspaces {
+                positions.extend(
+                    self.window_by_workspace
+                        .get(workspace)
+                        .into_iter()
+                        .flatten(),
+                );
             }
-            RuleType::EmptyWorkspace {
-                workspace: rule_workspace,
-                command,
-            } => {
-                // Only process empty workspace rules if workspace is empty and matches
-                if focused_workspace_windows.is_empty() && rule_workspace == workspace {
-                    println!("Workspace {workspace} is empty, executing command: {command}");
-
-                    if let Err(e) = execute_empty_workspace_command(command) {
-                        eprintln!("Failed to execute empty workspace command '{command}': {e}");
-                        actions_performed.push(format!(
-                            "Failed to execute empty workspace command '{}': {e}",
-                            rule.name,
-                        ));
-                    } else {
-                        actions_performed.push(format!(
-                            "Executed empty workspace rule '{}': {command}",
-                            rule.name,
-                        ));
+        };
+        match trigger {
+            Trigger::Workspace(workspace) => {
+                window_rules(&mut positions);
+                positions.extend(self.empty_workspace.get(workspace).into_iter().flatten());
+                positions.extend(&self.script);
+            }
+            Trigger::Windows => {
+                window_rules(&mut positions);
+                positions.extend(&self.script);
+            }
+            Trigger::TitleChanged => positions.extend(&self.title),
+            Trigger::Startup => positions.extend(&self.startup),
+        }
+        positions.sort_unstable();
+        // A rule listing several workspaces is found once for each of them
+        positions.dedup();
+        positions
+    }
+}
+
+/// Matches the enabled rules of `config` against `windows` and returns the actions
+/// they call for, in the order they would be performed. Nothing is executed, and
+/// `capture` actions capture nothing.
+pub fn plan(
+    windows: &[WindowInfo],
+    config: &Config,
+    trigger: Trigger,
+) -> Result<Vec<PlannedAction>, Box<dyn Error>> {
+    plan_indexed(
+        windows,
+        config,
+        &RuleIndex::new(config),
+        &EvalContext::new(trigger),
+    )
+}
+
+/// Like [`plan`], with the rules of `config` already indexed and the trigger taken from
+/// `context`. The commands of `capture` actions run through `context.capture`, if set,
+/// and the conditions of later rules may use their output.
+pub fn plan_indexed(
+    windows: &[WindowInfo],
+    config: &Config,
+    index: &RuleIndex,
+    context: &EvalContext,
+) -> Result<Vec<PlannedAction>, Box<dyn Error>> {
+    let trigger = context.trigger;
+    let mut planned = Vec::new();
+    let counted;
+    let workspace_windows = match &context.workspace_windows {
+        Some(counts) => counts,
+        None => {
+            counted = count_workspace_windows(windows);
+            &counted
+        }
+    };
+    // What capture actions printed so far, by window
+    let mut captured = HashMap::new();
+    let rules = index
+        .candidates(windows, trigger)
+        .into_iter()
+        .filter_map(|position| config.rules.get(position));
+    for rule in rules.filter(|rule| rule.enabled) {
+        match (&rule.rule_type, trigger) {
+            // Window rules only apply to populated workspaces, empty-workspace rules only
+            // to empty ones
+            (
+                RuleType::Window {
+                    action, workspaces, ..
+                },
+                Trigger::Workspace(_) | Trigger::Windows | Trigger::TitleChanged,
+            ) => {
+                // A broken condition was reported when the config loaded, and shouldn't
+                // keep the other rules from applying
+                let Some(Ok(condition)) = rule.condition() else {
+                    continue;
+                };
+                if matches!(trigger, Trigger::TitleChanged)
+                    && !condition.mentions(Field::WindowTitle)
+                {
+                    continue;
+                }
+                let match_context = MatchContext {
+                    tracker: context.tracker.as_deref(),
+                    workspace_windows: Some(workspace_windows),
+                    captured: Some(&captured),
+                };
+                let mut captures = Vec::new();
+                for window in windows.iter().filter(|window| in_scope(workspaces, window)) {
+                    let matched = match condition.matches_in(window, &match_context) {
+                        Ok(matched) => matched,
+                        Err(e) => {
+                            log_error!("[{}] condition failed, skipping the rule: {e}", rule.name);
+                            break;
+                        }
+                    };
+                    log_match(rule, &condition, window, matched, &match_context);
+                    if !matched {
+                        continue;
+                    }
+                    match Action::parse(action) {
+                        Ok(Action::Capture(command)) => {
+                            let Some(runner) = &context.capture else {
+                                continue;
+                            };
+                            let output = runner.capture(rule, &command, window, config, trigger);
+                            captures.extend(output.map(|output| (window.window_id, output)));
+                        }
+                        _ => planned.push(PlannedAction::Window {
+                            rule: rule.name.clone(),
+                            action: action.clone(),
+                            window: window.clone(),
+                        }),
                     }
                 }
+                captured.extend(captures);
+            }
+            (
+                RuleType::EmptyWorkspace {
+                    workspace: rule_workspace,
+                    command,
+                    cwd,
+                    env,
+                    detach,
+                },
+                Trigger::Workspace(workspace),
+            ) if windows.is_empty() && rule_workspace == workspace => {
+                planned.push(PlannedAction::Command {
+                    rule: rule.name.clone(),
+                    command: command.clone(),
+                    cwd: cwd.clone(),
+                    env: env.clone(),
+                    detach: detach.unwrap_or(true),
+                });
+            }
+            (RuleType::Startup { command }, Trigger::Startup) => {
+                planned.push(PlannedAction::Command {
+                    rule: rule.name.clone(),
+                    command: command.clone(),
+                    cwd: None,
+                    env: BTreeMap::new(),
+                    detach: false,
+                });
+            }
+            (RuleType::Script { script }, Trigger::Workspace(_) | Trigger::Windows) => {
+                let workspace = match trigger {
+                    Trigger::Workspace(workspace) => Some(workspace),
+                    _ => None,
+                };
+                match run_script(rule, script, windows, workspace) {
+                    Ok(actions) => planned.extend(actions),
+                    Err(e) => log_error!("[{}] script failed, skipping the rule: {e}", rule.name),
+                }
             }
+            _ => {}
         }
     }
+    Ok(planned)
+}
 
-    Ok(actions_performed)
+/// Whether a window rule limited to `workspaces` applies to `window`.
+fn in_scope(workspaces: &[String], window: &WindowInfo) -> bool {
+    workspaces.is_empty() || workspaces.contains(&window.workspace)
 }
 
-fn matches_condition(condition: &str, window: &WindowInfo) -> Result<bool, Box<dyn Error>> {
-    // Simple condition parser for now
-    // Format: "field = 'value'" or "field > number"
+impl CaptureRunner for CommandCapture {
+    /// Runs `command` for `window` right away and returns what it printed, trimmed. A
+    /// command that fails or that `[security]` refuses is logged and captures nothing.
+    fn capture(
+        &self,
+        rule: &Rule,
+        command: &str,
+        window: &WindowInfo,
+        config: &Config,
+        trigger: Trigger,
+    ) -> Option<String> {
+        let planned = PlannedAction::Window {
+            rule: rule.name.clone(),
+            action: Action::Capture(command.to_string()).to_string(),
+            window: window.clone(),
+        };
+        let env = action_env(&planned, Some(trigger));
+        let output = config
+            .security
+            .check(command)
+            .map_err(Into::into)
+            .and_then(|()| {
+                let parts = split_command(command)?;
+                program_output(
+                    &format!("Command '{command}'"),
+                    &parts[0],
+                    &parts[1..],
+                    None,
+                    config.action_timeout(rule),
+                    &env,
+                )
+            });
+        match output {
+            Ok(output) => {
+                let output = output.trim().to_string();
+                log_info!(
+                    "[{}] captured '{output}' for window {}",
+                    rule.name,
+                    window.window_id
+                );
+                Some(output)
+            }
+            Err(e) => {
+                log_error!(
+                    "[{}] capture failed for window {}: {e}",
+                    rule.name,
+                    window.window_id
+                );
+                None
+            }
+        }
+    }
+}
 
-    if condition.contains(" = ") {
-        let parts: Vec<&str> = condition.split(" = ").collect();
-        if parts.len() != 2 {
-            return Err(format!("Invalid condition format: {condition}").into());
+/// Logs how a window rule's condition fared against `window`, if the rule asks for it.
+fn log_match(
+    rule: &Rule,
+    condition: &Condition,
+    window: &WindowInfo,
+    matched: bool,
+    context: &MatchContext,
+) {
+    let level = rule.log_level();
+    if level < RuleLog::Debug {
+        return;
+    }
+    log_info!(
+        "[{}] {} {} ({}, '{}')",
+        rule.name,
+        if matched { "matched" } else { "did not match" },
+        window.app_name,
+        window.window_id,
+        window.window_title,
+    );
+    if level == RuleLog::Trace {
+        if let Ok(clauses) = condition.clauses(window, context) {
+            for clause in clauses {
+                log_info!(
+                    "[{}]   {}: {} (was '{}')",
+                    rule.name,
+                    clause.clause,
+                    clause.matched,
+                    clause.actual
+                );
+            }
         }
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn run_script(
+    rule: &Rule,
+    script: &str,
+    windows: &[WindowInfo],
+    workspace: Option<&str>,
+) -> Result<Vec<PlannedAction>, Box<dyn Error>> {
+    crate::script::run(&rule.name, script, windows, workspace)
+}
 
-        let field = parts[0].trim();
-        let value = parts[1].trim().trim_matches('\'').trim_matches('"');
+#[cfg(not(feature = "scripting"))]
+fn run_script(
+    rule: &Rule,
+    _script: &str,
+    _windows: &[WindowInfo],
+    _workspace: Option<&str>,
+) -> Result<Vec<PlannedAction>, Box<dyn Error>> {
+    Err(format!(
+        "Rule '{}' is a script, which needs the `scripting` feature",
+        rule.name
+    )
+    .into())
+}
 
-        match field {
-            "app-id" | "app-name" => Ok(window.app_name == value),
-            "window-title" => Ok(window.window_title.contains(value)),
-            "workspace" => Ok(window.workspace == value),
-            _ => Err(format!("Unknown field in condition: {field}").into()),
+/// Plans moving windows back to the workspace their move rules assign them to, e.g.
+/// after unplugging a monitor scrambled the layout. Like a regular evaluation, the last
+/// matching move rule decides. Windows already in place are left alone, and with
+/// `workspace` set only windows assigned to it are gathered.
+pub fn plan_gather(
+    windows: &[WindowInfo],
+    config: &Config,
+    workspace: Option<&str>,
+) -> Result<Vec<PlannedAction>, Box<dyn Error>> {
+    let mut planned = Vec::new();
+    for window in windows {
+        let mut assignment = None;
+        for rule in enabled_rules(config) {
+            // Rules with a broken condition are skipped, as in evaluations
+            let (
+                RuleType::Window {
+                    action, workspaces, ..
+                },
+                Some(Ok(condition)),
+            ) = (&rule.rule_type, rule.condition())
+            else {
+                continue;
+            };
+            if !in_scope(workspaces, window) {
+                continue;
+            }
+            if let Ok(Action::MoveToWorkspace(target)) = Action::parse(action) {
+                if condition.matches(window).unwrap_or(false) {
+                    assignment = Some((rule, action, target));
+                }
+            }
+        }
+        let Some((rule, action, target)) = assignment else {
+            continue;
+        };
+        if target != window.workspace && workspace.is_none_or(|workspace| workspace == target) {
+            planned.push(PlannedAction::Window {
+                rule: rule.name.clone(),
+                action: action.clone(),
+                window: window.clone(),
+            });
         }
-    } else if condition.contains(" > ") {
-        let parts: Vec<&str> = condition.split(" > ").collect();
-        if parts.len() != 2 {
-            return Err(format!("Invalid condition format: {condition}").into());
+    }
+    Ok(planned)
+}
+
+/// Performs planned actions through a window manager, running [`Hooks`] around each.
+#[derive(Debug)]
+pub struct Executor<'a> {
+    wm: &'a dyn WindowManager,
+    hooks: Hooks,
+    parallelism: usize,
+    /// How long each rule's actions may run, by rule name.
+    timeouts: HashMap<String, Duration>,
+    /// Rules that log their actions' outcomes, by name.
+    logged_rules: HashSet<String>,
+    /// Rules whose actions stop at the first failure, by name.
+    error_policies: HashMap<String, OnError>,
+    /// Rules whose moves of the focused window take focus along, by name.
+    followed_rules: HashSet<String>,
+    security: SecurityConfig,
+    trigger: Option<Trigger<'a>>,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new(wm: &'a dyn WindowManager) -> Self {
+        Self {
+            wm,
+            hooks: Hooks::new(),
+            parallelism: 1,
+            timeouts: HashMap::new(),
+            logged_rules: HashSet::new(),
+            error_policies: HashMap::new(),
+            followed_rules: HashSet::new(),
+            security: SecurityConfig::default(),
+            trigger: None,
         }
+    }
+
+    pub fn with_hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Performs actions on up to `parallelism` different windows at once. Actions on
+    /// the same window still run one after another, in plan order.
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    /// Kills the commands, shortcuts and AppleScripts of each rule in `config` once they
+    /// run longer than [`Config::action_timeout`] allows. Without this they run for up to
+    /// [`config::DEFAULT_ACTION_TIMEOUT_MS`].
+    pub fn with_timeouts(mut self, config: &Config) -> Self {
+        self.timeouts = config
+            .rules
+            .iter()
+            .map(|rule| (rule.name.clone(), config.action_timeout(rule)))
+            .collect();
+        self
+    }
+
+    /// Logs the outcome of every action of the rules in `config` that log at `debug`
+    /// or `trace`.
+    pub fn with_rule_logs(mut self, config: &Config) -> Self {
+        self.logged_rules = config
+            .rules
+            .iter()
+            .filter(|rule| rule.log_level() >= RuleLog::Debug)
+            .map(|rule| rule.name.clone())
+            .collect();
+        self
+    }
+
+    /// Applies the `on_error` policy of each rule in `config` that doesn't just continue.
+    pub fn with_error_policies(mut self, config: &Config) -> Self {
+        self.error_policies = config
+            .rules
+            .iter()
+            .filter(|rule| rule.on_error != OnError::Continue)
+            .map(|rule| (rule.name.clone(), rule.on_error))
+            .collect();
+        self
+    }
+
+    /// Focuses the workspace the focused window was moved to by a rule in `config` that
+    /// sets `follow`.
+    pub fn with_follow(mut self, config: &Config) -> Self {
+        self.followed_rules = config
+            .rules
+            .iter()
+            .filter(|rule| rule.follow)
+            .map(|rule| rule.name.clone())
+            .collect();
+        self
+    }
+
+    /// Refuses commands that `security` doesn't allow, failing their actions instead.
+    pub fn with_security(mut self, security: &SecurityConfig) -> Self {
+        self.security = security.clone();
+        self
+    }
+
+    /// Tells the programs actions run why they run, through `AR_*` environment variables.
+    pub fn with_trigger(mut self, trigger: Trigger<'a>) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
 
-        let field = parts[0].trim();
-        let value: u32 = parts[1].trim().parse()?;
+    /// Performs `plan`, reporting every action's outcome. Moves are batched and issued
+    /// after the other actions, so a window moved twice only moves to its last target.
+    /// The actions of rules with an `on_error` policy are performed after the others,
+    /// one rule at a time.
+    pub fn execute(&self, plan: &[PlannedAction]) -> Vec<ActionResult> {
+        let (guarded, unguarded): (Vec<_>, Vec<_>) = plan
+            .iter()
+            .partition(|planned| self.error_policies.contains_key(planned.rule()));
+        let mut results = self.execute_batched(&unguarded);
 
-        match field {
-            "window-width" => {
-                // For now, we'll assume all windows are "large" (> 1000)
-                // In a real implementation, we'd query the actual window dimensions
-                Ok(value < 1200) // Mock logic
+        let mut guarded_rules: Vec<&str> = Vec::new();
+        for planned in &guarded {
+            if !guarded_rules.contains(&planned.rule()) {
+                guarded_rules.push(planned.rule());
             }
-            "window-id" => Ok(window.window_id > value),
-            _ => Err(format!("Unknown numeric field in condition: {field}").into()),
         }
-    } else {
-        Err(format!("Unsupported condition format: {condition}").into())
+        for rule in guarded_rules {
+            let actions: Vec<_> = guarded
+                .iter()
+                .copied()
+                .filter(|planned| planned.rule() == rule)
+                .collect();
+            results.extend(self.execute_guarded(&actions, self.error_policies[rule]));
+        }
+
+        if let Some(workspace) = self.followed_workspace(plan, &results) {
+            if let Err(e) = self.wm.focus_workspace(&workspace) {
+                log_error!("Failed to follow the focused window to workspace {workspace}: {e}");
+            }
+        }
+        results
+    }
+
+    /// Where the last successful move of the focused window by a rule that sets
+    /// `follow` took it.
+    fn followed_workspace(
+        &self,
+        plan: &[PlannedAction],
+        results: &[ActionResult],
+    ) -> Option<String> {
+        let focused = plan.iter().find_map(|planned| match planned {
+            PlannedAction::Window { window, .. } if window.focused => Some(window.window_id),
+            _ => None,
+        })?;
+        results.iter().rev().find_map(|result| {
+            if !result.success
+                || result.window_id != Some(focused)
+                || !self.followed_rules.contains(&result.rule)
+            {
+                return None;
+            }
+            match Action::parse(&result.action) {
+                Ok(Action::MoveToWorkspace(workspace)) => Some(workspace),
+                _ => None,
+            }
+        })
+    }
+
+    fn execute_batched(&self, plan: &[&PlannedAction]) -> Vec<ActionResult> {
+        // Outcomes of the actions other than moves, `None` until performed
+        let mut performed = Vec::new();
+        let mut moves = MoveBatch::new();
+        let mut pending_moves = HashMap::new();
+
+        for &planned in plan {
+            let span = action_span(planned);
+            if let Err(veto) = self.hooks.run_before(planned) {
+                log_info!("Skipping '{}': {veto}", planned.rule());
+                performed.push((planned, span, Some(Err(veto))));
+                continue;
+            }
+            match planned {
+                PlannedAction::Window { action, window, .. } => match Action::parse(action) {
+                    Ok(Action::MoveToWorkspace(target_workspace)) => {
+                        moves.push(window.window_id, &target_workspace);
+                        // Only the last move of a window is issued
+                        if let Some((earlier, span)) =
+                            pending_moves.insert(window.window_id, (planned, span))
+                        {
+                            let superseded = format!("Superseded by rule '{}'", planned.rule());
+                            performed.push((earlier, span, Some(Err(superseded))));
+                        }
+                    }
+                    Ok(action) => match self.security.check_action(&action) {
+                        Ok(()) => performed.push((planned, span, None)),
+                        Err(e) => performed.push((planned, span, Some(Err(e)))),
+                    },
+                    Err(e) => performed.push((planned, span, Some(Err(e)))),
+                },
+                PlannedAction::Command { command, env, .. } => match self
+                    .security
+                    .check(command)
+                    .and_then(|()| self.security.check_env(env))
+                {
+                    Ok(()) => performed.push((planned, span, None)),
+                    Err(e) => performed.push((planned, span, Some(Err(e)))),
+                },
+            }
+        }
+
+        let runnable: Vec<_> = performed
+            .iter()
+            .filter(|(_, _, outcome)| outcome.is_none())
+            .map(|(planned, _, _)| *planned)
+            .collect();
+        let timeout = |rule: &str| self.timeout(rule);
+        let mut outcomes =
+            perform_all(&runnable, self.wm, self.parallelism, &timeout, self.trigger).into_iter();
+        let mut results: Vec<_> = performed
+            .into_iter()
+            .map(|(planned, span, outcome)| {
+                let outcome = outcome.or_else(|| outcomes.next()).unwrap_or(Ok(()));
+                self.finish(planned, &span, outcome)
+            })
+            .collect();
+
+        if !moves.is_empty() {
+            log_info!("Executing {} batched moves", moves.len());
+        }
+        for moved in moves.execute(self.wm, self.parallelism) {
+            if let Some((planned, span)) = pending_moves.remove(&moved.window_id) {
+                results.push(self.finish(planned, &span, moved.result));
+            }
+        }
+
+        results
+    }
+
+    /// Performs the actions of one rule in order, stopping at the first failure and,
+    /// with [`OnError::Rollback`], undoing the moves made before it. Each move back is
+    /// performed like any other action and reported as a result of its own.
+    fn execute_guarded(&self, actions: &[&PlannedAction], policy: OnError) -> Vec<ActionResult> {
+        let mut results: Vec<ActionResult> = Vec::new();
+        // Moves made so far, with the window as it is after the move
+        let mut moved: Vec<(UndoRecord, WindowInfo)> = Vec::new();
+        let mut failed: Option<String> = None;
+        for &planned in actions {
+            let span = action_span(planned);
+            if let Some(action) = &failed {
+                let skipped = Err(format!("Skipped after '{action}' failed"));
+                results.push(self.finish(planned, &span, skipped));
+                continue;
+            }
+
+            let result = self.finish(planned, &span, self.perform_one(planned));
+            if !result.success {
+                failed = Some(result.action.clone());
+            } else if let PlannedAction::Window { action, window, .. } = planned {
+                if let (Some(record), Ok(Action::MoveToWorkspace(target))) = (
+                    UndoRecord::of(&result, Some(&window.workspace)),
+                    Action::parse(action),
+                ) {
+                    let mut window = window.clone();
+                    window.workspace = target;
+                    moved.push((record, window));
+                }
+            }
+            results.push(result);
+        }
+
+        if let (Some(action), OnError::Rollback) = (&failed, policy) {
+            for (record, window) in moved.into_iter().rev() {
+                let planned = PlannedAction::Window {
+                    rule: actions[0].rule().to_string(),
+                    action: record.reverse(),
+                    window,
+                };
+                let span = action_span(&planned);
+                let outcome = self
+                    .perform_one(&planned)
+                    .map_err(|e| format!("Rollback after '{action}' failed: {e}"));
+                results.push(self.finish(&planned, &span, outcome));
+            }
+        }
+        results
+    }
+
+    /// Performs a single action, moves included, subject to the hooks and security
+    /// limits like batched ones.
+    fn perform_one(&self, planned: &PlannedAction) -> Result<(), String> {
+        self.hooks.run_before(planned)?;
+        match planned {
+            PlannedAction::Window { action, window, .. } => match Action::parse(action)? {
+                Action::MoveToWorkspace(workspace) => self
+                    .wm
+                    .move_window(window.window_id, &workspace)
+                    .map_err(|e| e.to_string()),
+                action => {
+                    self.security.check_action(&action)?;
+                    perform(planned, self.wm, self.timeout(planned.rule()), self.trigger)
+                }
+            },
+            PlannedAction::Command { command, env, .. } => {
+                self.security.check(command)?;
+                self.security.check_env(env)?;
+                perform(planned, self.wm, self.timeout(planned.rule()), self.trigger)
+            }
+        }
+    }
+
+    fn timeout(&self, rule: &str) -> Duration {
+        self.timeouts
+            .get(rule)
+            .copied()
+            .unwrap_or(Duration::from_millis(config::DEFAULT_ACTION_TIMEOUT_MS))
+    }
+
+    fn finish(
+        &self,
+        planned: &PlannedAction,
+        span: &Span,
+        result: Result<(), String>,
+    ) -> ActionResult {
+        if let Err(e) = &result {
+            span.fail(e);
+        }
+        let result = planned.result(result);
+        if self.logged_rules.contains(&result.rule) {
+            log_info!("[{}] {result}", result.rule);
+        }
+        metrics::record_action(&result.rule, result.success);
+        self.hooks.run_after(planned, &result);
+        result
     }
 }
 
-fn execute_action(action: &str, window: &WindowInfo) -> Result<(), Box<dyn Error>> {
-    println!(
-        "Executing action: {} for window {}",
-        action, window.window_id
-    );
+/// Performs `actions`, which mustn't be moves, on up to `parallelism` threads. Actions
+/// on the same window are performed one after another by the same thread. Returns the
+/// outcomes in the order of `actions`.
+fn perform_all(
+    actions: &[&PlannedAction],
+    wm: &dyn WindowManager,
+    parallelism: usize,
+    timeout: &(dyn Fn(&str) -> Duration + Sync),
+    trigger: Option<Trigger>,
+) -> Vec<Result<(), String>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut window_groups = HashMap::new();
+    for (index, planned) in actions.iter().enumerate() {
+        match planned {
+            PlannedAction::Window { window, .. } => {
+                let group = *window_groups.entry(window.window_id).or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+                groups[group].push(index);
+            }
+            PlannedAction::Command { .. } => groups.push(vec![index]),
+        }
+    }
+    if parallelism <= 1 || groups.len() <= 1 {
+        return actions
+            .iter()
+            .map(|planned| perform(planned, wm, timeout(planned.rule()), trigger))
+            .collect();
+    }
 
-    if action.starts_with("move-to-workspace ") {
-        let target_workspace = action.strip_prefix("move-to-workspace ").unwrap();
-
-        let output = Command::new("aerospace")
-            .args([
-                "move",
-                "--window-id",
-                &window.window_id.to_string(),
-                "--workspace",
-                target_workspace,
-            ])
-            .output()?;
-
-        if !output.status.success() {
-            return Err(format!(
-                "Failed to move window to workspace {}: {}",
-                target_workspace,
-                String::from_utf8_lossy(&output.stderr)
-            )
-            .into());
-        }
-
-        println!(
-            "Moved window {} to workspace {}",
-            window.window_id, target_workspace
-        );
-    } else if action == "maximize" {
-        let output = Command::new("aerospace")
-            .args(["fullscreen", "--window-id", &window.window_id.to_string()])
-            .output()?;
-
-        if !output.status.success() {
-            return Err(format!(
-                "Failed to maximize window: {}",
-                String::from_utf8_lossy(&output.stderr)
-            )
-            .into());
-        }
-
-        println!("Maximized window {}", window.window_id);
+    let outcomes: Vec<Mutex<Option<Result<(), String>>>> =
+        actions.iter().map(|_| Mutex::new(None)).collect();
+    let next_group = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..parallelism.min(groups.len()) {
+            scope.spawn(|| {
+                while let Some(group) = groups.get(next_group.fetch_add(1, Ordering::Relaxed)) {
+                    for &index in group {
+                        let planned = actions[index];
+                        let outcome = perform(planned, wm, timeout(planned.rule()), trigger);
+                        *outcomes[index].lock().unwrap() = Some(outcome);
+                    }
+                }
+            });
+        }
+    });
+    outcomes
+        .into_iter()
+        .map(|outcome| {
+            outcome
+                .into_inner()
+                .unwrap()
+                .unwrap_or_else(|| Err("Action was not performed".to_string()))
+        })
+        .collect()
+}
+
+/// Performs an action other than a move, killing any program it runs after `timeout`.
+/// A panic fails only this action.
+fn perform(
+    planned: &PlannedAction,
+    wm: &dyn WindowManager,
+    timeout: Duration,
+    trigger: Option<Trigger>,
+) -> Result<(), String> {
+    let env = action_env(planned, trigger);
+    let run = || match planned {
+        PlannedAction::Window { action, window, .. } => {
+            execute_action(&Action::parse(action)?, window, wm, timeout, &env)
+                .map_err(|e| e.to_string())
+        }
+        PlannedAction::Command {
+            command,
+            cwd,
+            detach,
+            ..
+        } => {
+            log_info!("Executing command: {command}");
+            execute_command(command, cwd.as_deref(), *detach, timeout, &env)
+                .map_err(|e| e.to_string())
+        }
+    };
+    std::panic::catch_unwind(AssertUnwindSafe(run)).unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown cause");
+        Err(format!("Action panicked: {message}"))
+    })
+}
+
+/// The environment a command's rule asks for, followed by variables describing
+/// `planned` to the programs its action runs:
+///
+/// | Variable       | Value                                                        |
+/// |----------------|--------------------------------------------------------------|
+/// | `AR_RULE_NAME` | Name of the rule the action belongs to                       |
+/// | `AR_TRIGGER`   | `workspace`, `windows`, `title-changed` or `startup`         |
+/// | `AR_WINDOW_ID` | ID of the targeted window, if any                            |
+/// | `AR_APP_NAME`  | App of the targeted window, if any                           |
+/// | `AR_WORKSPACE` | Workspace of the targeted window, or the evaluated workspace |
+fn action_env<'a>(planned: &'a PlannedAction, trigger: Option<Trigger>) -> Vec<(&'a str, String)> {
+    let mut env = match planned {
+        PlannedAction::Command { env, .. } => env
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone()))
+            .collect(),
+        PlannedAction::Window { .. } => Vec::new(),
+    };
+    env.push(("AR_RULE_NAME", planned.rule().to_string()));
+    if let Some(trigger) = trigger {
+        env.push(("AR_TRIGGER", trigger.label().to_string()));
+    }
+    match (planned, trigger) {
+        (PlannedAction::Window { window, .. }, _) => env.extend([
+            ("AR_WINDOW_ID", window.window_id.to_string()),
+            ("AR_APP_NAME", window.app_name.clone()),
+            ("AR_WORKSPACE", window.workspace.clone()),
+        ]),
+        (PlannedAction::Command { .. }, Some(Trigger::Workspace(workspace))) => {
+            env.push(("AR_WORKSPACE", workspace.to_string()))
+        }
+        (PlannedAction::Command { .. }, _) => {}
+    }
+    env
+}
+
+fn action_span(planned: &PlannedAction) -> Span {
+    let span = Span::start("action");
+    span.set("rule", planned.rule());
+    span.set("action", planned.action());
+    if let PlannedAction::Window { window, .. } = planned {
+        span.set("window.id", window.window_id);
+        span.set("window.app", window.app_name.as_str());
+        span.set("window.workspace", window.workspace.as_str());
+    }
+    span
+}
+
+/// Plans the actions for `trigger` and performs them, or in a dry run reports them as
+/// successful without touching any window.
+fn plan_and_execute(
+    windows: &[WindowInfo],
+    config: &Config,
+    index: &RuleIndex,
+    context: &EvalContext,
+    wm: &dyn WindowManager,
+    dry_run: bool,
+) -> Result<Vec<ActionResult>, Box<dyn Error>> {
+    let trigger = context.trigger;
+    let started = Instant::now();
+    let span = Span::start("evaluation");
+    span.set("trigger", trigger.label());
+    span.set("dry_run", dry_run);
+    if let Trigger::Workspace(workspace) = trigger {
+        span.set("workspace", workspace);
+    }
+    if let [window] = windows {
+        span.set("window.id", window.window_id);
+    }
+    let _entered = span.enter();
+
+    let planned =
+        plan_indexed(windows, config, index, context).inspect_err(|e| span.fail(&e.to_string()))?;
+    span.set("actions", planned.len());
+    for action in &planned {
+        if let PlannedAction::Window { window, .. } = action {
+            log_info!(
+                "Rule '{}' matches window: {} ({})",
+                action.rule(),
+                window.app_name,
+                window.window_id,
+            );
+        }
+    }
+    let results = if dry_run {
+        planned.iter().map(PlannedAction::to_result).collect()
     } else {
-        return Err(format!("Unknown action: {action}").into());
+        Executor::new(wm)
+            .with_hooks(Hooks::from_config(&config.hooks, &config.security))
+            .with_parallelism(config.parallel_actions())
+            .with_timeouts(config)
+            .with_rule_logs(config)
+            .with_error_policies(config)
+            .with_follow(config)
+            .with_security(&config.security)
+            .with_trigger(trigger)
+            .execute(&planned)
+    };
+    metrics::record_evaluation(trigger.label(), started.elapsed());
+    Ok(results)
+}
+
+/// Evaluates the rules for the windows of a workspace, usually with a
+/// [`Trigger::Workspace`] context.
+pub fn evaluate_rules_for_workspace(
+    focused_workspace_windows: Vec<WindowInfo>,
+    config: &Config,
+    index: &RuleIndex,
+    context: &EvalContext,
+    wm: &dyn WindowManager,
+    dry_run: bool,
+) -> Result<Vec<ActionResult>, Box<dyn Error>> {
+    log_info!(
+        "Evaluating {} rules for {}",
+        config.rules.len(),
+        context.trigger
+    );
+    log_info!(
+        "Found {} windows in {}",
+        focused_workspace_windows.len(),
+        context.trigger
+    );
+    plan_and_execute(
+        &focused_workspace_windows,
+        config,
+        index,
+        context,
+        wm,
+        dry_run,
+    )
+}
+
+/// Evaluates the window rules against a single window: all of them with a
+/// [`Trigger::Windows`] context, or only those depending on the window title with a
+/// [`Trigger::TitleChanged`] one, e.g. after the title of `window` changed.
+pub fn evaluate_rules_for_window(
+    window: &WindowInfo,
+    config: &Config,
+    index: &RuleIndex,
+    context: &EvalContext,
+    wm: &dyn WindowManager,
+    dry_run: bool,
+) -> Result<Vec<ActionResult>, Box<dyn Error>> {
+    plan_and_execute(
+        std::slice::from_ref(window),
+        config,
+        index,
+        context,
+        wm,
+        dry_run,
+    )
+}
+
+/// Moves stray windows back to their assigned workspace, see [`plan_gather`].
+pub fn gather_windows(
+    windows: &[WindowInfo],
+    config: &Config,
+    workspace: Option<&str>,
+    wm: &dyn WindowManager,
+    dry_run: bool,
+) -> Result<Vec<ActionResult>, Box<dyn Error>> {
+    let planned = plan_gather(windows, config, workspace)?;
+    log_info!("Gathering {} windows", planned.len());
+    if dry_run {
+        return Ok(planned.iter().map(PlannedAction::to_result).collect());
     }
+    Ok(Executor::new(wm)
+        .with_hooks(Hooks::from_config(&config.hooks, &config.security))
+        .with_parallelism(config.parallel_actions())
+        .with_timeouts(config)
+        .with_rule_logs(config)
+        .with_error_policies(config)
+        .with_follow(config)
+        .with_security(&config.security)
+        .execute(&planned))
+}
 
-    Ok(())
+fn enabled_rules(config: &Config) -> impl Iterator<Item = &Rule> {
+    config.rules.iter().filter(|rule| rule.enabled)
+}
+
+fn execute_action(
+    action: &Action,
+    window: &WindowInfo,
+    wm: &dyn WindowManager,
+    timeout: Duration,
+    env: &[(&str, String)],
+) -> Result<(), Box<dyn Error>> {
+    match action {
+        Action::Maximize => wm.fullscreen_window(window.window_id),
+        Action::MoveToWorkspace(workspace) => wm.move_window(window.window_id, workspace),
+        Action::Shortcut(name) => run_program(
+            &format!("Shortcut '{name}'"),
+            "shortcuts",
+            &["run", name],
+            None,
+            timeout,
+            env,
+        ),
+        Action::AppleScript(snippet) => run_program(
+            "AppleScript",
+            "osascript",
+            &["-e", snippet],
+            None,
+            timeout,
+            env,
+        ),
+        // Captures run while the rules are planned, so they're never performed
+        Action::Capture(_) => Ok(()),
+    }
+}
+
+/// Every rule checked against one window, as reported by `explain`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WindowExplanation {
+    pub window: WindowInfo,
+    pub rules: Vec<RuleExplanation>,
+    /// The window's lifecycle, when the service tracked it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<WindowRecord>,
+}
+
+/// Whether a rule matched a window and, if not, why.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RuleExplanation {
+    pub rule: String,
+    pub matched: bool,
+    /// The action a match would perform; `None` for rules that don't target windows.
+    pub action: Option<String>,
+    /// The failed condition clause, or why the rule was skipped.
+    pub reason: Option<String>,
 }
 
-fn execute_empty_workspace_command(command: &str) -> Result<(), Box<dyn Error>> {
-    println!("Executing empty workspace command: {command}");
+/// How many of `windows` are on each workspace.
+pub fn count_workspace_windows<'a>(
+    windows: impl IntoIterator<Item = &'a WindowInfo>,
+) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for window in windows {
+        *counts.entry(window.workspace.clone()).or_default() += 1;
+    }
+    counts
+}
+
+/// Checks every rule against `window` without performing any action. With a tracker in
+/// `context` the explanation includes the window's lifecycle. Without captured output in
+/// `context`, rules whose condition reads it are reported as skipped.
+pub fn explain_rules_for_window(
+    window: &WindowInfo,
+    config: &Config,
+    context: &MatchContext,
+) -> WindowExplanation {
+    let rules = config
+        .rules
+        .iter()
+        .map(|rule| {
+            let explanation =
+                |matched, action: Option<&String>, reason: Option<String>| RuleExplanation {
+                    rule: rule.name.clone(),
+                    matched,
+                    action: action.cloned(),
+                    reason,
+                };
 
-    // Parse command and arguments
-    let parts = match shlex::split(command) {
-        Some(parts) => parts,
-        None => return Err(format!("Failed to parse command: {command}").into()),
+            let (condition, action, workspaces) = match (&rule.rule_type, rule.condition()) {
+                (
+                    RuleType::Window {
+                        action, workspaces, ..
+                    },
+                    Some(condition),
+                ) => (condition, action, workspaces),
+                (RuleType::Window { .. }, None) => unreachable!("window rules have a condition"),
+                (RuleType::EmptyWorkspace { .. }, _) => {
+                    return explanation(
+                        false,
+                        None,
+                        Some("only runs for empty workspaces".to_string()),
+                    )
+                }
+                (RuleType::Startup { .. }, _) => {
+                    return explanation(false, None, Some("only runs on startup".to_string()))
+                }
+                (RuleType::Script { script }, _) => {
+                    return explain_script(rule, script, window);
+                }
+            };
+            if !rule.enabled {
+                return explanation(false, Some(action), Some("rule is disabled".to_string()));
+            }
+            if !in_scope(workspaces, window) {
+                return explanation(
+                    false,
+                    Some(action),
+                    Some(format!(
+                        "only applies to workspaces {}",
+                        workspaces.join(", ")
+                    )),
+                );
+            }
+            // Capture commands don't run just to explain a window
+            if context.captured.is_none()
+                && condition
+                    .as_ref()
+                    .is_ok_and(|condition| condition.mentions(Field::Captured))
+            {
+                return explanation(
+                    false,
+                    Some(action),
+                    Some("depends on captured output, and captures were skipped".to_string()),
+                );
+            }
+
+            let evaluated = condition.and_then(|c| {
+                Ok((
+                    c.matches_in(window, context)?,
+                    c.clauses(window, context)?,
+                    c,
+                ))
+            });
+            match evaluated {
+                Ok((true, _, _)) => explanation(true, Some(action), None),
+                // Any failed comparison explains a combined condition that didn't match,
+                // unless only an OR's alternatives failed together
+                Ok((false, clauses, condition)) => {
+                    let reason = match clauses.iter().find(|clause| !clause.matched) {
+                        Some(failed) if !matches!(*condition, Condition::Or(_)) => {
+                            format!("{} (was '{}')", failed.clause, failed.actual)
+                        }
+                        _ => format!("{condition} did not match"),
+                    };
+                    explanation(false, Some(action), Some(reason))
+                }
+                Err(e) => explanation(false, Some(action), Some(format!("error: {e}"))),
+            }
+        })
+        .collect();
+
+    WindowExplanation {
+        window: window.clone(),
+        rules,
+        record: context
+            .tracker
+            .and_then(|tracker| tracker.get(window.window_id))
+            .cloned(),
+    }
+}
+
+/// Runs a script rule against `window` alone and reports the actions it planned for it.
+fn explain_script(rule: &Rule, script: &str, window: &WindowInfo) -> RuleExplanation {
+    let explanation = |matched, action, reason| RuleExplanation {
+        rule: rule.name.clone(),
+        matched,
+        action,
+        reason,
     };
+    if !rule.enabled {
+        return explanation(false, None, Some("rule is disabled".to_string()));
+    }
+    match run_script(rule, script, std::slice::from_ref(window), None) {
+        Ok(planned) => {
+            let actions: Vec<_> = planned.iter().map(PlannedAction::action).collect();
+            if actions.is_empty() {
+                explanation(false, None, Some("script planned no action".to_string()))
+            } else {
+                explanation(true, Some(actions.join(", ")), None)
+            }
+        }
+        Err(e) => explanation(false, None, Some(format!("error: {e}"))),
+    }
+}
+
+/// Runs every startup rule, e.g. when the service starts or the window manager restarted.
+pub fn run_startup_rules(
+    config: &Config,
+    index: &RuleIndex,
+    context: &EvalContext,
+    wm: &dyn WindowManager,
+) -> Result<Vec<ActionResult>, Box<dyn Error>> {
+    plan_and_execute(&[], config, index, context, wm, false)
+}
 
-    if parts.is_empty() {
-        return Err("Empty command".into());
+fn execute_command(
+    command: &str,
+    cwd: Option<&str>,
+    detach: bool,
+    timeout: Duration,
+    env: &[(&str, String)],
+) -> Result<(), Box<dyn Error>> {
+    let parts = split_command(command)?;
+    let what = format!("Command '{command}'");
+    let cwd = cwd.map(expand_home);
+    if detach {
+        spawn_detached(what, &parts[0], &parts[1..], cwd.as_deref(), env)
+    } else {
+        run_program(&what, &parts[0], &parts[1..], cwd.as_deref(), timeout, env)
     }
+}
 
-    let program = &parts[0];
-    let args = &parts[1..];
+/// The program and arguments of `command`, split like a shell would.
+fn split_command(command: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    match shlex::split(command) {
+        Some(parts) if parts.is_empty() => Err("Empty command".into()),
+        Some(parts) => Ok(parts),
+        None => Err(format!("Failed to parse command: {command}").into()),
+    }
+}
 
-    let output = Command::new(program).args(args).output()?;
+/// Starts `program` like [`run_program`] without waiting for it to exit. It's waited
+/// for in the background, logging if it fails.
+fn spawn_detached(
+    what: String,
+    program: &str,
+    args: &[String],
+    cwd: Option<&Path>,
+    env: &[(&str, String)],
+) -> Result<(), Box<dyn Error>> {
+    let mut command = Command::new(program);
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    let mut child = command
+        .args(args)
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("{what} failed to start: {e}"))?;
+    std::thread::spawn(move || match child.wait() {
+        Ok(status) if !status.success() => {
+            log_error!("{what} failed with exit code {:?}", status.code())
+        }
+        Ok(_) => {}
+        Err(e) => log_error!("{what} couldn't be waited for: {e}"),
+    });
+    Ok(())
+}
+
+/// `path` with a leading `~` replaced by the home directory.
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
 
-    if !output.status.success() {
+/// Runs `program`, in `cwd` if set and with `env` added to its environment, failing with
+/// its stderr if it exits unsuccessfully or is still running after `timeout`, in which
+/// case it's killed. `what` names the run in errors.
+fn run_program(
+    what: &str,
+    program: &str,
+    args: &[impl AsRef<OsStr>],
+    cwd: Option<&Path>,
+    timeout: Duration,
+    env: &[(&str, String)],
+) -> Result<(), Box<dyn Error>> {
+    let stdout = program_output(what, program, args, cwd, timeout, env)?;
+    // Log stdout if there's any output
+    if !stdout.trim().is_empty() {
+        log_info!("Command output: {}", stdout.trim());
+    }
+    Ok(())
+}
+
+/// Runs `program` like [`run_program`] and returns what it printed to stdout.
+fn program_output(
+    what: &str,
+    program: &str,
+    args: &[impl AsRef<OsStr>],
+    cwd: Option<&Path>,
+    timeout: Duration,
+    env: &[(&str, String)],
+) -> Result<String, Box<dyn Error>> {
+    let mut command = Command::new(program);
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    let mut child = command
+        .args(args)
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Drained while waiting, so a chatty program can't block on a full pipe
+    let stdout = read_to_end_in_background(child.stdout.take());
+    let stderr = read_to_end_in_background(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("{what} timed out after {}ms", timeout.as_millis()).into());
+        }
+        std::thread::sleep(PROGRAM_POLL_INTERVAL);
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if !status.success() {
         return Err(format!(
-            "Command '{command}' failed with exit code {:?}: {}",
-            output.status.code(),
-            String::from_utf8_lossy(&output.stderr)
+            "{what} failed with exit code {:?}: {}",
+            status.code(),
+            String::from_utf8_lossy(&stderr)
         )
         .into());
     }
 
-    // Log stdout if there's any output
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    if !stdout.trim().is_empty() {
-        println!("Command output: {}", stdout.trim());
+    Ok(String::from_utf8_lossy(&stdout).into_owned())
+}
+
+fn read_to_end_in_background(
+    pipe: Option<impl Read + Send + 'static>,
+) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        output
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, window, Call, FakeWindowManager};
+
+    fn config(source: &str) -> Config {
+        config::parse_config(source).unwrap()
     }
 
-    println!("Successfully executed empty workspace command: {command}");
-    Ok(())
+    #[test]
+    fn test_plan_matches_rules_without_executing() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Move Slack"
+type = "window"
+condition = "app-name = 'Slack'"
+action = "move-to-workspace 9"
+
+[[rules]]
+name = "Open terminal"
+type = "empty-workspace"
+workspace = "1"
+command = "open -a Ghostty"
+"#,
+        );
+
+        let windows = [
+            window("Slack").id(1).build(),
+            window("Safari").id(2).build(),
+        ];
+        let planned = plan(&windows, &config, Trigger::Workspace("1")).unwrap();
+        testing::assert_rules(&planned, &["Move Slack"]);
+        testing::assert_moved(&planned, 1, "9");
+        testing::assert_not_planned(&planned, 2);
+
+        let planned = plan(&[], &config, Trigger::Workspace("1")).unwrap();
+        assert!(matches!(
+            &planned[..],
+            [PlannedAction::Command { command, .. }] if command == "open -a Ghostty"
+        ));
+    }
+
+    #[test]
+    fn test_workspace_window_count_counts_evaluated_windows() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Crowded"
+type = "window"
+condition = "workspace-window-count > 3 AND app-name = 'Ghostty'"
+action = "maximize"
+"#,
+        );
+        let mut windows: Vec<_> = (1..=3).map(|id| window("Safari").id(id).build()).collect();
+        windows.push(window("Ghostty").id(4).build());
+        let planned = plan(&windows, &config, Trigger::Workspace("1")).unwrap();
+        testing::assert_rules(&planned, &["Crowded"]);
+
+        let planned = plan(&windows[1..], &config, Trigger::Workspace("1")).unwrap();
+        assert!(planned.is_empty());
+    }
+
+    #[test]
+    fn test_workspace_scoped_rules() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Maximize terminals"
+type = "window"
+condition = "app-name = 'Ghostty'"
+action = "maximize"
+workspaces = ["1", "2"]
+"#,
+        );
+        let index = RuleIndex::new(&config);
+        let scratch = [window("Ghostty").id(1).workspace("S").build()];
+        assert!(index
+            .candidates(&scratch, Trigger::Workspace("S"))
+            .is_empty());
+        assert!(plan(&scratch, &config, Trigger::Windows)
+            .unwrap()
+            .is_empty());
+
+        let windows = [
+            window("Ghostty").id(2).workspace("2").build(),
+            scratch[0].clone(),
+        ];
+        let planned = plan(&windows, &config, Trigger::Windows).unwrap();
+        testing::assert_rules(&planned, &["Maximize terminals"]);
+        let PlannedAction::Window { window, .. } = &planned[0] else {
+            panic!("Expected a window action");
+        };
+        assert_eq!(window.window_id, 2);
+
+        let explanation = explain_rules_for_window(&scratch[0], &config, &MatchContext::default());
+        assert_eq!(
+            explanation.rules[0].reason.as_deref(),
+            Some("only applies to workspaces 1, 2")
+        );
+    }
+
+    #[test]
+    fn test_executor_reports_each_outcome() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Maximize"
+type = "window"
+condition = "app-name = 'Ghostty'"
+action = "maximize"
+
+[[rules]]
+name = "Move"
+type = "window"
+condition = "window-id > 0"
+action = "move-to-workspace 5"
+"#,
+        );
+        let wm = FakeWindowManager::new([
+            window("Ghostty").id(1),
+            window("Safari").id(2).workspace("2"),
+        ]);
+        wm.fail_window(2, "window is gone");
+
+        let planned = plan(&wm.windows(), &config, Trigger::Windows).unwrap();
+        let results = Executor::new(&wm).execute(&planned);
+
+        // Moves are batched after the other actions
+        assert_eq!(
+            wm.calls(),
+            [
+                Call::Fullscreen { window_id: 1 },
+                Call::Move {
+                    window_id: 1,
+                    workspace: "5".to_string()
+                },
+                Call::Move {
+                    window_id: 2,
+                    workspace: "5".to_string()
+                },
+            ]
+        );
+        assert_eq!(results.len(), 3);
+        assert!(results[0].success && results[1].success);
+        assert_eq!(results[2].error.as_deref(), Some("window is gone"));
+        assert_eq!(wm.windows()[0].workspace, "5");
+        assert_eq!(wm.windows()[1].workspace, "2");
+    }
+
+    #[test]
+    fn test_security_limits_applescript_and_shortcuts() {
+        let config = config(
+            r#"
+[security]
+allowed_commands = ["open", "shortcuts"]
+
+[[rules]]
+name = "Script"
+type = "window"
+condition = "app-name = 'Ghostty'"
+action = "applescript 'do shell script \"rm -rf ~\"'"
+
+[[rules]]
+name = "Maximize"
+type = "window"
+condition = "app-name = 'Ghostty'"
+action = "maximize"
+"#,
+        );
+        let wm = FakeWindowManager::new([window("Ghostty").id(1)]);
+
+        let planned = plan(&wm.windows(), &config, Trigger::Windows).unwrap();
+        let results = Executor::new(&wm)
+            .with_security(&config.security)
+            .execute(&planned);
+
+        assert_eq!(wm.calls(), [Call::Fullscreen { window_id: 1 }]);
+        assert!(results[0]
+            .error
+            .as_deref()
+            .is_some_and(|error| error.contains("runs 'osascript'")));
+        assert!(results[1].success);
+        assert!(config
+            .security
+            .check_action(&Action::Shortcut("Focus".to_string()))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_superseded_moves_are_reported() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Terminals"
+type = "window"
+condition = "app-name = 'Ghostty'"
+action = "move-to-workspace 2"
+
+[[rules]]
+name = "Everything"
+type = "window"
+condition = "window-id > 0"
+action = "move-to-workspace 5"
+"#,
+        );
+        let wm = FakeWindowManager::new([window("Ghostty").id(1)]);
+
+        let planned = plan(&wm.windows(), &config, Trigger::Windows).unwrap();
+        let results = Executor::new(&wm).execute(&planned);
+
+        assert_eq!(
+            wm.calls(),
+            [Call::Move {
+                window_id: 1,
+                workspace: "5".to_string()
+            }]
+        );
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].rule, "Terminals");
+        assert_eq!(
+            results[0].error.as_deref(),
+            Some("Superseded by rule 'Everything'")
+        );
+        assert!(results[1].success);
+    }
+
+    #[test]
+    fn test_rollback_moves_windows_back_after_a_failure() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Gather"
+type = "window"
+condition = "app-name = 'Ghostty'"
+action = "move-to-workspace 5"
+on_error = "rollback"
+"#,
+        );
+        let wm = FakeWindowManager::new([
+            window("Ghostty").id(1).workspace("1"),
+            window("Ghostty").id(2).workspace("2"),
+            window("Ghostty").id(3).workspace("3"),
+        ]);
+        wm.fail_window(2, "window is gone");
+
+        let planned = plan(&wm.windows(), &config, Trigger::Windows).unwrap();
+        let results = Executor::new(&wm)
+            .with_error_policies(&config)
+            .execute(&planned);
+
+        let outcomes: Vec<_> = results
+            .iter()
+            .map(|result| {
+                let outcome = result.error.as_deref().unwrap_or("ok");
+                (result.action.as_str(), outcome)
+            })
+            .collect();
+        // The move back is reported on its own, so the first move still counts as done
+        assert_eq!(
+            outcomes,
+            [
+                ("move-to-workspace 5", "ok"),
+                ("move-to-workspace 5", "window is gone"),
+                (
+                    "move-to-workspace 5",
+                    "Skipped after 'move-to-workspace 5' failed"
+                ),
+                ("move-to-workspace 1", "ok"),
+            ]
+        );
+        assert_eq!(results[3].window_id, Some(1));
+        let workspaces: Vec<_> = wm.windows().into_iter().map(|w| w.workspace).collect();
+        assert_eq!(workspaces, ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_follow_focuses_where_the_focused_window_went() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Chat"
+type = "window"
+condition = "app-name = 'Slack'"
+action = "move-to-workspace 9"
+follow = true
+"#,
+        );
+        let wm = FakeWindowManager::new([window("Slack").id(1)]);
+        let planned = plan(&wm.windows(), &config, Trigger::Windows).unwrap();
+        Executor::new(&wm).with_follow(&config).execute(&planned);
+        assert_eq!(wm.focused_workspace(), None);
+
+        let wm = FakeWindowManager::new([window("Slack").id(1).focused()]);
+        let planned = plan(&wm.windows(), &config, Trigger::Windows).unwrap();
+        Executor::new(&wm).with_follow(&config).execute(&planned);
+        assert_eq!(wm.focused_workspace().as_deref(), Some("9"));
+    }
+
+    #[test]
+    fn test_parallel_executor_reports_outcomes_in_plan_order() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Maximize"
+type = "window"
+condition = "window-id > 0"
+action = "maximize"
+"#,
+        );
+        let wm = FakeWindowManager::new((1..=5).map(|id| window("Ghostty").id(id)));
+        wm.fail_window(3, "window is gone");
+
+        let planned = plan(&wm.windows(), &config, Trigger::Windows).unwrap();
+        let results = Executor::new(&wm).with_parallelism(3).execute(&planned);
+
+        let window_ids: Vec<_> = results
+            .iter()
+            .filter_map(|result| result.window_id)
+            .collect();
+        assert_eq!(window_ids, [1, 2, 3, 4, 5]);
+        assert_eq!(results[2].error.as_deref(), Some("window is gone"));
+        assert_eq!(wm.calls().len(), 5);
+    }
+
+    #[test]
+    fn test_rule_index_only_offers_rules_that_may_apply() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Slack on 2"
+type = "window"
+condition = "workspace = '2'"
+action = "move-to-workspace 9"
+
+[[rules]]
+name = "Meetings"
+type = "window"
+condition = "window-title contains 'Zoom'"
+action = "maximize"
+
+[[rules]]
+name = "Open terminal"
+type = "empty-workspace"
+workspace = "3"
+command = "open -a Ghostty"
+
+[[rules]]
+name = "Development"
+type = "window"
+condition = "workspace in ['4', '5']"
+action = "maximize"
+"#,
+        );
+        let index = RuleIndex::new(&config);
+
+        let on_1 = [window("Slack").build()];
+        let on_2 = [window("Slack").workspace("2").build()];
+        assert_eq!(index.candidates(&on_1, Trigger::Workspace("1")), [1]);
+        assert_eq!(index.candidates(&on_2, Trigger::Windows), [0, 1]);
+        assert_eq!(index.candidates(&on_2, Trigger::TitleChanged), [1]);
+        assert_eq!(index.candidates(&[], Trigger::Workspace("3")), [1, 2]);
+        assert!(index.candidates(&[], Trigger::Startup).is_empty());
+
+        let on_4_and_5 = [
+            window("Ghostty").workspace("4").build(),
+            window("Ghostty").id(2).workspace("5").build(),
+        ];
+        assert_eq!(index.candidates(&on_4_and_5, Trigger::Windows), [1, 3]);
+    }
+
+    #[test]
+    fn test_hanging_command_is_killed_after_its_rule_timeout() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Hangs"
+type = "startup"
+command = "sleep 10"
+timeout_ms = 50
+"#,
+        );
+        let wm = FakeWindowManager::new([window("Ghostty")]);
+
+        let started = Instant::now();
+        let context = EvalContext::new(Trigger::Startup);
+        let results = run_startup_rules(&config, &RuleIndex::new(&config), &context, &wm).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            results[0].error.as_deref(),
+            Some("Command 'sleep 10' timed out after 50ms")
+        );
+    }
+
+    #[test]
+    fn test_commands_run_where_and_with_what_their_rule_says() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Checks env"
+type = "empty-workspace"
+workspace = "3"
+command = "sh -c 'test \"$(pwd):$PROJECT:$AR_RULE_NAME/$AR_TRIGGER/$AR_WORKSPACE\" = \"/:foo:Checks env/workspace/3\"'"
+cwd = "/"
+env = { PROJECT = "foo" }
+detach = false
+"#,
+        );
+        let wm = FakeWindowManager::new([window("Ghostty")]);
+
+        let context = EvalContext::new(Trigger::Workspace("3"));
+        let results = evaluate_rules_for_workspace(
+            Vec::new(),
+            &config,
+            &RuleIndex::new(&config),
+            &context,
+            &wm,
+            false,
+        )
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].success, "{:?}", results[0].error);
+    }
+
+    #[test]
+    fn test_detached_commands_are_not_waited_for() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Slow launcher"
+type = "empty-workspace"
+workspace = "3"
+command = "sleep 10"
+
+[[rules]]
+name = "Typo"
+type = "empty-workspace"
+workspace = "3"
+command = "no-such-launcher"
+"#,
+        );
+        let wm = FakeWindowManager::new([window("Ghostty")]);
+
+        let started = Instant::now();
+        let context = EvalContext::new(Trigger::Workspace("3"));
+        let index = RuleIndex::new(&config);
+        let results =
+            evaluate_rules_for_workspace(Vec::new(), &config, &index, &context, &wm, false)
+                .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(results[0].success);
+        // Failing to start is still reported
+        assert!(!results[1].success);
+    }
+
+    #[test]
+    fn test_conditions_compile_when_the_config_is_parsed() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Typo"
+type = "window"
+condition = "app-nam = 'Slack'"
+action = "maximize"
+
+[[rules]]
+name = "Slack"
+type = "window"
+condition = "app-name = 'Slack'"
+action = "maximize"
+"#,
+        );
+        assert!(config.rules[1]
+            .compiled
+            .as_ref()
+            .is_some_and(|compiled| compiled.condition.is_ok()));
+        assert_eq!(
+            config.condition_errors(),
+            ["Rule 'Typo' (line 5) has an invalid condition: Unknown field in condition: app-nam"]
+        );
+
+        // The broken rule is skipped while the valid one still fires
+        let slack = [window("Slack").build()];
+        let context = EvalContext::new(Trigger::Windows);
+        let planned = plan_indexed(&slack, &config, &RuleIndex::new(&config), &context).unwrap();
+        testing::assert_rules(&planned, &["Slack"]);
+    }
+
+    #[test]
+    fn test_captured_output_feeds_later_rules() {
+        let config = config(
+            r#"
+[[rules]]
+name = "Detect project"
+type = "window"
+condition = "app-name = 'Ghostty'"
+action = "capture echo web"
+
+[[rules]]
+name = "Web terminals"
+type = "window"
+condition = "captured = 'web'"
+action = "move-to-workspace 3"
+
+[[rules]]
+name = "Other windows"
+type = "window"
+condition = "captured != 'web'"
+action = "maximize"
+"#,
+        );
+        let windows = [
+            window("Ghostty").id(1).build(),
+            window("Mail").id(2).build(),
+        ];
+        let context = EvalContext {
+            capture: Some(Arc::new(CommandCapture)),
+            ..EvalContext::new(Trigger::Windows)
+        };
+        let planned = plan_indexed(&windows, &config, &RuleIndex::new(&config), &context).unwrap();
+        testing::assert_rules(&planned, &["Web terminals", "Other windows"]);
+        testing::assert_moved(&planned, 1, "3");
+        let PlannedAction::Window { window, .. } = &planned[1] else {
+            panic!("Expected a window action");
+        };
+        assert_eq!(window.window_id, 2);
+
+        // Without a runner, as for dry runs, nothing is captured
+        let planned = plan(&windows, &config, Trigger::Windows).unwrap();
+        testing::assert_rules(&planned, &["Other windows", "Other windows"]);
+
+        let explanation = explain_rules_for_window(&windows[0], &config, &MatchContext::default());
+        assert!(explanation.rules[0].matched);
+        assert_eq!(
+            explanation.rules[1].reason.as_deref(),
+            Some("depends on captured output, and captures were skipped")
+        );
+    }
 }
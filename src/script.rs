@@ -0,0 +1,202 @@
+//! `type = "script"` rules, written in [Rhai](https://rhai.rs).
+//!
+//! A script sees the evaluated windows and decides what to do with them through the
+//! `aerospace` object. Like any other rule it only plans actions; they are performed
+//! afterwards by the executor, so dry runs and hooks apply to scripts as well.
+//!
+//! ```rhai
+//! for window in windows {
+//!     if window.app_name == "Slack" && window.workspace != "9" {
+//!         aerospace.move_to_workspace(window.id, "9");
+//!     }
+//! }
+//! ```
+//!
+//! In scope are `windows`, an array of maps with `id`, `app_name`, `title`,
+//! `workspace` and `focused`, and `workspace`, the evaluated workspace or `""` when
+//! single windows are evaluated. `aerospace` offers `move_to_workspace(id, workspace)`,
+//! `maximize(id)` and `exec(command)`.
+//!
+//! Scripts can't touch files or processes themselves, and are stopped after
+//! [`TIME_LIMIT`].
+
+use crate::action::Action;
+use crate::rules::PlannedAction;
+use crate::{log_info, WindowInfo};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+use std::cell::RefCell;
+use std::error::Error;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How long a script may run before it is aborted.
+pub const TIME_LIMIT: Duration = Duration::from_millis(250);
+
+/// The `aerospace` object, collecting the actions a script asks for.
+#[derive(Debug, Clone, Default)]
+struct Api {
+    actions: Rc<RefCell<Vec<ScriptAction>>>,
+}
+
+#[derive(Debug)]
+enum ScriptAction {
+    Window { window_id: i64, action: Action },
+    Exec(String),
+}
+
+/// Compiles `script` without running it, reporting syntax errors.
+pub fn check(script: &str) -> Result<(), String> {
+    engine("")
+        .compile(script)
+        .map(drop)
+        .map_err(|e| e.to_string())
+}
+
+/// Runs the script of the rule named `rule` against `windows` and returns the actions
+/// it planned.
+pub fn run(
+    rule: &str,
+    script: &str,
+    windows: &[WindowInfo],
+    workspace: Option<&str>,
+) -> Result<Vec<PlannedAction>, Box<dyn Error>> {
+    let mut engine = engine(rule);
+    let started = Instant::now();
+    engine.on_progress(move |_| (started.elapsed() > TIME_LIMIT).then_some(Dynamic::UNIT));
+
+    let api = Api::default();
+    let mut scope = Scope::new();
+    scope.push("windows", windows.iter().map(window_map).collect::<Array>());
+    scope.push("workspace", workspace.unwrap_or_default().to_string());
+    scope.push("aerospace", api.clone());
+
+    engine
+        .run_with_scope(&mut scope, script)
+        .map_err(|e| match *e {
+            EvalAltResult::ErrorTerminated(..) => format!(
+                "Script of rule '{rule}' exceeded the time limit of {}ms",
+                TIME_LIMIT.as_millis()
+            ),
+            e => format!("Script of rule '{rule}' failed: {e}"),
+        })?;
+
+    let actions = api.actions.take();
+    actions
+        .into_iter()
+        .map(|action| match action {
+            ScriptAction::Window { window_id, action } => windows
+                .iter()
+                .find(|window| i64::from(window.window_id) == window_id)
+                .map(|window| PlannedAction::Window {
+                    rule: rule.to_string(),
+                    action: action.to_string(),
+                    window: window.clone(),
+                })
+                .ok_or_else(|| {
+                    format!("Script of rule '{rule}' targets unknown window {window_id}").into()
+                }),
+            ScriptAction::Exec(command) => Ok(PlannedAction::Command {
+                rule: rule.to_string(),
+                command,
+                cwd: None,
+                env: Default::default(),
+                detach: false,
+            }),
+        })
+        .collect()
+}
+
+fn engine(rule: &str) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .disable_symbol("eval")
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(64 * 1024)
+        .set_max_array_size(10_000)
+        .set_max_map_size(10_000);
+
+    let rule = rule.to_string();
+    engine.on_print(move |line| log_info!("[{rule}] {line}"));
+
+    engine
+        .register_type_with_name::<Api>("Aerospace")
+        .register_fn(
+            "move_to_workspace",
+            |api: &mut Api, window_id: i64, workspace: &str| -> Result<(), Box<EvalAltResult>> {
+                let action = Action::parse(&format!("move-to-workspace {workspace}"))?;
+                api.push(ScriptAction::Window { window_id, action });
+                Ok(())
+            },
+        )
+        .register_fn("maximize", |api: &mut Api, window_id: i64| {
+            api.push(ScriptAction::Window {
+                window_id,
+                action: Action::Maximize,
+            })
+        })
+        .register_fn("exec", |api: &mut Api, command: &str| {
+            api.push(ScriptAction::Exec(command.to_string()))
+        });
+    engine
+}
+
+impl Api {
+    fn push(&mut self, action: ScriptAction) {
+        self.actions.borrow_mut().push(action);
+    }
+}
+
+fn window_map(window: &WindowInfo) -> Dynamic {
+    let mut map = Map::new();
+    map.insert("id".into(), i64::from(window.window_id).into());
+    map.insert("app_name".into(), window.app_name.clone().into());
+    map.insert("title".into(), window.window_title.clone().into());
+    map.insert("workspace".into(), window.workspace.clone().into());
+    map.insert("focused".into(), window.focused.into());
+    map.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(app_name: &str, window_id: u32, workspace: &str) -> WindowInfo {
+        WindowInfo {
+            app_name: app_name.to_string(),
+            window_id,
+            window_title: String::new(),
+            workspace: workspace.to_string(),
+            monitor: None,
+            frame: None,
+            focused: false,
+        }
+    }
+
+    #[test]
+    fn test_script_plans_actions() {
+        let windows = [window("Slack", 1, "1"), window("Safari", 2, "1")];
+        let planned = run(
+            "Chat",
+            r#"
+for window in windows {
+    if window.app_name == "Slack" { aerospace.move_to_workspace(window.id, "9"); }
+}
+"#,
+            &windows,
+            Some("1"),
+        )
+        .unwrap();
+        assert!(matches!(
+            &planned[..],
+            [PlannedAction::Window { action, window, .. }]
+                if action == "move-to-workspace 9" && window.window_id == 1
+        ));
+    }
+
+    #[test]
+    fn test_endless_script_is_stopped() {
+        let error = run("Spin", "loop {}", &[], None).unwrap_err();
+        assert!(error.to_string().contains("exceeded the time limit"));
+    }
+}
@@ -0,0 +1,2014 @@
+//! The service: keeps track of the windows, evaluates rules as they change, and answers
+//! [`Request`]s on a Unix socket.
+
+use crate::backend::WindowManager;
+use crate::condition::MatchContext;
+use crate::config::WebhookEvent;
+use crate::telemetry::Span;
+use crate::validate::{self, Severity};
+use crate::{
+    aerospace, backend, bootstrap, compat, config, displays, log_error, log_info, logging, metrics,
+    rules,
+    rules::{CaptureRunner, EvalContext, Trigger, UndoRecord},
+    session, webhooks,
+    window_index::WindowIndex,
+    AppPlacement, ErrorKind, Event, HistoryEntry, Pong, Report, Request, Response, RuleActivity,
+    RuleHits, RuleStatus, ServiceState, WindowChange, WindowInfo, PID_PATH, SOCKET_PATH,
+};
+use notify::{
+    Config as NotifyConfig, Event as NotifyEvent, EventKind, RecommendedWatcher, RecursiveMode,
+    Watcher,
+};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, UnixListener, UnixStream};
+use tokio::sync::RwLock;
+use tokio::sync::{broadcast, mpsc};
+
+type SharedState = Arc<RwLock<ServiceState>>;
+type EventSender = broadcast::Sender<Event>;
+/// The most recent log lines, oldest first.
+type LogBuffer = Arc<Mutex<VecDeque<String>>>;
+
+const LOG_BUFFER_LINES: usize = 1000;
+const HISTORY_LEN: usize = 1000;
+/// How many window listings' changes are kept for [`Request::GetWindowsSince`].
+const WINDOW_CHANGES_KEPT: usize = 100;
+/// Period over which rule firings are counted for the rate alarm.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+/// Largest request a client may send, e.g. a session to restore.
+const MAX_REQUEST_BYTES: usize = 1 << 20;
+
+/// Reads one request, which ends at a newline, at the end of the stream or once it's
+/// complete JSON, so clients may keep their end open for the response. `None` if the
+/// client sent nothing.
+async fn read_request(
+    stream: &mut UnixStream,
+) -> Result<Option<Request>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            if buffer.iter().all(u8::is_ascii_whitespace) {
+                return Ok(None);
+            }
+            return Ok(Some(serde_json::from_slice(&buffer)?));
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(end) = buffer.iter().position(|&byte| byte == b'\n') {
+            return Ok(Some(serde_json::from_slice(&buffer[..end])?));
+        }
+        match serde_json::from_slice(&buffer) {
+            Ok(request) => return Ok(Some(request)),
+            // Only part of the request has arrived yet
+            Err(e) if e.is_eof() => {}
+            Err(e) => return Err(e.into()),
+        }
+        if buffer.len() > MAX_REQUEST_BYTES {
+            return Err(format!("Request is larger than {MAX_REQUEST_BYTES} bytes").into());
+        }
+    }
+}
+
+async fn handle_client(
+    mut stream: UnixStream,
+    state: SharedState,
+    events: EventSender,
+    logs: LogBuffer,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(request) = read_request(&mut stream).await? else {
+        return Ok(());
+    };
+
+    let response = match request {
+        Request::Subscribe => return stream_events(stream, events.subscribe()).await,
+        Request::SetWindowPinned { window_id, pinned } => {
+            let mut state_guard = state.write().await;
+            if pinned {
+                state_guard.pinned_windows.insert(window_id);
+            } else {
+                state_guard.pinned_windows.remove(&window_id);
+            }
+            log_info!(
+                "Window {window_id} {}",
+                if pinned { "pinned" } else { "unpinned" }
+            );
+            Response::Success
+        }
+        Request::SetPaused { paused } => {
+            set_paused(&mut *state.write().await, paused);
+            Response::Paused(paused)
+        }
+        Request::TogglePaused => {
+            let mut state_guard = state.write().await;
+            let paused = !state_guard.paused;
+            set_paused(&mut state_guard, paused);
+            Response::Paused(paused)
+        }
+        Request::GetPaused => Response::Paused(state.read().await.paused),
+        Request::GetPinnedWindows => {
+            let state_guard = state.read().await;
+            Response::PinnedWindows(state_guard.pinned_windows.iter().copied().collect())
+        }
+        Request::GetHistory {
+            since,
+            rule,
+            failed,
+        } => {
+            let state_guard = state.read().await;
+            Response::History(
+                state_guard
+                    .history
+                    .iter()
+                    .filter(|entry| since.is_none_or(|since| entry.at >= since))
+                    .filter(|entry| rule.as_ref().is_none_or(|rule| entry.result.rule == *rule))
+                    .filter(|entry| !failed || !entry.result.success)
+                    .cloned()
+                    .collect(),
+            )
+        }
+        Request::GetReport => Response::Report(report(&*state.read().await)),
+        Request::Undo { last } => {
+            // Entries are claimed under the lock, so a second undo doesn't move them again
+            let (backend, claimed) = {
+                let mut state_guard = state.write().await;
+                let backend = state_guard.backend.clone();
+                let mut claimed = Vec::new();
+                for entry in state_guard.history.iter_mut().rev() {
+                    if claimed.len() == last {
+                        break;
+                    }
+                    let Some(record) =
+                        UndoRecord::of(&entry.result, entry.from_workspace.as_deref())
+                    else {
+                        continue;
+                    };
+                    if entry.undone {
+                        continue;
+                    }
+                    entry.undone = true;
+                    claimed.push((entry.at, entry.result.clone(), record));
+                }
+                (backend, claimed)
+            };
+
+            let moves = claimed
+                .iter()
+                .map(|(_, _, record)| (record.window_id, record.from_workspace.clone()))
+                .collect::<Vec<_>>();
+            let outcomes = run_blocking(move || {
+                Ok(moves
+                    .iter()
+                    .map(|(window_id, workspace)| {
+                        backend
+                            .move_window(*window_id, workspace)
+                            .map_err(|e| e.to_string())
+                    })
+                    .collect::<Vec<_>>())
+            })
+            .await
+            .unwrap_or_else(|e| vec![Err(e); claimed.len()]);
+
+            let mut state_guard = state.write().await;
+            let mut results = Vec::new();
+            for ((at, result, record), outcome) in claimed.into_iter().zip(outcomes) {
+                if outcome.is_err() {
+                    let entry = state_guard.history.iter_mut().find(|entry| {
+                        entry.at == at
+                            && entry.result.window_id == result.window_id
+                            && entry.result.action == result.action
+                    });
+                    if let Some(entry) = entry {
+                        entry.undone = false;
+                    }
+                }
+                results.push(rules::ActionResult {
+                    rule: format!("Undo {}", result.rule),
+                    action: record.reverse(),
+                    window_id: Some(record.window_id),
+                    app_name: result.app_name,
+                    success: outcome.is_ok(),
+                    error: outcome.err(),
+                });
+            }
+            log_action_results("Undo", &results);
+            Response::Undone(results)
+        }
+        Request::Ping => {
+            let listed_at = state.read().await.windows_listed_at;
+            Response::Pong(Pong {
+                at: chrono::Utc::now(),
+                windows_age_ms: listed_at.map(|listed_at| listed_at.elapsed().as_millis() as u64),
+            })
+        }
+        Request::GetLogs { lines } => {
+            let logs = logs.lock().unwrap();
+            Response::Logs(
+                logs.iter()
+                    .skip(logs.len().saturating_sub(lines))
+                    .cloned()
+                    .collect(),
+            )
+        }
+        Request::GetWindows { filter } if filter.is_empty() => {
+            // Status bars poll this often, so the answer is serialized once per change
+            let json = all_windows_json(&state).await?;
+            stream.write_all(&json).await?;
+            return Ok(());
+        }
+        Request::GetWindowsSince { seq } => {
+            let backend = state.read().await.backend.clone();
+            let focused = backend.focused_window();
+            let state_guard = state.read().await;
+            let (changes, reset) = windows_since(&state_guard, seq);
+            Response::WindowsSince {
+                seq: state_guard.window_seq,
+                changes,
+                reset,
+                focused,
+            }
+        }
+        Request::GetWindows { filter } => {
+            let (windows, backend) = {
+                let state_guard = state.read().await;
+                (state_guard.windows.clone(), state_guard.backend.clone())
+            };
+            let candidates: Box<dyn Iterator<Item = &WindowInfo>> = match &filter.workspace {
+                Some(workspace) => Box::new(windows.in_workspace(workspace).iter()),
+                None => Box::new(windows.iter()),
+            };
+            let matching = candidates.filter(|window| filter.matches(window));
+            let mut windows = filter.apply(matching.cloned().collect());
+            mark_focused(&mut windows, backend.focused_window());
+            Response::Windows(windows)
+        }
+        Request::GetFocusedWorkspace => {
+            let backend = state.read().await.backend.clone();
+            match backend.focused_workspace() {
+                Some(workspace) => Response::FocusedWorkspace(workspace),
+                None => Response::error(
+                    ErrorKind::Failed,
+                    format!("{} reported no focused workspace", backend.name()),
+                ),
+            }
+        }
+        Request::GetConfig => {
+            let state_guard = state.read().await;
+            match &state_guard.config {
+                Some(config) => Response::Config(Box::new(config.clone())),
+                None => Response::error(ErrorKind::InvalidConfig, "No config loaded"),
+            }
+        }
+        Request::GetEffectiveConfig => {
+            let state_guard = state.read().await;
+            match &state_guard.config {
+                Some(config) => Response::Config(Box::new(config.effective())),
+                None => Response::error(ErrorKind::InvalidConfig, "No config loaded"),
+            }
+        }
+        Request::Reload => {
+            refresh_state(state.clone(), &events).await;
+            announce_config_reload(&state, &events).await;
+            // The other rules are loaded, but the caller should hear about broken ones now
+            // rather than when they next evaluate
+            let state_guard = state.read().await;
+            log_config_problems(state_guard.config.as_ref());
+            let errors = state_guard
+                .config
+                .as_ref()
+                .map(config::Config::condition_errors)
+                .unwrap_or_default();
+            if errors.is_empty() {
+                Response::Success
+            } else {
+                Response::error(ErrorKind::InvalidConfig, errors.join("\n"))
+            }
+        }
+        Request::GetRules => {
+            let state_guard = state.read().await;
+            match &state_guard.config {
+                Some(config) => Response::Rules(
+                    config
+                        .rules
+                        .iter()
+                        .map(|rule| {
+                            let hits = state_guard
+                                .rule_hits
+                                .get(&rule.name)
+                                .cloned()
+                                .unwrap_or_default();
+                            RuleStatus {
+                                rule: rule.clone(),
+                                hits: hits.matched,
+                                executed: hits.executed,
+                                last_hit: hits.last_hit,
+                                rate_alarm: rate_alarm_threshold(&state_guard).is_some_and(
+                                    |threshold| {
+                                        recent_firings(&state_guard, &rule.name, Instant::now())
+                                            > threshold
+                                    },
+                                ),
+                            }
+                        })
+                        .collect(),
+                ),
+                None => Response::error(ErrorKind::InvalidConfig, "No config loaded"),
+            }
+        }
+        Request::SetRuleEnabled { name, enabled } => {
+            let response = edit_config_file(&state, &events, |source| {
+                config::set_rule_enabled(source, &name, enabled)
+            })
+            .await;
+            if matches!(response, Response::Success) {
+                log_info!(
+                    "Rule '{name}' {}",
+                    if enabled { "enabled" } else { "disabled" }
+                );
+            }
+            response
+        }
+        Request::AddRule { rule } => {
+            // Check the rule on its own, so problems elsewhere in the file don't block it
+            let security = state
+                .read()
+                .await
+                .config
+                .as_ref()
+                .map(|config| config.security.clone())
+                .unwrap_or_default();
+            let diagnostics = toml::to_string(&config::Config {
+                service: Default::default(),
+                hooks: Default::default(),
+                security,
+                webhooks: Vec::new(),
+                workspaces: Default::default(),
+                display_profiles: Default::default(),
+                rules: vec![rule.clone()],
+            })
+            .map(|source| validate::validate_config(&source));
+            match diagnostics.as_deref().map(|diagnostics| {
+                diagnostics
+                    .iter()
+                    .find(|diagnostic| diagnostic.severity == Severity::Error)
+            }) {
+                Err(e) => Response::error(ErrorKind::InvalidRequest, e.to_string()),
+                Ok(Some(diagnostic)) => {
+                    Response::error(ErrorKind::InvalidRequest, diagnostic.message.clone())
+                }
+                Ok(None) => {
+                    let response =
+                        edit_config_file(&state, &events, |source| config::add_rule(source, &rule))
+                            .await;
+                    if matches!(response, Response::Success) {
+                        log_info!("Rule '{}' added", rule.name);
+                    }
+                    response
+                }
+            }
+        }
+        Request::RemoveRule { name } => {
+            let response =
+                edit_config_file(&state, &events, |source| config::remove_rule(source, &name))
+                    .await;
+            if matches!(response, Response::Success) {
+                log_info!("Rule '{name}' removed");
+            }
+            response
+        }
+        Request::EvaluateRules {
+            workspace, dry_run, ..
+        } if !dry_run && state.read().await.paused => {
+            log_info!("Paused, skipping rules for workspace {workspace}");
+            Response::Paused(true)
+        }
+        Request::EvaluateRules {
+            workspace,
+            dry_run,
+            changed_only,
+        } => {
+            let backend = state.read().await.backend.clone();
+            let focused_workspace = (!dry_run).then(|| backend.focused_workspace());
+            let (config, index, context, windows, evaluated, unpinned) = {
+                let mut state_guard = state.write().await;
+                if let Some(focused) = focused_workspace {
+                    announce_focused_workspace(&mut state_guard, focused, &events);
+                }
+                let windows = state_guard.windows.clone();
+                let workspace_windows = windows.in_workspace(&workspace);
+                let populated = !workspace_windows.is_empty();
+                let unpinned: Vec<WindowInfo> = workspace_windows
+                    .iter()
+                    .filter(|window| !state_guard.pinned_windows.contains(&window.window_id))
+                    .cloned()
+                    .collect();
+                let evaluated = unpinned.clone();
+                let unpinned = if changed_only {
+                    let previous = state_guard.evaluated_windows.get(&workspace);
+                    unpinned
+                        .into_iter()
+                        .filter(|window| {
+                            !previous.is_some_and(|previous| {
+                                previous.iter().any(|seen| same_window(seen, window))
+                            })
+                        })
+                        .collect()
+                } else {
+                    unpinned
+                };
+                // A workspace holding only pinned or unchanged windows isn't empty, so no
+                // rule applies
+                let unpinned = (!populated || !unpinned.is_empty()).then_some(unpinned);
+                (
+                    state_guard.config.clone(),
+                    state_guard.rule_index.clone(),
+                    eval_context(&state_guard, Trigger::Windows, dry_run),
+                    windows,
+                    evaluated,
+                    unpinned,
+                )
+            };
+            match config {
+                None => Response::error(ErrorKind::InvalidConfig, "No config loaded"),
+                Some(config) => {
+                    let evaluation = match unpinned {
+                        None => Ok(Vec::new()),
+                        Some(mut unpinned) => {
+                            let workspace = workspace.clone();
+                            run_blocking(move || {
+                                // The index doesn't follow focus, so is-focused needs a fresh
+                                // answer
+                                if !unpinned.is_empty() {
+                                    mark_focused(&mut unpinned, backend.focused_window());
+                                }
+                                let context = EvalContext {
+                                    trigger: Trigger::Workspace(&workspace),
+                                    ..context
+                                };
+                                rules::evaluate_rules_for_workspace(
+                                    unpinned,
+                                    &config,
+                                    &index,
+                                    &context,
+                                    backend.as_ref(),
+                                    dry_run,
+                                )
+                                .map_err(|e| e.to_string())
+                            })
+                            .await
+                        }
+                    };
+
+                    match evaluation {
+                        Ok(results) => {
+                            let context = if dry_run {
+                                format!("Dry run for workspace {workspace}")
+                            } else {
+                                format!("Workspace {workspace}")
+                            };
+                            log_action_results(&context, &results);
+                            if !dry_run {
+                                let mut state_guard = state.write().await;
+                                record_actions_from(&mut state_guard, &events, &results, &windows);
+                                state_guard.evaluated_windows.insert(workspace, evaluated);
+                            }
+                            Response::evaluated(results)
+                        }
+                        Err(e) => Response::error(
+                            ErrorKind::InvalidConfig,
+                            format!("Rule evaluation failed: {e}"),
+                        ),
+                    }
+                }
+            }
+        }
+        Request::EvaluateWindow { window_id, dry_run } if !dry_run && state.read().await.paused => {
+            log_info!("Paused, skipping rules for window {window_id}");
+            Response::Paused(true)
+        }
+        Request::EvaluateWindow { window_id, dry_run } => {
+            let backend = state.read().await.backend.clone();
+            let listed = {
+                let backend = backend.clone();
+                run_blocking(move || backend.list_windows().map_err(|e| e.to_string())).await
+            };
+            let (config, index, context, windows, window, pinned) = {
+                let mut state_guard = state.write().await;
+                let window = listed.map(|windows| {
+                    let window = windows
+                        .iter()
+                        .find(|window| window.window_id == window_id)
+                        .cloned();
+                    // The window usually just opened, so the listing is newer than the index
+                    let windows = WindowIndex::new(windows);
+                    announce_window_changes(&state_guard.windows, &windows, &events);
+                    set_windows(&mut state_guard, windows);
+                    window
+                });
+                (
+                    state_guard.config.clone(),
+                    state_guard.rule_index.clone(),
+                    eval_context(&state_guard, Trigger::Windows, dry_run),
+                    state_guard.windows.clone(),
+                    window,
+                    state_guard.pinned_windows.contains(&window_id),
+                )
+            };
+            match (config, window) {
+                (None, _) => Response::error(ErrorKind::InvalidConfig, "No config loaded"),
+                (_, Err(e)) => {
+                    Response::error(ErrorKind::Failed, format!("Failed to list windows: {e}"))
+                }
+                (_, Ok(None)) => Response::error(
+                    ErrorKind::InvalidRequest,
+                    format!("Window {window_id} not found"),
+                ),
+                (Some(_), Ok(Some(_))) if pinned => Response::evaluated(Vec::new()),
+                (Some(config), Ok(Some(mut window))) => {
+                    let evaluation = run_blocking(move || {
+                        mark_focused(std::slice::from_mut(&mut window), backend.focused_window());
+                        rules::evaluate_rules_for_window(
+                            &window,
+                            &config,
+                            &index,
+                            &context,
+                            backend.as_ref(),
+                            dry_run,
+                        )
+                        .map_err(|e| e.to_string())
+                    })
+                    .await;
+                    match evaluation {
+                        Ok(results) => {
+                            log_action_results(&format!("Window {window_id}"), &results);
+                            if !dry_run {
+                                let mut state_guard = state.write().await;
+                                record_actions_from(&mut state_guard, &events, &results, &windows);
+                            }
+                            Response::evaluated(results)
+                        }
+                        Err(e) => Response::error(
+                            ErrorKind::InvalidConfig,
+                            format!("Rule evaluation failed: {e}"),
+                        ),
+                    }
+                }
+            }
+        }
+        Request::Bootstrap { dry_run, .. } if !dry_run && state.read().await.paused => {
+            log_info!("Paused, not bootstrapping workspaces");
+            Response::Paused(true)
+        }
+        Request::Bootstrap { workspace, dry_run } => {
+            let (config, backend) = {
+                let state_guard = state.read().await;
+                (state_guard.config.clone(), state_guard.backend.clone())
+            };
+            match config {
+                None => Response::error(ErrorKind::InvalidConfig, "No config loaded"),
+                Some(config)
+                    if workspace
+                        .as_ref()
+                        .is_some_and(|workspace| !config.workspaces.contains_key(workspace)) =>
+                {
+                    Response::error(
+                        ErrorKind::InvalidRequest,
+                        format!(
+                            "No workspace named '{}' in the config",
+                            workspace.unwrap_or_default()
+                        ),
+                    )
+                }
+                Some(config) => {
+                    // Waiting for launched apps takes a while, so the state stays unlocked
+                    let bootstrapped = run_blocking(move || {
+                        bootstrap::bootstrap(
+                            &config,
+                            workspace.as_deref(),
+                            backend.as_ref(),
+                            dry_run,
+                            bootstrap::WINDOW_TIMEOUT,
+                        )
+                        .map_err(|e| e.to_string())
+                    })
+                    .await;
+                    match bootstrapped {
+                        Ok(results) => {
+                            log_action_results("Bootstrap", &results);
+                            if !dry_run {
+                                record_actions(&mut *state.write().await, &events, &results);
+                                refresh_state(state.clone(), &events).await;
+                            }
+                            Response::evaluated(results)
+                        }
+                        Err(e) => {
+                            Response::error(ErrorKind::Failed, format!("Bootstrap failed: {e}"))
+                        }
+                    }
+                }
+            }
+        }
+        Request::RestoreSession { name, dry_run, .. } if !dry_run && state.read().await.paused => {
+            log_info!("Paused, not restoring session {name}");
+            Response::Paused(true)
+        }
+        Request::RestoreSession {
+            name,
+            session,
+            dry_run,
+        } => {
+            let (backend, pinned, windows) = {
+                let state_guard = state.read().await;
+                (
+                    state_guard.backend.clone(),
+                    state_guard.pinned_windows.clone(),
+                    state_guard.windows.clone(),
+                )
+            };
+            let label = name.clone();
+            let restored = run_blocking(move || {
+                let listed = backend.list_windows().map_err(|e| e.to_string())?;
+                let unpinned: Vec<WindowInfo> = listed
+                    .into_iter()
+                    .filter(|window| !pinned.contains(&window.window_id))
+                    .collect();
+                Ok(session::restore(
+                    &label,
+                    &session,
+                    &unpinned,
+                    backend.as_ref(),
+                    dry_run,
+                ))
+            })
+            .await;
+            match restored {
+                Ok(results) => {
+                    log_action_results(&format!("Session {name}"), &results);
+                    if !dry_run {
+                        let mut state_guard = state.write().await;
+                        record_actions_from(&mut state_guard, &events, &results, &windows);
+                    }
+                    Response::evaluated(results)
+                }
+                Err(e) => {
+                    Response::error(ErrorKind::Failed, format!("Failed to list windows: {e}"))
+                }
+            }
+        }
+        Request::Gather { dry_run, .. } if !dry_run && state.read().await.paused => {
+            log_info!("Paused, not gathering windows");
+            Response::Paused(true)
+        }
+        Request::Gather { workspace, dry_run } => {
+            let (config, backend, pinned, windows) = {
+                let state_guard = state.read().await;
+                (
+                    state_guard.config.clone(),
+                    state_guard.backend.clone(),
+                    state_guard.pinned_windows.clone(),
+                    state_guard.windows.clone(),
+                )
+            };
+            match config {
+                None => Response::error(ErrorKind::InvalidConfig, "No config loaded"),
+                Some(config) => {
+                    let gathered = run_blocking(move || {
+                        let listed = backend.list_windows().map_err(|e| e.to_string())?;
+                        let unpinned: Vec<WindowInfo> = listed
+                            .into_iter()
+                            .filter(|window| !pinned.contains(&window.window_id))
+                            .collect();
+                        Ok(rules::gather_windows(
+                            &unpinned,
+                            &config,
+                            workspace.as_deref(),
+                            backend.as_ref(),
+                            dry_run,
+                        )
+                        .map_err(|e| e.to_string()))
+                    })
+                    .await;
+                    match gathered {
+                        Ok(Ok(results)) => {
+                            log_action_results("Gather", &results);
+                            if !dry_run {
+                                let mut state_guard = state.write().await;
+                                record_actions_from(&mut state_guard, &events, &results, &windows);
+                            }
+                            Response::evaluated(results)
+                        }
+                        Ok(Err(e)) => Response::error(
+                            ErrorKind::InvalidConfig,
+                            format!("Rule evaluation failed: {e}"),
+                        ),
+                        Err(e) => Response::error(
+                            ErrorKind::Failed,
+                            format!("Failed to list windows: {e}"),
+                        ),
+                    }
+                }
+            }
+        }
+        Request::ExplainWindow {
+            window_id,
+            app_name,
+        } => {
+            let state_guard = state.read().await;
+            match (&state_guard.config, state_guard.backend.list_windows()) {
+                (Some(config), Ok(windows)) => {
+                    let workspace_windows = rules::count_workspace_windows(&windows);
+                    let context = MatchContext {
+                        tracker: Some(&state_guard.tracker),
+                        workspace_windows: Some(&workspace_windows),
+                        captured: None,
+                    };
+                    let explanations: Vec<_> = windows
+                        .iter()
+                        .filter(|window| window_id.is_none_or(|id| window.window_id == id))
+                        .filter(|window| {
+                            app_name
+                                .as_ref()
+                                .is_none_or(|app| window.app_name.eq_ignore_ascii_case(app))
+                        })
+                        .map(|window| rules::explain_rules_for_window(window, config, &context))
+                        .collect();
+                    if explanations.is_empty() {
+                        Response::error(ErrorKind::InvalidRequest, "No matching window found")
+                    } else {
+                        Response::Explained(explanations)
+                    }
+                }
+                (None, _) => Response::error(ErrorKind::InvalidConfig, "No config loaded"),
+                (_, Err(e)) => {
+                    Response::error(ErrorKind::Failed, format!("Failed to list windows: {e}"))
+                }
+            }
+        }
+    };
+
+    let response_json = serde_json::to_string(&response)?;
+    stream.write_all(response_json.as_bytes()).await?;
+
+    Ok(())
+}
+
+/// Replaces the known windows, noting their changes in the window records and for
+/// [`Request::GetWindowsSince`]. Requests still reading the previous ones keep them.
+fn set_windows(state: &mut ServiceState, windows: WindowIndex) {
+    Arc::make_mut(&mut state.tracker).observe(windows.iter(), chrono::Utc::now());
+    let updated = windows
+        .iter()
+        .filter(|window| state.windows.get(window.window_id) != Some(window))
+        .map(|window| WindowChange::Updated(window.clone()));
+    let closed = state
+        .windows
+        .iter()
+        .filter(|window| windows.get(window.window_id).is_none())
+        .map(|window| WindowChange::Closed(window.window_id));
+    let changes: Vec<_> = updated.chain(closed).collect();
+    if !changes.is_empty() {
+        state.window_seq += 1;
+        state.window_changes.push_back((state.window_seq, changes));
+        if state.window_changes.len() > WINDOW_CHANGES_KEPT {
+            state.window_changes.pop_front();
+        }
+    }
+    state.windows = Arc::new(windows);
+    state.windows_json = None;
+}
+
+/// Replaces the loaded config, indexing its rules.
+fn set_config(state: &mut ServiceState, config: Option<config::Config>) {
+    let index = config
+        .as_ref()
+        .map(rules::RuleIndex::new)
+        .unwrap_or_default();
+    state.rule_index = Arc::new(index);
+    state.config = config;
+}
+
+/// Logs what's wrong with a config that was just (re)loaded. Not done on every refresh,
+/// which would repeat the same errors every few seconds.
+fn log_config_problems(config: Option<&config::Config>) {
+    let Some(config) = config else {
+        return;
+    };
+    for error in config.condition_errors() {
+        log_error!("{error}");
+    }
+    for rule in &config.rules {
+        for deprecation in compat::deprecations(rule) {
+            log_info!("Rule '{}' {}: {deprecation}", rule.name, deprecation.field);
+        }
+    }
+}
+
+/// Sets which of `windows` has focus, if the window manager could tell.
+fn mark_focused(windows: &mut [WindowInfo], focused: Option<u32>) {
+    if let Some(focused) = focused {
+        for window in windows {
+            window.focused = window.window_id == focused;
+        }
+    }
+}
+
+/// The window changes after sequence number `seq`, one per window, and whether they
+/// had to be every current window instead because those changes aren't all kept.
+fn windows_since(state: &ServiceState, seq: u64) -> (Vec<WindowChange>, bool) {
+    let kept_since = state
+        .window_changes
+        .front()
+        .map_or(state.window_seq, |(first, _)| first - 1);
+    // A sequence number from before a restart may be ahead of the current one
+    if seq > state.window_seq || seq < kept_since {
+        let windows = state.windows.iter().cloned();
+        return (windows.map(WindowChange::Updated).collect(), true);
+    }
+
+    // Later changes to a window replace earlier ones
+    let mut latest = BTreeMap::new();
+    for (_, changes) in state.window_changes.iter().filter(|(at, _)| *at > seq) {
+        for change in changes {
+            let window_id = match change {
+                WindowChange::Updated(window) => window.window_id,
+                WindowChange::Closed(window_id) => *window_id,
+            };
+            latest.insert(window_id, change.clone());
+        }
+    }
+    (latest.into_values().collect(), false)
+}
+
+/// [`Response::Windows`] with every window, serialized. Reused until the windows or the
+/// focused window change.
+async fn all_windows_json(state: &SharedState) -> Result<Arc<[u8]>, serde_json::Error> {
+    let backend = state.read().await.backend.clone();
+    let focused = backend.focused_window();
+    if let Some((cached_focused, json)) = &state.read().await.windows_json {
+        if *cached_focused == focused {
+            return Ok(json.clone());
+        }
+    }
+
+    let shared = state.read().await.windows.clone();
+    let mut windows = shared.to_vec();
+    mark_focused(&mut windows, focused);
+    let json: Arc<[u8]> = serde_json::to_vec(&Response::Windows(windows))?.into();
+    let mut state_guard = state.write().await;
+    // Unless a refresh replaced the windows meanwhile
+    if Arc::ptr_eq(&state_guard.windows, &shared) {
+        state_guard.windows_json = Some((focused, json.clone()));
+    }
+    Ok(json)
+}
+
+async fn stream_events(
+    mut stream: UnixStream,
+    mut receiver: broadcast::Receiver<Event>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    loop {
+        match receiver.recv().await {
+            Ok(event) => {
+                let mut line = serde_json::to_string(&event)?;
+                line.push('\n');
+                if stream.write_all(line.as_bytes()).await.is_err() {
+                    // Subscriber went away
+                    return Ok(());
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                log_error!("Subscriber lagged behind, skipped {skipped} events");
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+fn set_paused(state: &mut ServiceState, paused: bool) {
+    if state.paused != paused {
+        log_info!("Rules {}", if paused { "paused" } else { "resumed" });
+    }
+    state.paused = paused;
+}
+
+/// Counts performed actions towards their rule, adds them to the history and the
+/// records of their windows, and tells subscribers about them.
+fn record_actions(state: &mut ServiceState, events: &EventSender, results: &[rules::ActionResult]) {
+    let windows = state.windows.clone();
+    record_actions_from(state, events, results, &windows);
+}
+
+/// Like [`record_actions`], but takes where windows were from `windows`, as listed
+/// before the actions ran. Actions run without the state lock, so a refresh may
+/// already have seen them move.
+fn record_actions_from(
+    state: &mut ServiceState,
+    events: &EventSender,
+    results: &[rules::ActionResult],
+    windows: &WindowIndex,
+) {
+    let now = chrono::Utc::now();
+    for result in results {
+        let hits = state.rule_hits.entry(result.rule.clone()).or_default();
+        hits.matched += 1;
+        hits.executed += u64::from(result.success);
+        hits.last_hit = Some(now);
+        track_firing_rate(state, events, &result.rule);
+        if let Some(window_id) = result.window_id.filter(|_| result.success) {
+            Arc::make_mut(&mut state.tracker).record_match(window_id, &result.rule);
+        }
+
+        if state.history.len() == HISTORY_LEN {
+            state.history.pop_front();
+        }
+        let from_workspace = result
+            .window_id
+            .and_then(|window_id| windows.get(window_id))
+            .map(|window| window.workspace.clone());
+        state.history.push_back(HistoryEntry {
+            at: now,
+            result: result.clone(),
+            from_workspace,
+            undone: false,
+        });
+
+        // Sending only fails when nobody is subscribed
+        let result = result.clone();
+        let _ = events.send(if result.success {
+            Event::RuleFired { result }
+        } else {
+            Event::ActionFailed { result }
+        });
+    }
+
+    let hits_file = state
+        .config
+        .as_ref()
+        .and_then(|config| config.service.rule_hits_file.as_deref());
+    if let Some(path) = hits_file.filter(|_| !results.is_empty()) {
+        write_rule_hits(path, &state.rule_hits);
+    }
+}
+
+/// Reads the hit counters kept in `path`. A missing file means counting starts now.
+fn read_rule_hits(path: &str) -> HashMap<String, RuleHits> {
+    match std::fs::read_to_string(path) {
+        Ok(source) => serde_json::from_str(&source).unwrap_or_else(|e| {
+            log_error!("Ignoring rule hits in {path}: {e}");
+            HashMap::new()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+        Err(e) => {
+            log_error!("Failed to read rule hits from {path}: {e}");
+            HashMap::new()
+        }
+    }
+}
+
+fn write_rule_hits(path: &str, hits: &HashMap<String, RuleHits>) {
+    // Renamed into place, so a crash while writing never loses the counts
+    let partial = format!("{path}.partial");
+    let written = serde_json::to_vec(hits)
+        .map_err(std::io::Error::from)
+        .and_then(|json| std::fs::write(&partial, json))
+        .and_then(|()| std::fs::rename(&partial, path));
+    if let Err(e) = written {
+        log_error!("Failed to write rule hits to {path}: {e}");
+    }
+}
+
+/// Firings per minute above which a rule raises an alarm, `None` if disabled.
+fn rate_alarm_threshold(state: &ServiceState) -> Option<usize> {
+    let threshold = state
+        .config
+        .as_ref()
+        .and_then(|config| config.service.rate_alarm_per_minute)
+        .unwrap_or(config::DEFAULT_RATE_ALARM_PER_MINUTE);
+    (threshold > 0).then_some(threshold as usize)
+}
+
+/// How often `rule` fired within the minute before `now`.
+fn recent_firings(state: &ServiceState, rule: &str, now: Instant) -> usize {
+    state.rule_firings.get(rule).map_or(0, |firings| {
+        firings
+            .iter()
+            .filter(|fired| now.duration_since(**fired) < RATE_WINDOW)
+            .count()
+    })
+}
+
+/// Notes that `rule` fired and warns when that takes it over the alarm threshold.
+fn track_firing_rate(state: &mut ServiceState, events: &EventSender, rule: &str) {
+    let now = Instant::now();
+    let firings = state.rule_firings.entry(rule.to_string()).or_default();
+    while firings
+        .front()
+        .is_some_and(|fired| now.duration_since(*fired) >= RATE_WINDOW)
+    {
+        firings.pop_front();
+    }
+    firings.push_back(now);
+
+    let per_minute = firings.len();
+    if rate_alarm_threshold(state).is_some_and(|threshold| per_minute == threshold + 1) {
+        log_error!(
+            "Rule '{rule}' fired {per_minute} times within a minute; check it for a loop or an overly broad condition"
+        );
+        // Sending only fails when nobody is subscribed
+        let _ = events.send(Event::RuleRateExceeded {
+            rule: rule.to_string(),
+            per_minute,
+        });
+    }
+}
+
+type PlacementTime = HashMap<(String, String), Duration>;
+
+/// Adds `elapsed` to the app and workspace of each of `windows`.
+fn add_placement_time(
+    placement_time: &mut PlacementTime,
+    windows: &WindowIndex,
+    elapsed: Duration,
+) {
+    for window in windows.iter() {
+        let key = (window.app_name.clone(), window.workspace.clone());
+        *placement_time.entry(key).or_default() += elapsed;
+    }
+}
+
+fn report(state: &ServiceState) -> Report {
+    // Count the windows as they were last listed up to now, without recording it
+    let mut placement_time = state.placement_time.clone();
+    if let Some(listed_at) = state.windows_listed_at {
+        add_placement_time(&mut placement_time, &state.windows, listed_at.elapsed());
+    }
+    let mut placements: Vec<_> = placement_time
+        .into_iter()
+        .map(|((app_name, workspace), time)| AppPlacement {
+            app_name,
+            workspace,
+            seconds: time.as_secs(),
+        })
+        .collect();
+    placements.sort_by(|a, b| {
+        b.seconds
+            .cmp(&a.seconds)
+            .then_with(|| a.app_name.cmp(&b.app_name))
+    });
+
+    let mut rules: Vec<RuleActivity> = Vec::new();
+    for entry in &state.history {
+        let index = match rules.iter().position(|rule| rule.rule == entry.result.rule) {
+            Some(index) => index,
+            None => {
+                rules.push(RuleActivity {
+                    rule: entry.result.rule.clone(),
+                    actions: 0,
+                    failed: 0,
+                });
+                rules.len() - 1
+            }
+        };
+        rules[index].actions += 1;
+        if !entry.result.success {
+            rules[index].failed += 1;
+        }
+    }
+    rules.sort_by(|a, b| b.actions.cmp(&a.actions).then_with(|| a.rule.cmp(&b.rule)));
+
+    Report {
+        since: state.started_at,
+        placements,
+        rules,
+    }
+}
+
+/// Whether `a` and `b` are the same window with the same app and title, i.e. rules
+/// matching one would match the other the same way.
+fn same_window(a: &WindowInfo, b: &WindowInfo) -> bool {
+    a.window_id == b.window_id && a.app_name == b.app_name && a.window_title == b.window_title
+}
+
+/// Tells subscribers which windows appeared, disappeared or changed workspace.
+fn announce_window_changes(previous: &WindowIndex, current: &WindowIndex, events: &EventSender) {
+    for window in current.iter() {
+        let event = match previous.get(window.window_id) {
+            None => Event::WindowCreated {
+                window: window.clone(),
+            },
+            Some(old) if old.workspace != window.workspace => Event::WindowMoved {
+                window: window.clone(),
+                from: old.workspace.clone(),
+            },
+            Some(_) => continue,
+        };
+        let _ = events.send(event);
+    }
+
+    for window in previous.iter() {
+        if current.get(window.window_id).is_none() {
+            let _ = events.send(Event::WindowClosed {
+                window: window.clone(),
+            });
+        }
+    }
+}
+
+/// Moves workspaces to the monitors the display profile for the connected monitors
+/// assigns them, e.g. after docking.
+fn apply_display_profile(state: &mut ServiceState, events: &EventSender) {
+    let Some(config) = state.config.clone() else {
+        return;
+    };
+    let monitors = state.monitors.len();
+    let Some((name, profile)) = displays::profile_for(&config, monitors) else {
+        state.display_profile = None;
+        return;
+    };
+    log_info!("{monitors} monitors connected, using display profile {name}");
+    state.display_profile = Some(name.to_string());
+    if state.paused {
+        log_info!("Paused, not moving workspaces to their monitors");
+        return;
+    }
+    let results = displays::apply(name, profile, state.backend.as_ref());
+    log_action_results(&format!("Display profile {name}"), &results);
+    record_actions(state, events, &results);
+}
+
+/// Tells subscribers when `focused` differs from the workspace last seen focused.
+fn announce_focused_workspace(
+    state: &mut ServiceState,
+    focused: Option<String>,
+    events: &EventSender,
+) {
+    let Some(workspace) = focused else {
+        return;
+    };
+    if state.focused_workspace.as_ref() == Some(&workspace) {
+        return;
+    }
+    let previous = state.focused_workspace.replace(workspace.clone());
+    state.previous_workspace.clone_from(&previous);
+    let _ = events.send(Event::WorkspaceFocused {
+        workspace,
+        previous,
+    });
+}
+
+/// The context to evaluate rules for `trigger` in, from what the service last saw.
+/// Capture actions only run their commands when it's not for a dry run.
+/// Runs `work` on the blocking pool. Actions may wait on commands for up to their
+/// timeout, and the runtime has to keep answering other requests meanwhile.
+async fn run_blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result)
+}
+
+fn eval_context<'a>(state: &ServiceState, trigger: Trigger<'a>, dry_run: bool) -> EvalContext<'a> {
+    let capture: Option<Arc<dyn CaptureRunner>> =
+        (!dry_run).then(|| Arc::new(rules::CommandCapture) as _);
+    EvalContext {
+        focused_workspace: state.focused_workspace.clone(),
+        previous_workspace: state.previous_workspace.clone(),
+        monitors: state.monitors.clone(),
+        profile: state.display_profile.clone(),
+        tracker: Some(state.tracker.clone()),
+        workspace_windows: Some(rules::count_workspace_windows(state.windows.iter())),
+        frontmost_app: state
+            .focused_window
+            .and_then(|window_id| state.windows.get(window_id))
+            .map(|window| window.app_name.clone()),
+        capture,
+        ..EvalContext::new(trigger)
+    }
+}
+
+async fn announce_config_reload(state: &SharedState, events: &EventSender) {
+    let rules = state
+        .read()
+        .await
+        .config
+        .as_ref()
+        .map(|config| config.rules.len());
+    let event = match rules {
+        Some(rules) => {
+            let (errors, warnings) = config_diagnostics(state).await;
+            Event::ConfigReloaded {
+                rules,
+                warnings,
+                errors,
+            }
+        }
+        // Without a config, load it again for the reason
+        None => match load_config(state).await {
+            Err(error) => Event::ConfigReloadFailed { error },
+            Ok(_) => return,
+        },
+    };
+    let _ = events.send(event);
+}
+
+/// What validating the config file finds, as errors and warnings, including rules
+/// targeting workspaces the window manager doesn't have. Nothing if the file can't be
+/// read.
+async fn config_diagnostics(state: &SharedState) -> (Vec<String>, Vec<String>) {
+    let (path, backend) = {
+        let state_guard = state.read().await;
+        (
+            get_config_file_path(state_guard.config_path.as_deref()),
+            state_guard.backend.clone(),
+        )
+    };
+    let Some(source) = path.and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return Default::default();
+    };
+    let workspaces = backend.workspaces();
+    let (errors, warnings): (Vec<_>, Vec<_>) =
+        validate::validate_config_with_workspaces(&source, &workspaces)
+            .into_iter()
+            .partition(|diagnostic| diagnostic.severity == Severity::Error);
+    let describe = |diagnostics: Vec<validate::Diagnostic>| {
+        diagnostics
+            .iter()
+            .map(validate::Diagnostic::describe)
+            .collect()
+    };
+    (describe(errors), describe(warnings))
+}
+
+/// POSTs rule and config events to the webhooks of the config. The webhooks of the
+/// last config that loaded stay in use while the config is broken, so they still hear
+/// about it.
+async fn deliver_webhooks(state: SharedState, mut events: broadcast::Receiver<Event>) {
+    let mut webhooks = Vec::new();
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                log_error!("Webhooks missed {missed} events");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let Some(kind) = WebhookEvent::of(&event) else {
+            continue;
+        };
+        if let Some(config) = &state.read().await.config {
+            webhooks.clone_from(&config.webhooks);
+        }
+
+        for webhook in webhooks
+            .iter()
+            .filter(|webhook| webhook.events.contains(&kind))
+        {
+            let url = webhook.url.clone();
+            let event = event.clone();
+            tokio::task::spawn_blocking(move || {
+                let payload = webhooks::Payload {
+                    event: kind,
+                    at: chrono::Utc::now(),
+                    details: &event,
+                };
+                if let Err(e) = webhooks::post(&url, &payload) {
+                    log_error!("Webhook {url} failed: {e}");
+                }
+            });
+        }
+    }
+}
+
+fn log_action_results(context: &str, results: &[rules::ActionResult]) {
+    let failed = results.iter().filter(|result| !result.success).count();
+    for result in results {
+        if result.success {
+            log_info!("{result}");
+        } else {
+            log_error!("{result}");
+        }
+    }
+    log_info!(
+        "{context}: {} actions performed, {failed} failed",
+        results.len() - failed
+    );
+}
+
+fn get_config_file_path(explicit_path: Option<&str>) -> Option<PathBuf> {
+    if let Some(path) = explicit_path {
+        // Convert to absolute path
+        let path_buf = PathBuf::from(path);
+        if path_buf.is_absolute() {
+            Some(path_buf)
+        } else {
+            // Make relative paths absolute by prepending current directory
+            if let Ok(current_dir) = std::env::current_dir() {
+                Some(current_dir.join(path_buf))
+            } else {
+                Some(path_buf)
+            }
+        }
+    } else {
+        // Use the same logic as config::find_config_file() but return the path even if file doesn't exist
+        let xdg_runtime_dir = std::env::var("XDG_RUNTIME_DIR")
+            .unwrap_or_else(|_| format!("{}/.config", std::env::var("HOME").unwrap_or_default()));
+
+        let xdg_path = PathBuf::from(xdg_runtime_dir)
+            .join("aerospace")
+            .join("rules.toml");
+
+        if xdg_path.exists() {
+            Some(xdg_path)
+        } else if let Ok(home_dir) = std::env::var("HOME") {
+            Some(PathBuf::from(home_dir).join(".aerospace-rules.toml"))
+        } else {
+            None
+        }
+    }
+}
+
+async fn load_config(state: &SharedState) -> Result<Option<config::Config>, String> {
+    let config_path = state.read().await.config_path.clone();
+    config::read_config(config_path.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Rewrites the config file with `edit`, creating it if needed, and reloads it.
+async fn edit_config_file(
+    state: &SharedState,
+    events: &EventSender,
+    edit: impl FnOnce(&str) -> Result<String, Box<dyn std::error::Error>>,
+) -> Response {
+    let Some(path) = get_config_file_path(state.read().await.config_path.as_deref()) else {
+        return Response::error(ErrorKind::Failed, "No location for the config file");
+    };
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Response::error(
+                ErrorKind::Failed,
+                format!("Failed to read {}: {e}", path.display()),
+            )
+        }
+    };
+    let edited = match edit(&source) {
+        Ok(edited) => edited,
+        Err(e) => return Response::error(ErrorKind::InvalidRequest, e.to_string()),
+    };
+    if let Err(e) = std::fs::write(&path, edited) {
+        return Response::error(
+            ErrorKind::Failed,
+            format!("Failed to write {}: {e}", path.display()),
+        );
+    }
+
+    // Don't wait for the file watcher, so the change is visible to the next request
+    refresh_config_only(state.clone(), events).await;
+    Response::Success
+}
+
+/// Applies the `[service]` section of a freshly loaded config to the running process.
+async fn apply_service_settings(state: &SharedState, config: Option<&config::Config>) {
+    let default_service = config::ServiceConfig::default();
+    let service_config = config.map_or(&default_service, |config| &config.service);
+
+    let backend = backend::for_config(service_config);
+    let mut state_guard = state.write().await;
+    if !state_guard.backend_fixed && state_guard.backend.name() != backend.name() {
+        log_info!("Switching window manager backend to {}", backend.name());
+        state_guard.backend = backend;
+    }
+    drop(state_guard);
+
+    let service = config.map(|config| &config.service);
+    aerospace::set_binary(service.and_then(|service| service.aerospace_bin.as_deref()));
+    aerospace::set_cache_ttl(
+        service
+            .and_then(|service| service.query_cache_ttl_ms)
+            .map_or(aerospace::DEFAULT_CACHE_TTL, Duration::from_millis),
+    );
+    aerospace::set_move_limits(
+        service
+            .and_then(|service| service.parallel_actions)
+            .unwrap_or(config::DEFAULT_PARALLEL_ACTIONS),
+        Duration::from_millis(
+            service
+                .and_then(|service| service.action_timeout_ms)
+                .unwrap_or(config::DEFAULT_ACTION_TIMEOUT_MS),
+        ),
+    );
+}
+
+async fn refresh_state(state: SharedState, events: &EventSender) {
+    log_info!("Refreshing aerospace state...");
+    let span = Span::start("refresh");
+
+    let config = load_config(&state).await.ok().flatten();
+    apply_service_settings(&state, config.as_ref()).await;
+
+    let backend = state.read().await.backend.clone();
+    let windows = match backend.list_windows() {
+        Ok(windows) => windows,
+        Err(e) => {
+            log_error!("Failed to refresh windows: {e}");
+            span.fail(&e.to_string());
+            return;
+        }
+    };
+    span.set("windows", windows.len());
+
+    let focused = backend.focused_workspace();
+    let focused_window = backend.focused_window();
+    let monitors = backend.monitors();
+
+    let windows = WindowIndex::new(windows);
+    let mut state_guard = state.write().await;
+    announce_window_changes(&state_guard.windows, &windows, events);
+    announce_focused_workspace(&mut state_guard, focused, events);
+    state_guard.focused_window = focused_window;
+    let monitors_changed = state_guard.monitors != monitors;
+    state_guard.monitors = monitors;
+    let now = Instant::now();
+    if let Some(listed_at) = state_guard.windows_listed_at {
+        let state_guard = &mut *state_guard;
+        add_placement_time(
+            &mut state_guard.placement_time,
+            &state_guard.windows,
+            now.duration_since(listed_at),
+        );
+    }
+    state_guard.windows_listed_at = Some(now);
+    set_windows(&mut state_guard, windows);
+    set_config(&mut state_guard, config);
+    if monitors_changed {
+        apply_display_profile(&mut state_guard, events);
+    }
+
+    log_info!("State refreshed: {} windows", state_guard.windows.len());
+}
+
+async fn refresh_config_only(state: SharedState, events: &EventSender) {
+    log_info!("Config file changed, reloading...");
+
+    let config = load_config(&state).await;
+    if let Err(e) = &config {
+        log_error!("Failed to load config: {e}");
+    }
+    let config = config.ok().flatten();
+
+    apply_service_settings(&state, config.as_ref()).await;
+
+    let mut state_guard = state.write().await;
+    log_config_problems(config.as_ref());
+    set_config(&mut state_guard, config);
+
+    match &state_guard.config {
+        Some(config) => log_info!("Config reloaded successfully: {} rules", config.rules.len()),
+        None => log_info!("Config file not found or invalid"),
+    }
+    drop(state_guard);
+    announce_config_reload(&state, events).await;
+}
+
+/// Reloads the config whenever the user's or the system-wide config file is created,
+/// changed or removed.
+async fn watch_config_files(
+    user_config_path: Option<PathBuf>,
+    system_config_path: Option<PathBuf>,
+    state: SharedState,
+    events: EventSender,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    // We need to watch the parent directory since the file might not exist initially
+
+    let mut watcher = RecommendedWatcher::new(
+        move |result: Result<NotifyEvent, notify::Error>| match result {
+            Ok(event) => {
+                if let Err(e) = tx.send(event) {
+                    log_error!("Failed to send watch event: {e}");
+                }
+            }
+            Err(e) => log_error!("Watch error: {e}"),
+        },
+        NotifyConfig::default(),
+    )?;
+
+    // Ensure the user's config directory exists, but leave system directories alone
+    if let Some(parent_dir) = user_config_path.as_deref().and_then(Path::parent) {
+        if let Err(e) = std::fs::create_dir_all(parent_dir) {
+            log_error!("Failed to create config directory {parent_dir:?}: {e}");
+        }
+    }
+
+    // Watch the directories containing the config files. One that can't be watched
+    // mustn't keep changes to the other from being seen.
+    let config_paths: Vec<PathBuf> = user_config_path
+        .into_iter()
+        .chain(system_config_path)
+        .collect();
+    let mut watched = HashSet::new();
+    for parent_dir in config_paths.iter().filter_map(|path| path.parent()) {
+        if !watched.insert(parent_dir) {
+            continue;
+        }
+        if let Err(e) = watcher.watch(parent_dir, RecursiveMode::NonRecursive) {
+            log_error!("Failed to watch config directory {parent_dir:?}: {e}");
+            continue;
+        }
+        log_info!("Watching config directory: {parent_dir:?}");
+    }
+
+    // Process filesystem events
+    while let Some(event) = rx.recv().await {
+        // Check if the event is related to one of our config files
+        let relevant_event = event.paths.iter().any(|path| {
+            config_paths.iter().any(|config_path| {
+                path == config_path || path.file_name() == config_path.file_name()
+            })
+        });
+
+        if relevant_event {
+            match event.kind {
+                EventKind::Create(_) | EventKind::Modify(_) => {
+                    log_info!("Config file change detected: {:?}", event.kind);
+                    refresh_config_only(state.clone(), &events).await;
+                }
+                EventKind::Remove(_) => {
+                    // The other config file, if any, still applies
+                    log_info!("Config file removed");
+                    refresh_config_only(state.clone(), &events).await;
+                }
+                _ => {
+                    // Ignore other event types
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Re-evaluates title-based rules whenever the accessibility observer reports a
+/// title change, instead of waiting for the next periodic refresh.
+#[cfg(all(target_os = "macos", feature = "accessibility"))]
+fn spawn_title_watcher(state: SharedState, events: EventSender) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let watcher = crate::accessibility::spawn_title_watcher(move |change| {
+        let _ = tx.send(change);
+    });
+    if watcher.is_none() {
+        log_error!("Accessibility permission missing, title changes are only seen on refresh");
+        return;
+    }
+
+    tokio::spawn(async move {
+        // Windows whose title changed but hasn't yet stayed the same for `title_settle_ms`,
+        // by when it last changed
+        let mut settling: HashMap<u32, tokio::time::Instant> = HashMap::new();
+        loop {
+            let settle = {
+                let state_guard = state.read().await;
+                state_guard
+                    .config
+                    .as_ref()
+                    .map_or(Duration::ZERO, |config| config.title_settle())
+            };
+            let due = settling.values().min().map(|changed| *changed + settle);
+            tokio::select! {
+                change = rx.recv() => {
+                    let Some(change) = change else {
+                        break;
+                    };
+                    if !set_title(&state, change.window_id, change.title).await {
+                        continue;
+                    }
+                    if settle.is_zero() {
+                        evaluate_title_change(&state, &events, change.window_id).await;
+                    } else {
+                        settling.insert(change.window_id, tokio::time::Instant::now());
+                    }
+                }
+                () = tokio::time::sleep_until(due.unwrap_or_else(tokio::time::Instant::now)),
+                    if due.is_some() =>
+                {
+                    let now = tokio::time::Instant::now();
+                    let settled: Vec<u32> = settling
+                        .iter()
+                        .filter(|(_, changed)| now.duration_since(**changed) >= settle)
+                        .map(|(window_id, _)| *window_id)
+                        .collect();
+                    for window_id in settled {
+                        settling.remove(&window_id);
+                        evaluate_title_change(&state, &events, window_id).await;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Stores a title the accessibility observer reported, telling whether it changed.
+#[cfg(all(target_os = "macos", feature = "accessibility"))]
+async fn set_title(state: &SharedState, window_id: u32, title: String) -> bool {
+    let mut state_guard = state.write().await;
+    let Some(window) = Arc::make_mut(&mut state_guard.windows).get_mut(window_id) else {
+        return false;
+    };
+    if window.window_title == title {
+        return false;
+    }
+    window.window_title = title;
+    let window = window.clone();
+    state_guard.windows_json = None;
+    Arc::make_mut(&mut state_guard.tracker).observe_window(&window, chrono::Utc::now());
+    true
+}
+
+/// Evaluates the title-based rules against the window with its current title.
+#[cfg(all(target_os = "macos", feature = "accessibility"))]
+async fn evaluate_title_change(state: &SharedState, events: &EventSender, window_id: u32) {
+    let (mut window, config, index, context, backend) = {
+        let state_guard = state.read().await;
+        let Some(window) = state_guard.windows.get(window_id) else {
+            return;
+        };
+        // Pinned windows still get their new title but no rules
+        if state_guard.paused || state_guard.pinned_windows.contains(&window_id) {
+            return;
+        }
+        (
+            window.clone(),
+            state_guard.config.clone(),
+            state_guard.rule_index.clone(),
+            eval_context(&state_guard, Trigger::TitleChanged, false),
+            state_guard.backend.clone(),
+        )
+    };
+
+    let Some(config) = config else {
+        return;
+    };
+    mark_focused(std::slice::from_mut(&mut window), backend.focused_window());
+    let evaluation = rules::evaluate_rules_for_window(
+        &window,
+        &config,
+        &index,
+        &context,
+        backend.as_ref(),
+        false,
+    )
+    .map_err(|e| e.to_string());
+    match evaluation {
+        Ok(results) if !results.is_empty() => {
+            log_action_results(&format!("Title change of window {window_id}"), &results);
+            record_actions(&mut *state.write().await, events, &results);
+        }
+        Ok(_) => {}
+        Err(e) => log_error!("Rule evaluation failed after title change: {e}"),
+    }
+}
+
+async fn run_startup_rules(state: &SharedState, events: &EventSender) {
+    let (config, index, context, backend, paused) = {
+        let state_guard = state.read().await;
+        (
+            state_guard.config.clone(),
+            state_guard.rule_index.clone(),
+            eval_context(&state_guard, Trigger::Startup, false),
+            state_guard.backend.clone(),
+            state_guard.paused,
+        )
+    };
+    if paused {
+        log_info!("Paused, skipping startup rules");
+        return;
+    }
+    if let Some(config) = config {
+        let results = match rules::run_startup_rules(&config, &index, &context, backend.as_ref()) {
+            Ok(results) => results,
+            Err(e) => {
+                log_error!("Failed to run startup rules: {e}");
+                return;
+            }
+        };
+        if !results.is_empty() {
+            log_action_results("Startup rules", &results);
+            record_actions(&mut *state.write().await, events, &results);
+        }
+    }
+}
+
+/// Compares the window manager's PID with the last one seen and, when it changed,
+/// drops cached state, re-runs startup rules, and notifies subscribers.
+async fn detect_wm_restart(state: &SharedState, events: &EventSender) {
+    let backend = state.read().await.backend.clone();
+    let pid = backend.server_pid();
+    let previous = std::mem::replace(&mut state.write().await.wm_pid, pid);
+    if pid.is_none() || pid == previous {
+        return;
+    }
+
+    log_info!(
+        "Detected {} restart (pid {previous:?} -> {pid:?})",
+        backend.name()
+    );
+    aerospace::invalidate_cache();
+    set_windows(&mut *state.write().await, WindowIndex::default());
+
+    if backend.name() == "aerospace" {
+        let version = aerospace::detect_version().ok();
+        state.write().await.aerospace_version = version;
+    }
+
+    refresh_state(state.clone(), events).await;
+    run_startup_rules(state, events).await;
+
+    // Sending only fails when nobody is subscribed
+    let _ = events.send(Event::WindowManagerRestarted {
+        backend: backend.name().to_string(),
+        pid,
+    });
+}
+
+async fn periodic_refresh(state: SharedState, events: EventSender, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+        detect_wm_restart(&state, &events).await;
+        refresh_state(state.clone(), &events).await;
+
+        let textfile = state
+            .read()
+            .await
+            .config
+            .as_ref()
+            .and_then(|config| config.service.metrics_textfile.clone());
+        if let Some(path) = textfile {
+            write_metrics_textfile(&path);
+        }
+    }
+}
+
+/// Serves [`metrics::render`] at `/metrics` on `port` of localhost.
+async fn serve_metrics(port: u16) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    log_info!("Serving metrics on http://127.0.0.1:{port}/metrics");
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            let mut request = [0; 1024];
+            let Ok(read) = stream.read(&mut request).await else {
+                return;
+            };
+            let response = if request[..read].starts_with(b"GET /metrics ") {
+                let body = metrics::render();
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string()
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+fn write_metrics_textfile(path: &str) {
+    // Renamed into place, so the collector never reads a partly written file
+    let partial = format!("{path}.partial");
+    let written =
+        std::fs::write(&partial, metrics::render()).and_then(|()| std::fs::rename(&partial, path));
+    if let Err(e) = written {
+        log_error!("Failed to write metrics to {path}: {e}");
+    }
+}
+
+/// How to run the service. The default is the installed service: the config from the
+/// default locations, the backend it selects, and the well-known socket and PID paths.
+#[derive(Debug, Clone)]
+pub struct ServiceOptions {
+    /// Config file to load instead of the first one in the default locations.
+    pub config_path: Option<String>,
+    pub socket_path: PathBuf,
+    /// Where to record the PID of this process, if anywhere.
+    pub pid_path: Option<PathBuf>,
+    /// Window manager to drive instead of the one `[service] backend` selects.
+    pub backend: Option<Arc<dyn WindowManager>>,
+    /// How often windows are read from the window manager again.
+    pub refresh_interval: Duration,
+}
+
+impl Default for ServiceOptions {
+    fn default() -> Self {
+        Self {
+            config_path: None,
+            socket_path: PathBuf::from(SOCKET_PATH),
+            pid_path: Some(PathBuf::from(PID_PATH)),
+            backend: None,
+            refresh_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+/// A service listening on its socket, see [`Service::bind`].
+#[derive(Debug)]
+pub struct Service {
+    listener: UnixListener,
+    state: SharedState,
+    events: EventSender,
+    logs: LogBuffer,
+}
+
+impl Service {
+    /// Loads the config, starts watching it and the window manager, and binds the
+    /// socket. Requests are answered once [`Service::run`] is called.
+    pub async fn bind(
+        options: ServiceOptions,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        // Keep recent log lines for `logs` and stream new ones to subscribers
+        let (events, _) = broadcast::channel(64);
+        let logs: LogBuffer = Default::default();
+        let sink_events: EventSender = events.clone();
+        let sink_logs = logs.clone();
+        logging::set_sink(move |line| {
+            let mut logs = sink_logs.lock().unwrap();
+            if logs.len() == LOG_BUFFER_LINES {
+                logs.pop_front();
+            }
+            logs.push_back(line.to_string());
+            // Sending only fails when nobody is subscribed
+            let _ = sink_events.send(Event::Log {
+                message: line.to_string(),
+            });
+        });
+
+        log_info!("Starting aerospace-rules service...");
+
+        let config_path_for_watching = get_config_file_path(options.config_path.as_deref());
+        let system_config_path_for_watching = config::system_config_path();
+
+        let initial_config = config::load_config_from_path(options.config_path.as_deref());
+        let default_service = config::ServiceConfig::default();
+        let backend_fixed = options.backend.is_some();
+        let backend = options.backend.unwrap_or_else(|| {
+            backend::for_config(
+                initial_config
+                    .as_ref()
+                    .map_or(&default_service, |c| &c.service),
+            )
+        });
+
+        // Initialize state
+        let state = Arc::new(RwLock::new(ServiceState {
+            backend,
+            backend_fixed,
+            windows: Default::default(),
+            windows_listed_at: None,
+            window_seq: 0,
+            window_changes: Default::default(),
+            windows_json: None,
+            // Set below
+            config: None,
+            rule_index: Default::default(),
+            config_path: options.config_path,
+            aerospace_version: None,
+            wm_pid: None,
+            rule_hits: Default::default(),
+            rule_firings: Default::default(),
+            evaluated_windows: Default::default(),
+            placement_time: Default::default(),
+            started_at: chrono::Utc::now(),
+            focused_workspace: None,
+            previous_workspace: None,
+            focused_window: None,
+            monitors: Vec::new(),
+            display_profile: None,
+            pinned_windows: Default::default(),
+            history: Default::default(),
+            tracker: Default::default(),
+            paused: false,
+        }));
+        log_config_problems(initial_config.as_ref());
+        set_config(&mut *state.write().await, initial_config.clone());
+        if let Some(path) = initial_config
+            .as_ref()
+            .and_then(|config| config.service.rule_hits_file.as_deref())
+        {
+            state.write().await.rule_hits = read_rule_hits(path);
+        }
+
+        // Resolve the aerospace binary before the first query so version detection uses it
+        apply_service_settings(&state, initial_config.as_ref()).await;
+        if state.read().await.backend.name() == "aerospace" {
+            let aerospace_version = match aerospace::detect_version() {
+                Ok(version) => {
+                    log_info!("Detected aerospace {version}");
+                    Some(version)
+                }
+                Err(e) => {
+                    log_error!("Failed to detect aerospace version: {e}");
+                    None
+                }
+            };
+            state.write().await.aerospace_version = aerospace_version;
+        }
+
+        tokio::spawn(deliver_webhooks(state.clone(), events.subscribe()));
+        if let Some(port) = initial_config
+            .as_ref()
+            .and_then(|config| config.service.metrics_port)
+        {
+            tokio::spawn(async move {
+                if let Err(e) = serve_metrics(port).await {
+                    log_error!("Metrics endpoint failed: {e}");
+                }
+            });
+        }
+
+        // Initial state refresh
+        refresh_state(state.clone(), &events).await;
+        let wm_pid = state.read().await.backend.server_pid();
+        state.write().await.wm_pid = wm_pid;
+        run_startup_rules(&state, &events).await;
+
+        // Start config file watcher if we have a config path to watch
+        if config_path_for_watching.is_some() || system_config_path_for_watching.is_some() {
+            let watcher_state = state.clone();
+            let watcher_events = events.clone();
+            tokio::spawn(async move {
+                if let Err(e) = watch_config_files(
+                    config_path_for_watching,
+                    system_config_path_for_watching,
+                    watcher_state,
+                    watcher_events,
+                )
+                .await
+                {
+                    log_error!("Config file watcher failed: {e}");
+                }
+            });
+        } else {
+            log_info!("No config file path available for watching");
+        }
+
+        #[cfg(all(target_os = "macos", feature = "accessibility"))]
+        spawn_title_watcher(state.clone(), events.clone());
+
+        tokio::spawn(periodic_refresh(
+            state.clone(),
+            events.clone(),
+            options.refresh_interval,
+        ));
+
+        // Remove existing socket file if it exists
+        let _ = std::fs::remove_file(&options.socket_path);
+
+        let listener = UnixListener::bind(&options.socket_path)?;
+        log_info!("Service listening on {}", options.socket_path.display());
+        if let Some(pid_path) = &options.pid_path {
+            if let Err(e) = std::fs::write(pid_path, std::process::id().to_string()) {
+                log_error!("Failed to write {}: {e}", pid_path.display());
+            }
+        }
+
+        Ok(Self {
+            listener,
+            state,
+            events,
+            logs,
+        })
+    }
+
+    /// Answers clients until the process exits.
+    pub async fn run(self) {
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, _)) => {
+                    let state = self.state.clone();
+                    let events = self.events.clone();
+                    let logs = self.logs.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_client(stream, state, events, logs).await {
+                            log_error!("Error handling client: {e}");
+                        }
+                    });
+                }
+                Err(e) => {
+                    log_error!("Error accepting connection: {e}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Saved sessions: the workspace every window was on, captured to put windows back
+//! where they were after a reboot or a window manager crash.
+//!
+//! Window IDs don't survive a reboot, so a saved window is matched to a current one by
+//! its ID and app, then by app and title, and finally by app alone, in listing order.
+
+use crate::backend::WindowManager;
+use crate::rules::ActionResult;
+use crate::WindowInfo;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+    pub saved_at: DateTime<Utc>,
+    pub windows: Vec<SavedWindow>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SavedWindow {
+    pub window_id: u32,
+    pub app_name: String,
+    pub window_title: String,
+    pub workspace: String,
+}
+
+impl Session {
+    pub fn capture(windows: &[WindowInfo]) -> Self {
+        Self {
+            saved_at: Utc::now(),
+            windows: windows
+                .iter()
+                .map(|window| SavedWindow {
+                    window_id: window.window_id,
+                    app_name: window.app_name.clone(),
+                    window_title: window.window_title.clone(),
+                    workspace: window.workspace.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The moves that put `windows` back on their saved workspaces, as (window,
+    /// workspace) pairs. Windows already there or not in the session stay put.
+    pub fn moves<'a>(&'a self, windows: &'a [WindowInfo]) -> Vec<(&'a WindowInfo, &'a str)> {
+        let mut claimed = vec![false; self.windows.len()];
+        let mut assigned: Vec<Option<&SavedWindow>> = vec![None; windows.len()];
+        let passes: [fn(&SavedWindow, &WindowInfo) -> bool; 3] = [
+            |saved, window| {
+                saved.window_id == window.window_id && saved.app_name == window.app_name
+            },
+            |saved, window| {
+                saved.app_name == window.app_name && saved.window_title == window.window_title
+            },
+            |saved, window| saved.app_name == window.app_name,
+        ];
+        for matches in passes {
+            for (window, assignment) in windows.iter().zip(&mut assigned) {
+                if assignment.is_some() {
+                    continue;
+                }
+                let found = self
+                    .windows
+                    .iter()
+                    .enumerate()
+                    .find(|(index, saved)| !claimed[*index] && matches(saved, window));
+                if let Some((index, saved)) = found {
+                    claimed[index] = true;
+                    *assignment = Some(saved);
+                }
+            }
+        }
+
+        windows
+            .iter()
+            .zip(assigned)
+            .filter_map(|(window, saved)| Some((window, saved?.workspace.as_str())))
+            .filter(|(window, workspace)| window.workspace != *workspace)
+            .collect()
+    }
+}
+
+/// Where sessions are saved: `$XDG_STATE_HOME/aerospace-rules/sessions`, or
+/// `~/.local/state/aerospace-rules/sessions`.
+pub fn sessions_dir() -> Option<PathBuf> {
+    let state_dir = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(env::var_os("HOME")?).join(".local/state")))?;
+    Some(state_dir.join("aerospace-rules").join("sessions"))
+}
+
+fn session_path(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("Invalid session name '{name}'").into());
+    }
+    let dir = sessions_dir().ok_or("Neither XDG_STATE_HOME nor HOME is set")?;
+    Ok(dir.join(format!("{name}.json")))
+}
+
+/// Saves `session` as `name`, replacing any session saved under that name before.
+/// Returns the file it was saved to.
+pub fn save(name: &str, session: &Session) -> Result<PathBuf, Box<dyn Error>> {
+    let path = session_path(name)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(session)?)?;
+    Ok(path)
+}
+
+pub fn load(name: &str) -> Result<Session, Box<dyn Error>> {
+    let path = session_path(name)?;
+    let source = fs::read_to_string(&path).map_err(|e| {
+        format!(
+            "Failed to read session '{name}' from {}: {e}",
+            path.display()
+        )
+    })?;
+    Ok(serde_json::from_str(&source)
+        .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?)
+}
+
+/// Moves `windows` back to their workspaces in `session`, saved as `name`, in one
+/// batch. A dry run reports the moves without making them.
+pub fn restore(
+    name: &str,
+    session: &Session,
+    windows: &[WindowInfo],
+    wm: &dyn WindowManager,
+    dry_run: bool,
+) -> Vec<ActionResult> {
+    let label = format!("session {name}");
+    let moves = session.moves(windows);
+    let batch: Vec<(u32, String)> = moves
+        .iter()
+        .map(|(window, workspace)| (window.window_id, workspace.to_string()))
+        .collect();
+    let outcomes = if dry_run || batch.is_empty() {
+        Vec::new()
+    } else {
+        wm.move_windows(&batch)
+    };
+    moves
+        .into_iter()
+        .map(|(window, workspace)| {
+            let result = outcomes
+                .iter()
+                .find(|outcome| outcome.window_id == window.window_id)
+                .map_or(Ok(()), |outcome| outcome.result.clone());
+            ActionResult::for_window(
+                &label,
+                &format!("move-to-workspace {workspace}"),
+                window,
+                result,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{window, Call, FakeWindowManager};
+
+    #[test]
+    fn test_restore_matches_windows_whose_ids_changed() {
+        let session = Session::capture(&[
+            window("Ghostty")
+                .id(1)
+                .title("server")
+                .workspace("3")
+                .build(),
+            window("Ghostty")
+                .id(2)
+                .title("editor")
+                .workspace("2")
+                .build(),
+            window("Slack").id(3).workspace("9").build(),
+            window("Mail").id(4).build(),
+        ]);
+        // After a reboot every window has a new ID
+        let wm = FakeWindowManager::new([
+            window("Ghostty").id(11).title("editor"),
+            window("Ghostty").id(12).title("zsh"),
+            window("Slack").id(13),
+            window("Mail").id(14),
+        ]);
+        let windows = wm.list_windows().unwrap();
+
+        let results = restore("work", &session, &windows, &wm, false);
+        assert!(results.iter().all(|result| result.success));
+        let moved: Vec<(u32, &str)> = session
+            .moves(&windows)
+            .iter()
+            .map(|(window, workspace)| (window.window_id, *workspace))
+            .collect();
+        assert_eq!(moved, [(11, "2"), (12, "3"), (13, "9")]);
+        assert_eq!(
+            wm.calls(),
+            [
+                Call::Move {
+                    window_id: 11,
+                    workspace: "2".to_string()
+                },
+                Call::Move {
+                    window_id: 12,
+                    workspace: "3".to_string()
+                },
+                Call::Move {
+                    window_id: 13,
+                    workspace: "9".to_string()
+                },
+            ]
+        );
+    }
+}
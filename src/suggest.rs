@@ -0,0 +1,109 @@
+//! Candidate rules derived from the current window layout.
+
+use crate::action::Action;
+use crate::config::{Config, Rule, RuleType};
+use crate::rules::{self, PlannedAction, Trigger};
+use crate::WindowInfo;
+use std::collections::BTreeMap;
+
+/// Suggests a move rule for every app whose windows all sit on the same workspace,
+/// unless a rule in `config` already moves that app's windows.
+pub fn suggest_rules(windows: &[WindowInfo], config: Option<&Config>) -> Vec<Rule> {
+    let mut workspaces_by_app: BTreeMap<&str, Vec<&WindowInfo>> = BTreeMap::new();
+    for window in windows {
+        workspaces_by_app
+            .entry(&window.app_name)
+            .or_default()
+            .push(window);
+    }
+
+    workspaces_by_app
+        .into_iter()
+        .filter_map(|(app, windows)| {
+            let workspace = &windows[0].workspace;
+            if windows.iter().any(|window| window.workspace != *workspace) {
+                return None;
+            }
+            if config.is_some_and(|config| windows.iter().any(|window| is_moved(window, config))) {
+                return None;
+            }
+            Some(Rule {
+                name: format!("Move {app}"),
+                enabled: true,
+                tags: Vec::new(),
+                timeout_ms: None,
+                log: Default::default(),
+                trace: false,
+                on_error: Default::default(),
+                follow: false,
+                rule_type: RuleType::Window {
+                    condition: format!("app-name = '{app}'").into(),
+                    action: Action::MoveToWorkspace(workspace.clone()).to_string(),
+                    workspaces: Vec::new(),
+                },
+                compiled: None,
+            })
+        })
+        .collect()
+}
+
+fn is_moved(window: &WindowInfo, config: &Config) -> bool {
+    rules::plan(std::slice::from_ref(window), config, Trigger::Windows).is_ok_and(|planned| {
+        planned.iter().any(|planned| {
+            matches!(
+                planned,
+                PlannedAction::Window { action, .. }
+                    if matches!(Action::parse(action), Ok(Action::MoveToWorkspace(_)))
+            )
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(app_name: &str, window_id: u32, workspace: &str) -> WindowInfo {
+        WindowInfo {
+            app_name: app_name.to_string(),
+            window_id,
+            window_title: String::new(),
+            workspace: workspace.to_string(),
+            monitor: None,
+            frame: None,
+            focused: false,
+        }
+    }
+
+    #[test]
+    fn test_suggests_apps_kept_on_one_workspace() {
+        let windows = [
+            window("Slack", 1, "9"),
+            window("Safari", 2, "1"),
+            window("Safari", 3, "2"),
+            window("IntelliJ IDEA", 4, "5"),
+        ];
+        let config: Config = toml::from_str(
+            r#"
+[[rules]]
+name = "Move IntelliJ"
+type = "window"
+condition = "app-name = 'IntelliJ IDEA'"
+action = "move-to-workspace 5"
+"#,
+        )
+        .unwrap();
+
+        let suggestions = suggest_rules(&windows, Some(&config));
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].name, "Move Slack");
+        let RuleType::Window {
+            condition, action, ..
+        } = &suggestions[0].rule_type
+        else {
+            panic!("Expected Window rule type");
+        };
+        assert_eq!(condition, "app-name = 'Slack'");
+        assert_eq!(action, "move-to-workspace 9");
+    }
+}
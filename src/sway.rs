@@ -0,0 +1,309 @@
+use crate::backend::{MoveResult, WindowManager};
+use crate::{WindowFrame, WindowInfo};
+use serde_json::Value;
+use std::error::Error;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::Command;
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const GET_TREE: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    Sway,
+    I3,
+}
+
+impl Flavor {
+    fn binary(self) -> &'static str {
+        match self {
+            Flavor::Sway => "sway",
+            Flavor::I3 => "i3",
+        }
+    }
+
+    fn socket_env_var(self) -> &'static str {
+        match self {
+            Flavor::Sway => "SWAYSOCK",
+            Flavor::I3 => "I3SOCK",
+        }
+    }
+}
+
+/// Drives sway or i3 through their shared IPC protocol.
+#[derive(Debug)]
+pub struct Sway {
+    flavor: Flavor,
+}
+
+impl Sway {
+    pub fn new(flavor: Flavor) -> Self {
+        Self { flavor }
+    }
+
+    fn socket_path(&self) -> Result<PathBuf, Box<dyn Error>> {
+        if let Some(path) = std::env::var_os(self.flavor.socket_env_var()) {
+            return Ok(PathBuf::from(path));
+        }
+
+        let output = Command::new(self.flavor.binary())
+            .arg("--get-socketpath")
+            .output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} --get-socketpath failed: {}",
+                self.flavor.binary(),
+                String::from_utf8_lossy(&output.stderr)
+            )
+            .into());
+        }
+
+        Ok(PathBuf::from(String::from_utf8(output.stdout)?.trim()))
+    }
+
+    fn request(&self, message_type: u32, payload: &str) -> Result<Value, Box<dyn Error>> {
+        let mut stream = UnixStream::connect(self.socket_path()?)?;
+
+        let mut message = Vec::with_capacity(14 + payload.len());
+        message.extend_from_slice(MAGIC);
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&message_type.to_ne_bytes());
+        message.extend_from_slice(payload.as_bytes());
+        stream.write_all(&message)?;
+
+        let mut header = [0u8; 14];
+        stream.read_exact(&mut header)?;
+        if &header[..6] != MAGIC {
+            return Err("Invalid IPC reply header".into());
+        }
+        let length = u32::from_ne_bytes(header[6..10].try_into()?) as usize;
+
+        let mut body = vec![0u8; length];
+        stream.read_exact(&mut body)?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Runs commands in one IPC round trip and returns each command's outcome.
+    fn run_commands(&self, commands: &[String]) -> Result<Vec<Result<(), String>>, Box<dyn Error>> {
+        let reply = self.request(RUN_COMMAND, &commands.join("; "))?;
+        let outcomes = reply
+            .as_array()
+            .ok_or("Unexpected RUN_COMMAND reply")?
+            .iter()
+            .map(|outcome| {
+                if outcome["success"].as_bool().unwrap_or(false) {
+                    Ok(())
+                } else {
+                    Err(outcome["error"]
+                        .as_str()
+                        .unwrap_or("unknown error")
+                        .to_string())
+                }
+            })
+            .collect();
+        Ok(outcomes)
+    }
+}
+
+impl WindowManager for Sway {
+    fn name(&self) -> &'static str {
+        self.flavor.binary()
+    }
+
+    fn list_windows(&self) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
+        let tree = self.request(GET_TREE, "")?;
+        let mut windows = Vec::new();
+        collect_windows(&tree, None, None, &mut windows);
+        Ok(windows)
+    }
+
+    fn list_windows_in_workspace(
+        &self,
+        workspace: &str,
+    ) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
+        Ok(self
+            .list_windows()?
+            .into_iter()
+            .filter(|window| window.workspace == workspace)
+            .collect())
+    }
+
+    fn move_window(&self, window_id: u32, workspace: &str) -> Result<(), Box<dyn Error>> {
+        self.move_windows(&[(window_id, workspace.to_string())])
+            .remove(0)
+            .result
+            .map_err(|e| e.into())
+    }
+
+    fn move_windows(&self, moves: &[(u32, String)]) -> Vec<MoveResult> {
+        let commands: Vec<String> = moves
+            .iter()
+            .map(|(window_id, workspace)| {
+                format!(
+                    "[con_id={window_id}] move container to workspace {}",
+                    quote(workspace)
+                )
+            })
+            .collect();
+
+        let outcomes = self
+            .run_commands(&commands)
+            .unwrap_or_else(|e| vec![Err(e.to_string()); moves.len()]);
+
+        moves
+            .iter()
+            .zip(
+                outcomes
+                    .into_iter()
+                    .chain(std::iter::repeat(Err("No reply for command".to_string()))),
+            )
+            .map(|((window_id, workspace), result)| MoveResult {
+                window_id: *window_id,
+                workspace: workspace.clone(),
+                result,
+            })
+            .collect()
+    }
+
+    fn fullscreen_window(&self, window_id: u32) -> Result<(), Box<dyn Error>> {
+        self.run_commands(&[format!("[con_id={window_id}] fullscreen enable")])?
+            .remove(0)
+            .map_err(|e| e.into())
+    }
+
+    fn focus_workspace(&self, workspace: &str) -> Result<(), Box<dyn Error>> {
+        self.run_commands(&[format!("workspace {}", quote(workspace))])?
+            .remove(0)
+            .map_err(|e| e.into())
+    }
+
+    fn focused_window(&self) -> Option<u32> {
+        let windows = self.list_windows().ok()?;
+        windows
+            .iter()
+            .find(|window| window.focused)
+            .map(|window| window.window_id)
+    }
+
+    fn focused_workspace(&self) -> Option<String> {
+        let workspaces = self.request(GET_WORKSPACES, "").ok()?;
+        workspaces
+            .as_array()?
+            .iter()
+            .find(|workspace| workspace["focused"].as_bool().unwrap_or(false))?["name"]
+            .as_str()
+            .map(str::to_string)
+    }
+
+    fn server_pid(&self) -> Option<u32> {
+        let output = Command::new("pgrep")
+            .args(["-x", self.flavor.binary()])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .next()?
+            .trim()
+            .parse()
+            .ok()
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Walks a GET_TREE reply collecting leaf containers that hold an application window.
+fn collect_windows(
+    node: &Value,
+    output: Option<&str>,
+    workspace: Option<&str>,
+    windows: &mut Vec<WindowInfo>,
+) {
+    let output = if node["type"] == "output" {
+        node["name"].as_str()
+    } else {
+        output
+    };
+    let workspace = if node["type"] == "workspace" {
+        node["name"].as_str()
+    } else {
+        workspace
+    };
+
+    let children: Vec<&Value> = ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[*key].as_array())
+        .flatten()
+        .collect();
+
+    let is_window = !node["pid"].is_null() || !node["window"].is_null();
+    if children.is_empty() && is_window {
+        if let (Some(workspace), Some(window_id)) = (workspace, node["id"].as_u64()) {
+            let app_name = node["app_id"]
+                .as_str()
+                .or_else(|| node["window_properties"]["class"].as_str())
+                .unwrap_or_default();
+            let rect = &node["rect"];
+
+            windows.push(WindowInfo {
+                app_name: app_name.to_string(),
+                window_id: window_id as u32,
+                window_title: node["name"].as_str().unwrap_or_default().to_string(),
+                workspace: workspace.to_string(),
+                monitor: output.map(str::to_string),
+                frame: Some(WindowFrame {
+                    x: rect["x"].as_f64().unwrap_or_default(),
+                    y: rect["y"].as_f64().unwrap_or_default(),
+                    width: rect["width"].as_f64().unwrap_or_default(),
+                    height: rect["height"].as_f64().unwrap_or_default(),
+                }),
+                focused: node["focused"].as_bool().unwrap_or(false),
+            });
+        }
+    }
+
+    for child in children {
+        collect_windows(child, output, workspace, windows);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_windows_from_tree() {
+        let tree: Value = serde_json::from_str(
+            r#"{
+                "type": "root", "name": "root", "nodes": [{
+                    "type": "output", "name": "eDP-1", "nodes": [{
+                        "type": "workspace", "name": "2", "floating_nodes": [], "nodes": [
+                            {"type": "con", "id": 7, "name": "vim", "pid": 10, "app_id": "foot",
+                             "rect": {"x": 0, "y": 0, "width": 960, "height": 1080}, "nodes": []},
+                            {"type": "con", "id": 8, "name": "Inbox", "window": 4194307,
+                             "app_id": null, "window_properties": {"class": "Thunderbird"},
+                             "rect": {"x": 960, "y": 0, "width": 960, "height": 1080}, "nodes": []}
+                        ]
+                    }]
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let mut windows = Vec::new();
+        collect_windows(&tree, None, None, &mut windows);
+
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].app_name, "foot");
+        assert_eq!(windows[0].workspace, "2");
+        assert_eq!(windows[0].monitor.as_deref(), Some("eDP-1"));
+        assert_eq!(windows[1].app_name, "Thunderbird");
+        assert_eq!(windows[1].window_id, 8);
+        assert_eq!(windows[1].frame.unwrap().x, 960.0);
+    }
+}
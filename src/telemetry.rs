@@ -0,0 +1,148 @@
+//! OpenTelemetry spans for refreshes, evaluations and the individual actions they
+//! perform, exported over OTLP/HTTP when built with the `otel` feature. Without it,
+//! [`Span`] does nothing.
+//!
+//! The exporter is configured through the standard `OTEL_EXPORTER_OTLP_*` environment
+//! variables and by default sends to a collector at `http://localhost:4318`.
+//!
+//! | Span         | Attributes                                                      |
+//! |--------------|-----------------------------------------------------------------|
+//! | `refresh`    | `windows`                                                       |
+//! | `evaluation` | `trigger`, `workspace`, `window.id`, `dry_run`, `actions`       |
+//! | `action`     | `rule`, `action`, `window.id`, `window.app`, `window.workspace` |
+//!
+//! Action spans are children of the evaluation that planned them. Moves are batched,
+//! so the span of a move lasts until the whole batch is done.
+
+/// A value of a span attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attribute {
+    String(String),
+    Int(i64),
+    Bool(bool),
+}
+
+impl From<&str> for Attribute {
+    fn from(value: &str) -> Self {
+        Attribute::String(value.to_string())
+    }
+}
+
+impl From<String> for Attribute {
+    fn from(value: String) -> Self {
+        Attribute::String(value)
+    }
+}
+
+impl From<u32> for Attribute {
+    fn from(value: u32) -> Self {
+        Attribute::Int(value.into())
+    }
+}
+
+impl From<usize> for Attribute {
+    fn from(value: usize) -> Self {
+        Attribute::Int(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+}
+
+impl From<bool> for Attribute {
+    fn from(value: bool) -> Self {
+        Attribute::Bool(value)
+    }
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use super::Attribute;
+    use opentelemetry::trace::{Status, TraceContextExt, Tracer};
+    use opentelemetry::{global, Context, ContextGuard, KeyValue};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use opentelemetry_sdk::Resource;
+    use std::error::Error;
+
+    /// Installs the OTLP exporter as the global tracer provider.
+    pub fn init() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .build()?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(env!("CARGO_PKG_NAME"))
+                    .build(),
+            )
+            .build();
+        global::set_tracer_provider(provider);
+        Ok(())
+    }
+
+    /// A started span, ended when dropped.
+    #[derive(Debug)]
+    pub struct Span(Context);
+
+    /// Keeps a span the parent of spans started on this thread, see [`Span::enter`].
+    #[derive(Debug)]
+    pub struct Entered(#[allow(dead_code)] ContextGuard);
+
+    impl Span {
+        /// Starts a span named `name`, as a child of the entered span if there is one.
+        pub fn start(name: &'static str) -> Self {
+            let span = global::tracer(env!("CARGO_PKG_NAME")).start(name);
+            Self(Context::current_with_span(span))
+        }
+
+        pub fn set(&self, key: &'static str, value: impl Into<Attribute>) {
+            let value = match value.into() {
+                Attribute::String(value) => KeyValue::new(key, value),
+                Attribute::Int(value) => KeyValue::new(key, value),
+                Attribute::Bool(value) => KeyValue::new(key, value),
+            };
+            self.0.span().set_attribute(value);
+        }
+
+        /// Marks the span as failed with `error`.
+        pub fn fail(&self, error: &str) {
+            self.0.span().set_status(Status::error(error.to_string()));
+        }
+
+        /// Makes this span the parent of spans started on this thread until the
+        /// returned guard is dropped.
+        pub fn enter(&self) -> Entered {
+            Entered(self.0.clone().attach())
+        }
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+mod otel {
+    use super::Attribute;
+    use std::error::Error;
+
+    pub fn init() -> Result<(), Box<dyn Error + Send + Sync>> {
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    pub struct Span;
+
+    #[derive(Debug)]
+    pub struct Entered;
+
+    impl Span {
+        pub fn start(_name: &'static str) -> Self {
+            Self
+        }
+
+        pub fn set(&self, _key: &'static str, _value: impl Into<Attribute>) {}
+
+        pub fn fail(&self, _error: &str) {}
+
+        pub fn enter(&self) -> Entered {
+            Entered
+        }
+    }
+}
+
+pub use otel::{init, Entered, Span};
@@ -0,0 +1,338 @@
+//! Helpers for testing rulesets without a window manager: a scriptable fake backend,
+//! a builder for windows, and assertions on planned actions.
+//!
+//! ```
+//! use aerospace_rules::rules::{self, Trigger};
+//! use aerospace_rules::testing::{self, window};
+//!
+//! let config = toml::from_str(r#"
+//! [[rules]]
+//! name = "Chat"
+//! type = "window"
+//! condition = "app-name = 'Slack'"
+//! action = "move-to-workspace 9"
+//! "#).unwrap();
+//! let windows = [window("Slack").id(1).build(), window("Safari").id(2).build()];
+//!
+//! let planned = rules::plan(&windows, &config, Trigger::Workspace("1")).unwrap();
+//! testing::assert_moved(&planned, 1, "9");
+//! testing::assert_not_planned(&planned, 2);
+//! ```
+
+use crate::action::Action;
+use crate::backend::WindowManager;
+use crate::rules::PlannedAction;
+use crate::{WindowFrame, WindowInfo};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Mutex;
+
+/// Starts building a window of `app_name`, by default with ID 1 on workspace 1.
+pub fn window(app_name: &str) -> WindowBuilder {
+    WindowBuilder(WindowInfo {
+        app_name: app_name.to_string(),
+        window_id: 1,
+        window_title: String::new(),
+        workspace: "1".to_string(),
+        monitor: None,
+        frame: None,
+        focused: false,
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowBuilder(WindowInfo);
+
+impl WindowBuilder {
+    pub fn id(mut self, window_id: u32) -> Self {
+        self.0.window_id = window_id;
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.0.window_title = title.to_string();
+        self
+    }
+
+    pub fn workspace(mut self, workspace: &str) -> Self {
+        self.0.workspace = workspace.to_string();
+        self
+    }
+
+    pub fn monitor(mut self, monitor: &str) -> Self {
+        self.0.monitor = Some(monitor.to_string());
+        self
+    }
+
+    pub fn size(mut self, width: f64, height: f64) -> Self {
+        self.0.frame = Some(WindowFrame {
+            x: 0.0,
+            y: 0.0,
+            width,
+            height,
+        });
+        self
+    }
+
+    pub fn focused(mut self) -> Self {
+        self.0.focused = true;
+        self
+    }
+
+    pub fn build(self) -> WindowInfo {
+        self.0
+    }
+}
+
+impl From<WindowBuilder> for WindowInfo {
+    fn from(builder: WindowBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// A window manager operation recorded by [`FakeWindowManager`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    Move { window_id: u32, workspace: String },
+    Fullscreen { window_id: u32 },
+    Layout { window_id: u32, layout: String },
+    Launch { app_name: String },
+    WorkspaceToMonitor { workspace: String, monitor: String },
+    FocusWorkspace { workspace: String },
+}
+
+/// An in-memory window manager. Moves change the workspace of its windows, every
+/// operation is recorded, and operations on chosen windows can be made to fail.
+#[derive(Debug, Default)]
+pub struct FakeWindowManager {
+    windows: Mutex<Vec<WindowInfo>>,
+    calls: Mutex<Vec<Call>>,
+    failures: Mutex<HashMap<u32, String>>,
+    focused_workspace: Mutex<Option<String>>,
+    monitors: Mutex<Vec<String>>,
+    workspaces: Mutex<Vec<String>>,
+    server_pid: Mutex<Option<u32>>,
+}
+
+impl FakeWindowManager {
+    pub fn new(windows: impl IntoIterator<Item = impl Into<WindowInfo>>) -> Self {
+        Self {
+            windows: Mutex::new(windows.into_iter().map(Into::into).collect()),
+            ..Self::default()
+        }
+    }
+
+    /// Makes every operation on `window_id` fail with `error`.
+    pub fn fail_window(&self, window_id: u32, error: &str) {
+        self.failures
+            .lock()
+            .unwrap()
+            .insert(window_id, error.to_string());
+    }
+
+    pub fn set_windows(&self, windows: impl IntoIterator<Item = impl Into<WindowInfo>>) {
+        *self.windows.lock().unwrap() = windows.into_iter().map(Into::into).collect();
+    }
+
+    pub fn set_monitors(&self, monitors: &[&str]) {
+        *self.monitors.lock().unwrap() = monitors.iter().map(|name| name.to_string()).collect();
+    }
+
+    pub fn set_workspaces(&self, workspaces: &[&str]) {
+        *self.workspaces.lock().unwrap() = workspaces.iter().map(|name| name.to_string()).collect();
+    }
+
+    pub fn set_focused_workspace(&self, workspace: Option<&str>) {
+        *self.focused_workspace.lock().unwrap() = workspace.map(str::to_string);
+    }
+
+    /// Changes the reported PID, which the service takes as a window manager restart.
+    pub fn set_server_pid(&self, pid: Option<u32>) {
+        *self.server_pid.lock().unwrap() = pid;
+    }
+
+    /// The current windows, reflecting the moves made so far.
+    pub fn windows(&self) -> Vec<WindowInfo> {
+        self.windows.lock().unwrap().clone()
+    }
+
+    /// Every operation performed so far, oldest first.
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn check(&self, window_id: u32) -> Result<(), Box<dyn Error>> {
+        if let Some(error) = self.failures.lock().unwrap().get(&window_id) {
+            return Err(error.clone().into());
+        }
+        if !self
+            .windows
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|window| window.window_id == window_id)
+        {
+            return Err(format!("No window with ID {window_id}").into());
+        }
+        Ok(())
+    }
+}
+
+impl WindowManager for FakeWindowManager {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn list_windows(&self) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
+        Ok(self.windows())
+    }
+
+    fn list_windows_in_workspace(
+        &self,
+        workspace: &str,
+    ) -> Result<Vec<WindowInfo>, Box<dyn Error>> {
+        Ok(self
+            .windows()
+            .into_iter()
+            .filter(|window| window.workspace == workspace)
+            .collect())
+    }
+
+    fn move_window(&self, window_id: u32, workspace: &str) -> Result<(), Box<dyn Error>> {
+        self.calls.lock().unwrap().push(Call::Move {
+            window_id,
+            workspace: workspace.to_string(),
+        });
+        self.check(window_id)?;
+        for window in self.windows.lock().unwrap().iter_mut() {
+            if window.window_id == window_id {
+                window.workspace = workspace.to_string();
+            }
+        }
+        Ok(())
+    }
+
+    fn fullscreen_window(&self, window_id: u32) -> Result<(), Box<dyn Error>> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(Call::Fullscreen { window_id });
+        self.check(window_id)
+    }
+
+    fn set_layout(&self, window_id: u32, layout: &str) -> Result<(), Box<dyn Error>> {
+        self.calls.lock().unwrap().push(Call::Layout {
+            window_id,
+            layout: layout.to_string(),
+        });
+        self.check(window_id)
+    }
+
+    /// Opens a window of the app on the focused workspace, or on workspace 1.
+    fn launch_app(&self, app_name: &str) -> Result<(), Box<dyn Error>> {
+        self.calls.lock().unwrap().push(Call::Launch {
+            app_name: app_name.to_string(),
+        });
+        let workspace = self.focused_workspace().unwrap_or_else(|| "1".to_string());
+        let mut windows = self.windows.lock().unwrap();
+        let window_id = windows
+            .iter()
+            .map(|window| window.window_id)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        windows.push(window(app_name).id(window_id).workspace(&workspace).build());
+        Ok(())
+    }
+
+    fn focused_window(&self) -> Option<u32> {
+        self.windows()
+            .iter()
+            .find(|window| window.focused)
+            .map(|window| window.window_id)
+    }
+
+    fn focused_workspace(&self) -> Option<String> {
+        self.focused_workspace.lock().unwrap().clone()
+    }
+
+    fn monitors(&self) -> Vec<String> {
+        self.monitors.lock().unwrap().clone()
+    }
+
+    fn workspaces(&self) -> Vec<String> {
+        self.workspaces.lock().unwrap().clone()
+    }
+
+    fn move_workspace_to_monitor(
+        &self,
+        workspace: &str,
+        monitor: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.calls.lock().unwrap().push(Call::WorkspaceToMonitor {
+            workspace: workspace.to_string(),
+            monitor: monitor.to_string(),
+        });
+        Ok(())
+    }
+
+    fn focus_workspace(&self, workspace: &str) -> Result<(), Box<dyn Error>> {
+        self.calls.lock().unwrap().push(Call::FocusWorkspace {
+            workspace: workspace.to_string(),
+        });
+        self.set_focused_workspace(Some(workspace));
+        Ok(())
+    }
+
+    fn server_pid(&self) -> Option<u32> {
+        *self.server_pid.lock().unwrap()
+    }
+}
+
+/// The window moves in `planned` as `(window_id, workspace)`, in order.
+pub fn moves(planned: &[PlannedAction]) -> Vec<(u32, String)> {
+    planned
+        .iter()
+        .filter_map(|planned| match planned {
+            PlannedAction::Window { action, window, .. } => match Action::parse(action) {
+                Ok(Action::MoveToWorkspace(workspace)) => Some((window.window_id, workspace)),
+                _ => None,
+            },
+            PlannedAction::Command { .. } => None,
+        })
+        .collect()
+}
+
+/// Asserts that `planned` moves `window_id` to `workspace`.
+#[track_caller]
+pub fn assert_moved(planned: &[PlannedAction], window_id: u32, workspace: &str) {
+    let moves = moves(planned);
+    assert!(
+        moves
+            .iter()
+            .any(|(id, target)| *id == window_id && target == workspace),
+        "expected window {window_id} to move to workspace {workspace}, planned moves: {moves:?}"
+    );
+}
+
+/// Asserts that no action in `planned` targets `window_id`.
+#[track_caller]
+pub fn assert_not_planned(planned: &[PlannedAction], window_id: u32) {
+    let targeting: Vec<_> = planned
+        .iter()
+        .filter(|planned| {
+            matches!(planned, PlannedAction::Window { window, .. } if window.window_id == window_id)
+        })
+        .collect();
+    assert!(
+        targeting.is_empty(),
+        "expected no action for window {window_id}, planned: {targeting:?}"
+    );
+}
+
+/// Asserts that `planned` consists of actions of exactly the rules in `rules`, in order.
+#[track_caller]
+pub fn assert_rules(planned: &[PlannedAction], rules: &[&str]) {
+    let planned: Vec<_> = planned.iter().map(PlannedAction::rule).collect();
+    assert_eq!(planned, rules, "planned actions came from other rules");
+}
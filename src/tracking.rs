@@ -0,0 +1,156 @@
+//! What the service has seen of each window since it opened, kept by window ID so a
+//! window stays the same window across title changes and moves.
+
+use crate::WindowInfo;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Titles kept per window; older ones are dropped first.
+const MAX_TITLES: usize = 20;
+/// Records of closed windows kept for explaining them; the longest closed go first.
+const MAX_CLOSED: usize = 200;
+
+/// The lifecycle of one window.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WindowRecord {
+    pub window_id: u32,
+    pub app_name: String,
+    pub first_seen: DateTime<Utc>,
+    /// When the window was first missing from a listing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<DateTime<Utc>>,
+    /// Titles the window had, oldest first, ending with the current one.
+    pub titles: Vec<String>,
+    /// Workspaces the window was on, in the order it went there.
+    pub workspaces: Vec<String>,
+    /// Rules that acted on the window.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub matched_rules: BTreeSet<String>,
+}
+
+impl WindowRecord {
+    fn new(window: &WindowInfo, now: DateTime<Utc>) -> Self {
+        Self {
+            window_id: window.window_id,
+            app_name: window.app_name.clone(),
+            first_seen: now,
+            closed_at: None,
+            titles: vec![window.window_title.clone()],
+            workspaces: vec![window.workspace.clone()],
+            matched_rules: BTreeSet::new(),
+        }
+    }
+
+    fn update(&mut self, window: &WindowInfo) {
+        if self.titles.last() != Some(&window.window_title) {
+            if self.titles.len() == MAX_TITLES {
+                self.titles.remove(0);
+            }
+            self.titles.push(window.window_title.clone());
+        }
+        if self.workspaces.last() != Some(&window.workspace) {
+            self.workspaces.push(window.workspace.clone());
+        }
+    }
+}
+
+/// Records of the open windows and the most recently closed ones.
+#[derive(Debug, Clone, Default)]
+pub struct WindowTracker {
+    records: HashMap<u32, WindowRecord>,
+}
+
+impl WindowTracker {
+    /// Notes the state of `windows`, a full listing: windows missing from it are
+    /// closed. A window whose ID comes back with another app is a new window.
+    pub fn observe<'a>(
+        &mut self,
+        windows: impl IntoIterator<Item = &'a WindowInfo>,
+        now: DateTime<Utc>,
+    ) {
+        let mut seen = BTreeSet::new();
+        for window in windows {
+            seen.insert(window.window_id);
+            self.observe_window(window, now);
+        }
+        for record in self.records.values_mut() {
+            if record.closed_at.is_none() && !seen.contains(&record.window_id) {
+                record.closed_at = Some(now);
+            }
+        }
+
+        let mut closed: Vec<_> = self
+            .records
+            .values()
+            .filter_map(|record| Some((record.closed_at?, record.window_id)))
+            .collect();
+        if closed.len() > MAX_CLOSED {
+            closed.sort_unstable();
+            for (_, window_id) in &closed[..closed.len() - MAX_CLOSED] {
+                self.records.remove(window_id);
+            }
+        }
+    }
+
+    /// Notes the current state of a single open window, e.g. after its title changed.
+    pub fn observe_window(&mut self, window: &WindowInfo, now: DateTime<Utc>) {
+        match self.records.get_mut(&window.window_id) {
+            Some(record) if record.closed_at.is_none() && record.app_name == window.app_name => {
+                record.update(window)
+            }
+            _ => {
+                self.records
+                    .insert(window.window_id, WindowRecord::new(window, now));
+            }
+        }
+    }
+
+    /// Notes that `rule` acted on the window.
+    pub fn record_match(&mut self, window_id: u32, rule: &str) {
+        if let Some(record) = self.records.get_mut(&window_id) {
+            record.matched_rules.insert(rule.to_string());
+        }
+    }
+
+    pub fn get(&self, window_id: u32) -> Option<&WindowRecord> {
+        self.records.get(&window_id)
+    }
+
+    /// Whether `rule` acted on the window before.
+    pub fn has_matched(&self, window_id: u32, rule: &str) -> bool {
+        self.get(window_id)
+            .is_some_and(|record| record.matched_rules.contains(rule))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::window;
+
+    #[test]
+    fn test_windows_keep_their_record_across_changes() {
+        let now = Utc::now();
+        let mut tracker = WindowTracker::default();
+        tracker.observe(&[window("Safari").id(1).title("Start").build()], now);
+        tracker.record_match(1, "Browsers");
+        let later = now + chrono::Duration::seconds(5);
+        tracker.observe(
+            &[window("Safari").id(1).title("Docs").workspace("2").build()],
+            later,
+        );
+
+        let record = tracker.get(1).unwrap();
+        assert_eq!(record.first_seen, now);
+        assert_eq!(record.titles, ["Start", "Docs"]);
+        assert_eq!(record.workspaces, ["1", "2"]);
+        assert!(tracker.has_matched(1, "Browsers"));
+
+        tracker.observe(&[], later);
+        assert_eq!(tracker.get(1).unwrap().closed_at, Some(later));
+        // The ID was reused by a new window
+        tracker.observe(&[window("Mail").id(1).build()], later);
+        assert!(!tracker.has_matched(1, "Browsers"));
+    }
+}
@@ -0,0 +1,458 @@
+//! Static checks of a rules file, reported with the line and field they concern.
+
+use crate::action::Action;
+use crate::compat;
+use crate::config::{Config, RuleType};
+use crate::rules::expand_home;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+use toml_edit::ImDocument;
+
+/// Words aerospace's `layout` command accepts.
+const LAYOUTS: [&str; 10] = [
+    "tiles",
+    "accordion",
+    "horizontal",
+    "vertical",
+    "tiling",
+    "floating",
+    "h_tiles",
+    "v_tiles",
+    "h_accordion",
+    "v_accordion",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// 1-based line in the config file, when known.
+    pub line: Option<usize>,
+    /// Name of the rule the problem is in.
+    pub rule: Option<String>,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// The problem and the rule and field it is in, without the severity.
+    pub fn describe(&self) -> String {
+        let mut description = String::new();
+        if let Some(rule) = &self.rule {
+            description.push_str(&format!("rule '{rule}': "));
+        }
+        if let Some(field) = &self.field {
+            description.push_str(&format!("{field}: "));
+        }
+        description.push_str(&self.message);
+        description
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => f.write_str("error: ")?,
+            Severity::Warning => f.write_str("warning: ")?,
+        }
+        f.write_str(&self.describe())
+    }
+}
+
+/// Checks that `source` parses as a config and that every rule's condition, action,
+/// workspace and command is usable. Returns no diagnostics for a valid config.
+pub fn validate_config(source: &str) -> Vec<Diagnostic> {
+    validate_config_with_workspaces(source, &[])
+}
+
+/// Like [`validate_config`], but also reports rules that target a workspace not in
+/// `workspaces`, the ones the window manager has configured. An empty list skips that
+/// check, as when the window manager can't be asked.
+pub fn validate_config_with_workspaces(source: &str, workspaces: &[String]) -> Vec<Diagnostic> {
+    let config: Config = match toml::from_str(source) {
+        Ok(config) => config,
+        Err(e) => {
+            return vec![Diagnostic {
+                severity: Severity::Error,
+                line: e.span().map(|span| line_of(source, span.start)),
+                rule: None,
+                field: None,
+                message: e.message().to_string(),
+            }]
+        }
+    };
+    // Only used for locations, so a document that serde accepted is assumed to parse
+    let document = ImDocument::parse(source).ok();
+    let rule_tables = document
+        .as_ref()
+        .and_then(|document| document.get("rules"))
+        .and_then(|rules| rules.as_array_of_tables());
+
+    let mut diagnostics = Vec::new();
+    let mut names = HashSet::new();
+    let unknown_severity = config
+        .service
+        .unknown_workspaces
+        .unwrap_or(Severity::Warning);
+    let check_known = |workspace: &str| {
+        if workspaces.is_empty() || workspaces.iter().any(|known| known == workspace) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Workspace '{workspace}' is not configured in the window manager"
+            ))
+        }
+    };
+
+    let hooks = [
+        ("before_action", &config.hooks.before_action),
+        ("after_action", &config.hooks.after_action),
+    ];
+    for (field, command) in hooks {
+        let Some(command) = command else { continue };
+        if let Err(message) = check_command(command).and_then(|()| config.security.check(command)) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                line: document
+                    .as_ref()
+                    .and_then(|document| document.get("hooks"))
+                    .and_then(|hooks| hooks.get(field))
+                    .and_then(|item| item.span())
+                    .map(|span| line_of(source, span.start)),
+                rule: None,
+                field: Some(format!("hooks.{field}")),
+                message,
+            });
+        }
+    }
+
+    for (index, webhook) in config.webhooks.iter().enumerate() {
+        if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                line: document
+                    .as_ref()
+                    .and_then(|document| document.get("webhooks"))
+                    .and_then(|webhooks| webhooks.as_array_of_tables())
+                    .and_then(|tables| tables.get(index))
+                    .and_then(|table| table.get("url"))
+                    .and_then(|item| item.span())
+                    .map(|span| line_of(source, span.start)),
+                rule: None,
+                field: Some("webhooks.url".to_string()),
+                message: format!("Webhook URL '{}' is not an http(s) URL", webhook.url),
+            });
+        }
+    }
+
+    for (name, workspace) in &config.workspaces {
+        let Some(layout) = &workspace.layout else {
+            continue;
+        };
+        if let Some(unknown) = layout
+            .split_whitespace()
+            .find(|word| !LAYOUTS.contains(word))
+        {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                line: document
+                    .as_ref()
+                    .and_then(|document| document.get("workspaces"))
+                    .and_then(|workspaces| workspaces.get(name))
+                    .and_then(|table| table.get("layout"))
+                    .and_then(|item| item.span())
+                    .map(|span| line_of(source, span.start)),
+                rule: None,
+                field: Some(format!("workspaces.{name}.layout")),
+                message: format!("Unknown layout '{unknown}'"),
+            });
+        }
+    }
+
+    for (index, rule) in config.rules.iter().enumerate() {
+        let table = rule_tables.and_then(|tables| tables.get(index));
+        let field_line = |field: &str| {
+            table
+                .and_then(|table| table.get(field))
+                .and_then(|item| item.span())
+                .or_else(|| table.and_then(|table| table.span()))
+                .map(|span: Range<usize>| line_of(source, span.start))
+        };
+        let mut report = |severity, field: &str, message: String| {
+            diagnostics.push(Diagnostic {
+                severity,
+                line: field_line(field),
+                rule: Some(rule.name.clone()),
+                field: Some(field.to_string()),
+                message,
+            })
+        };
+
+        if rule.name.trim().is_empty() {
+            report(Severity::Error, "name", "Rule name is empty".to_string());
+        } else if !names.insert(rule.name.as_str()) {
+            report(
+                Severity::Error,
+                "name",
+                "Another rule has the same name".to_string(),
+            );
+        }
+
+        match &rule.rule_type {
+            RuleType::Window {
+                condition,
+                action,
+                workspaces,
+            } => {
+                if let Err(e) = condition.to_condition() {
+                    report(Severity::Error, "condition", e.to_string());
+                }
+                for workspace in workspaces {
+                    if let Err(message) = check_workspace(workspace) {
+                        report(Severity::Error, "workspaces", message);
+                    } else if let Err(message) = check_known(workspace) {
+                        report(unknown_severity, "workspaces", message);
+                    }
+                }
+                match Action::parse(action) {
+                    Ok(Action::MoveToWorkspace(workspace)) => {
+                        if let Err(message) = check_workspace(&workspace) {
+                            report(Severity::Error, "action", message);
+                        } else if let Err(message) = check_known(&workspace) {
+                            report(unknown_severity, "action", message);
+                        }
+                    }
+                    Ok(Action::Capture(command)) => {
+                        if let Err(message) =
+                            check_command(&command).and_then(|()| config.security.check(&command))
+                        {
+                            report(Severity::Error, "action", message);
+                        }
+                    }
+                    Ok(action) => {
+                        if let Err(message) = config.security.check_action(&action) {
+                            report(Severity::Error, "action", message);
+                        }
+                    }
+                    Err(message) => report(Severity::Error, "action", message),
+                }
+            }
+            RuleType::EmptyWorkspace {
+                workspace,
+                command,
+                cwd,
+                env,
+                ..
+            } => {
+                if let Err(message) = check_workspace(workspace) {
+                    report(Severity::Error, "workspace", message);
+                } else if let Err(message) = check_known(workspace) {
+                    report(unknown_severity, "workspace", message);
+                }
+                if let Some(cwd) = cwd.as_deref().filter(|cwd| !expand_home(cwd).is_dir()) {
+                    report(
+                        Severity::Warning,
+                        "cwd",
+                        format!("Directory '{cwd}' doesn't exist"),
+                    );
+                }
+                if let Some(name) = env
+                    .keys()
+                    .find(|name| name.is_empty() || name.contains(['=', '\0']))
+                {
+                    report(
+                        Severity::Error,
+                        "env",
+                        format!("'{name}' is not a valid environment variable name"),
+                    );
+                } else if let Err(message) = config.security.check_env(env) {
+                    report(Severity::Error, "env", message);
+                }
+                if let Err(message) =
+                    check_command(command).and_then(|()| config.security.check(command))
+                {
+                    report(Severity::Error, "command", message);
+                }
+            }
+            RuleType::Startup { command } => {
+                if let Err(message) =
+                    check_command(command).and_then(|()| config.security.check(command))
+                {
+                    report(Severity::Error, "command", message);
+                }
+            }
+            RuleType::Script { script } => {
+                if let Err(message) = check_script(script) {
+                    report(Severity::Error, "script", message);
+                }
+            }
+        }
+
+        for deprecation in compat::deprecations(rule) {
+            report(
+                Severity::Warning,
+                deprecation.field,
+                deprecation.to_string(),
+            );
+        }
+
+        if !rule.enabled {
+            report(Severity::Warning, "enabled", "Rule is disabled".to_string());
+        }
+    }
+
+    diagnostics
+}
+
+fn check_workspace(workspace: &str) -> Result<(), String> {
+    if workspace.is_empty() {
+        Err("Workspace name is empty".to_string())
+    } else if workspace.trim() != workspace {
+        Err(format!(
+            "Workspace name '{workspace}' has surrounding whitespace"
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_command(command: &str) -> Result<(), String> {
+    match shlex::split(command) {
+        Some(parts) if parts.is_empty() => Err("Command is empty".to_string()),
+        Some(_) => Ok(()),
+        None => Err(format!("Failed to parse command: {command}")),
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn check_script(script: &str) -> Result<(), String> {
+    crate::script::check(script)
+}
+
+#[cfg(not(feature = "scripting"))]
+fn check_script(_script: &str) -> Result<(), String> {
+    Err("Script rules need the `scripting` feature".to_string())
+}
+
+pub(crate) fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_config_has_no_diagnostics() {
+        let source = r#"
+[[rules]]
+name = "Slack"
+type = "window"
+condition = "app-name = 'Slack'"
+action = "move-to-workspace 9"
+"#;
+        assert!(validate_config(source).is_empty());
+    }
+
+    #[test]
+    fn test_diagnostics_point_at_field_line() {
+        let source = r#"
+[[rules]]
+name = "Ok"
+type = "window"
+condition = "app-name = 'Slack'"
+action = "maximize"
+
+[[rules]]
+name = "Broken"
+type = "window"
+condition = "color = 'red'"
+action = "explode"
+"#;
+        let diagnostics = validate_config(source);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].field.as_deref(), Some("condition"));
+        assert_eq!(diagnostics[0].line, Some(11));
+        assert_eq!(diagnostics[0].rule.as_deref(), Some("Broken"));
+        assert_eq!(diagnostics[1].field.as_deref(), Some("action"));
+        assert_eq!(diagnostics[1].line, Some(12));
+    }
+
+    #[test]
+    fn test_malformed_lists_are_errors() {
+        let source = r#"
+[[rules]]
+name = "Chat"
+type = "window"
+condition = "app-name in ['Slack', 'Discord'"
+action = "move-to-workspace 9"
+"#;
+        let diagnostics = validate_config(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(
+            diagnostics[0].message,
+            "Unclosed list in condition: app-name in ['Slack', 'Discord'"
+        );
+    }
+
+    #[test]
+    fn test_syntax_error_has_line() {
+        let diagnostics = validate_config("[[rules]]\nname = \n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, Some(2));
+    }
+
+    #[test]
+    fn test_old_spellings_are_warnings() {
+        let source = r#"
+[[rules]]
+name = "Slack"
+type = "window"
+condition = "app-id = 'Slack'"
+action = "move-node-to-workspace 9"
+"#;
+        let diagnostics = validate_config(source);
+        let messages: Vec<_> = diagnostics.iter().map(Diagnostic::describe).collect();
+        assert_eq!(
+            messages,
+            [
+                "rule 'Slack': condition: 'app-id' is deprecated, use 'app-name'",
+                "rule 'Slack': action: 'move-node-to-workspace' is deprecated, use 'move-to-workspace'",
+            ]
+        );
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn test_unknown_workspaces() {
+        let source = r#"
+[[rules]]
+name = "Slack"
+type = "window"
+condition = "app-name = 'Slack'"
+action = "move-to-workspace 12"
+"#;
+        let workspaces = ["1".to_string(), "9".to_string()];
+        assert!(validate_config(source).is_empty());
+
+        let diagnostics = validate_config_with_workspaces(source, &workspaces);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].field.as_deref(), Some("action"));
+
+        let strict = format!("[service]\nunknown_workspaces = \"error\"\n{source}");
+        let diagnostics = validate_config_with_workspaces(&strict, &workspaces);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+}
@@ -0,0 +1,64 @@
+//! Delivery of service events to the `[[webhooks]]` of the config.
+//!
+//! Every webhook receives a JSON POST for the events it selected:
+//!
+//! ```json
+//! {"event": "action-failed", "at": "2025-01-01T12:00:00Z", "details": {"ActionFailed": {...}}}
+//! ```
+//!
+//! `details` holds the [`Event`] as sent to subscribers. Requests are made with `curl`,
+//! which ships with macOS.
+
+use crate::config::WebhookEvent;
+use crate::Event;
+use serde::Serialize;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// How long a webhook may take to answer, in seconds.
+const TIMEOUT_SECS: u32 = 10;
+
+#[derive(Serialize, Debug)]
+pub struct Payload<'a> {
+    pub event: WebhookEvent,
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub details: &'a Event,
+}
+
+impl WebhookEvent {
+    /// The webhook event `event` counts as, if any.
+    pub fn of(event: &Event) -> Option<Self> {
+        match event {
+            Event::RuleFired { .. } => Some(WebhookEvent::RuleFired),
+            Event::ActionFailed { .. } => Some(WebhookEvent::ActionFailed),
+            Event::ConfigReloadFailed { .. } => Some(WebhookEvent::ConfigReloadFailed),
+            _ => None,
+        }
+    }
+}
+
+/// POSTs `payload` to `url`, failing on connection errors and non-2xx responses.
+pub fn post(url: &str, payload: &Payload) -> Result<(), String> {
+    let body = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+    let mut curl = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time"])
+        .arg(TIMEOUT_SECS.to_string())
+        .args(["--header", "Content-Type: application/json"])
+        .args(["--data-binary", "@-", "--output", "/dev/null", "--"])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run curl: {e}"))?;
+
+    if let Some(mut stdin) = curl.stdin.take() {
+        stdin.write_all(&body).map_err(|e| e.to_string())?;
+    }
+    let output = curl.wait_with_output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
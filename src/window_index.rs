@@ -0,0 +1,105 @@
+//! The windows the service knows about, grouped by workspace and indexed by ID.
+
+use crate::WindowInfo;
+use std::collections::HashMap;
+
+/// Windows grouped by workspace, with an index from window ID to workspace, so looking
+/// up the windows of a workspace or a single window doesn't scan all of them.
+#[derive(Debug, Clone, Default)]
+pub struct WindowIndex {
+    /// Workspaces in the order their first window was listed.
+    workspaces: Vec<String>,
+    by_workspace: HashMap<String, Vec<WindowInfo>>,
+    workspace_of: HashMap<u32, String>,
+}
+
+impl WindowIndex {
+    pub fn new(windows: impl IntoIterator<Item = WindowInfo>) -> Self {
+        let mut index = Self::default();
+        for window in windows {
+            index
+                .workspace_of
+                .insert(window.window_id, window.workspace.clone());
+            match index.by_workspace.get_mut(&window.workspace) {
+                Some(windows) => windows.push(window),
+                None => {
+                    index.workspaces.push(window.workspace.clone());
+                    index
+                        .by_workspace
+                        .insert(window.workspace.clone(), vec![window]);
+                }
+            }
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_workspace.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_workspace.is_empty()
+    }
+
+    /// Every window, grouped by workspace, in the order the window manager listed them.
+    pub fn iter(&self) -> impl Iterator<Item = &WindowInfo> {
+        self.workspaces
+            .iter()
+            .flat_map(|workspace| &self.by_workspace[workspace])
+    }
+
+    pub fn in_workspace(&self, workspace: &str) -> &[WindowInfo] {
+        self.by_workspace
+            .get(workspace)
+            .map_or(&[], |windows| windows.as_slice())
+    }
+
+    pub fn get(&self, window_id: u32) -> Option<&WindowInfo> {
+        let workspace = self.workspace_of.get(&window_id)?;
+        self.by_workspace[workspace]
+            .iter()
+            .find(|window| window.window_id == window_id)
+    }
+
+    /// The window with this ID, to update in place. Its workspace mustn't be changed,
+    /// as the window would still be filed under the old one.
+    pub fn get_mut(&mut self, window_id: u32) -> Option<&mut WindowInfo> {
+        let workspace = self.workspace_of.get(&window_id)?;
+        self.by_workspace
+            .get_mut(workspace)?
+            .iter_mut()
+            .find(|window| window.window_id == window_id)
+    }
+
+    pub fn to_vec(&self) -> Vec<WindowInfo> {
+        self.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::window;
+
+    #[test]
+    fn test_windows_are_found_by_workspace_and_id() {
+        let index = WindowIndex::new([
+            window("Slack").id(1).workspace("2").build(),
+            window("Safari").id(2).build(),
+            window("Mail").id(3).workspace("2").build(),
+        ]);
+
+        assert_eq!(index.len(), 3);
+        let ids = |windows: Vec<&WindowInfo>| -> Vec<u32> {
+            windows.iter().map(|window| window.window_id).collect()
+        };
+        assert_eq!(ids(index.iter().collect()), [1, 3, 2]);
+        assert_eq!(ids(index.in_workspace("2").iter().collect()), [1, 3]);
+        assert!(index.in_workspace("9").is_empty());
+        assert_eq!(
+            index.get(2).map(|window| window.app_name.as_str()),
+            Some("Safari")
+        );
+        assert!(index.get(4).is_none());
+    }
+}
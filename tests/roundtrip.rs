@@ -0,0 +1,369 @@
+//! Property tests that configs and conditions survive being written out and read back,
+//! so additions to the config format can't silently drop or change settings.
+
+use aerospace_rules::condition::{Condition, Field, Glob, Operator, Pattern, Value};
+use aerospace_rules::config::{
+    BackendKind, ConditionValue, Config, DisplayProfile, HooksConfig, OnError, Rule, RuleCondition,
+    RuleLog, RuleType, SecurityConfig, ServiceConfig, Webhook, WebhookEvent, WorkspaceConfig,
+};
+use aerospace_rules::validate::Severity;
+use proptest::prelude::*;
+
+fn text() -> impl Strategy<Value = String> {
+    "\\PC{0,24}"
+}
+
+fn option_text() -> impl Strategy<Value = Option<String>> {
+    proptest::option::of(text())
+}
+
+fn service() -> impl Strategy<Value = ServiceConfig> {
+    (
+        proptest::option::of(prop_oneof![
+            Just(BackendKind::Aerospace),
+            Just(BackendKind::Sway),
+            Just(BackendKind::I3),
+            Just(BackendKind::Komorebi),
+        ]),
+        option_text(),
+        proptest::option::of(0..u32::MAX as u64),
+        proptest::option::of(any::<u32>()),
+        proptest::option::of(any::<u16>()),
+        option_text(),
+        proptest::option::of(0..64usize),
+        proptest::option::of(any::<u32>().prop_map(u64::from)),
+        option_text(),
+        proptest::option::of(prop_oneof![Just(Severity::Error), Just(Severity::Warning)]),
+        proptest::option::of(any::<u32>().prop_map(u64::from)),
+    )
+        .prop_map(
+            |(
+                backend,
+                aerospace_bin,
+                query_cache_ttl_ms,
+                rate_alarm_per_minute,
+                metrics_port,
+                metrics_textfile,
+                parallel_actions,
+                action_timeout_ms,
+                rule_hits_file,
+                unknown_workspaces,
+                title_settle_ms,
+            )| {
+                ServiceConfig {
+                    backend,
+                    aerospace_bin,
+                    query_cache_ttl_ms,
+                    rate_alarm_per_minute,
+                    metrics_port,
+                    metrics_textfile,
+                    parallel_actions,
+                    action_timeout_ms,
+                    rule_hits_file,
+                    unknown_workspaces,
+                    title_settle_ms,
+                }
+            },
+        )
+}
+
+fn security() -> impl Strategy<Value = SecurityConfig> {
+    (
+        proptest::option::of(proptest::collection::vec(text(), 0..3)),
+        any::<bool>(),
+    )
+        .prop_map(
+            |(allowed_commands, refuse_shell_metacharacters)| SecurityConfig {
+                allowed_commands,
+                refuse_shell_metacharacters,
+            },
+        )
+}
+
+fn webhook() -> impl Strategy<Value = Webhook> {
+    (
+        text(),
+        proptest::sample::subsequence(
+            vec![
+                WebhookEvent::RuleFired,
+                WebhookEvent::ActionFailed,
+                WebhookEvent::ConfigReloadFailed,
+            ],
+            0..=3,
+        ),
+    )
+        .prop_map(|(url, events)| Webhook { url, events })
+}
+
+fn workspace() -> impl Strategy<Value = WorkspaceConfig> {
+    (proptest::collection::vec(text(), 0..3), option_text())
+        .prop_map(|(apps, layout)| WorkspaceConfig { apps, layout })
+}
+
+fn display_profile() -> impl Strategy<Value = DisplayProfile> {
+    (
+        0..4usize,
+        proptest::collection::btree_map(text(), text(), 0..3),
+    )
+        .prop_map(|(monitors, workspaces)| DisplayProfile {
+            monitors,
+            workspaces,
+        })
+}
+
+fn rule_type() -> impl Strategy<Value = RuleType> {
+    prop_oneof![
+        (
+            prop_oneof![
+                text().prop_map(RuleCondition::Text),
+                condition().prop_map(|condition| as_table(&condition))
+            ],
+            text(),
+            proptest::collection::vec(text(), 0..3)
+        )
+            .prop_map(|(condition, action, workspaces)| RuleType::Window {
+                condition,
+                action,
+                workspaces,
+            }),
+        (
+            text(),
+            text(),
+            option_text(),
+            proptest::collection::btree_map(text(), text(), 0..3),
+            proptest::option::of(any::<bool>()),
+        )
+            .prop_map(
+                |(workspace, command, cwd, env, detach)| RuleType::EmptyWorkspace {
+                    workspace,
+                    command,
+                    cwd,
+                    env,
+                    detach,
+                }
+            ),
+        text().prop_map(|command| RuleType::Startup { command }),
+        text().prop_map(|script| RuleType::Script { script }),
+    ]
+}
+
+fn rule() -> impl Strategy<Value = Rule> {
+    (
+        text(),
+        any::<bool>(),
+        proptest::collection::vec(text(), 0..3),
+        proptest::option::of(any::<u32>().prop_map(u64::from)),
+        prop_oneof![
+            Just(RuleLog::Info),
+            Just(RuleLog::Debug),
+            Just(RuleLog::Trace)
+        ],
+        any::<bool>(),
+        prop_oneof![
+            Just(OnError::Continue),
+            Just(OnError::Abort),
+            Just(OnError::Rollback)
+        ],
+        any::<bool>(),
+        rule_type(),
+    )
+        .prop_map(
+            |(name, enabled, tags, timeout_ms, log, trace, on_error, follow, rule_type)| Rule {
+                name,
+                enabled,
+                tags,
+                timeout_ms,
+                log,
+                trace,
+                on_error,
+                follow,
+                rule_type,
+                compiled: None,
+            },
+        )
+}
+
+fn config() -> impl Strategy<Value = Config> {
+    (
+        service(),
+        (option_text(), option_text(), security()),
+        proptest::collection::vec(webhook(), 0..3),
+        (
+            proptest::collection::btree_map(text(), workspace(), 0..3),
+            proptest::collection::btree_map(text(), display_profile(), 0..3),
+        ),
+        proptest::collection::vec(rule(), 0..6),
+    )
+        .prop_map(
+            |(
+                service,
+                (before_action, after_action, security),
+                webhooks,
+                (workspaces, display_profiles),
+                rules,
+            )| Config {
+                service,
+                hooks: HooksConfig {
+                    before_action,
+                    after_action,
+                },
+                security,
+                webhooks,
+                workspaces,
+                display_profiles,
+                rules,
+            },
+        )
+}
+
+/// Comparisons the DSL can express: string values can't contain quotes or `=`.
+fn comparison() -> impl Strategy<Value = Condition> {
+    let string_field = prop_oneof![
+        Just(Field::AppName),
+        Just(Field::WindowTitle),
+        Just(Field::Workspace),
+        Just(Field::Monitor),
+        Just(Field::Captured)
+    ];
+    let numeric_field = prop_oneof![
+        Just(Field::WindowId),
+        Just(Field::WindowWidth),
+        Just(Field::WindowHeight),
+        Just(Field::WindowX),
+        Just(Field::WindowY),
+        Just(Field::WorkspaceWindowCount)
+    ];
+    prop_oneof![
+        (
+            string_field.clone(),
+            prop_oneof![
+                Just(Operator::Equals),
+                Just(Operator::NotEquals),
+                Just(Operator::Contains),
+                Just(Operator::StartsWith),
+                Just(Operator::EndsWith)
+            ],
+            "[A-Za-z0-9 ._-]{0,20}"
+        )
+            .prop_map(|(field, op, value)| Condition::Compare {
+                field,
+                op,
+                value: Value::String(value),
+            }),
+        (
+            numeric_field,
+            prop_oneof![
+                Just(Operator::Equals),
+                Just(Operator::NotEquals),
+                Just(Operator::GreaterThan),
+                Just(Operator::LessThan),
+                Just(Operator::AtLeast),
+                Just(Operator::AtMost)
+            ],
+            any::<i64>()
+        )
+            .prop_map(|(field, op, value)| Condition::Compare {
+                field,
+                op,
+                value: Value::Number(value),
+            }),
+        (
+            prop_oneof![Just(Operator::Equals), Just(Operator::NotEquals)],
+            any::<bool>()
+        )
+            .prop_map(|(op, value)| Condition::Compare {
+                field: Field::IsFocused,
+                op,
+                value: Value::String(value.to_string()),
+            }),
+        (string_field.clone(), "[A-Za-z0-9 ._-]{0,20}").prop_map(|(field, pattern)| {
+            Condition::Compare {
+                field,
+                op: Operator::Matches,
+                value: Value::Pattern(Pattern::new(&pattern).unwrap()),
+            }
+        }),
+        (string_field.clone(), "[A-Za-z0-9 ._*?-]{0,20}").prop_map(|(field, glob)| {
+            Condition::Compare {
+                field,
+                op: Operator::Like,
+                value: Value::Glob(Glob::new(&glob)),
+            }
+        }),
+        (
+            string_field.clone(),
+            proptest::collection::vec("[A-Za-z0-9 ._-]{0,10}", 1..4)
+        )
+            .prop_map(|(field, values)| Condition::Compare {
+                field,
+                op: Operator::In,
+                value: Value::List(values),
+            }),
+    ]
+}
+
+/// Comparisons combined with AND, OR and NOT, nested a few levels deep.
+fn condition() -> impl Strategy<Value = Condition> {
+    comparison().prop_recursive(3, 16, 3, |inner| {
+        prop_oneof![
+            proptest::collection::vec(inner.clone(), 2..4).prop_map(Condition::And),
+            proptest::collection::vec(inner.clone(), 2..4).prop_map(Condition::Or),
+            inner.prop_map(|condition| Condition::Not(Box::new(condition))),
+        ]
+    })
+}
+
+/// `condition` written as TOML tables rather than in the DSL.
+fn as_table(condition: &Condition) -> RuleCondition {
+    match condition {
+        Condition::Compare { field, op, value } => RuleCondition::Compare {
+            field: *field,
+            op: *op,
+            value: match value {
+                Value::String(value) => ConditionValue::Text(value.clone()),
+                Value::Number(value) => ConditionValue::Number(*value),
+                Value::Pattern(pattern) => ConditionValue::Text(pattern.as_str().to_string()),
+                Value::Glob(glob) => ConditionValue::Text(glob.as_str().to_string()),
+                Value::List(values) => ConditionValue::List(values.clone()),
+            },
+        },
+        Condition::And(conditions) => RuleCondition::AllOf {
+            all_of: conditions.iter().map(as_table).collect(),
+        },
+        Condition::Or(conditions) => RuleCondition::AnyOf {
+            any_of: conditions.iter().map(as_table).collect(),
+        },
+        Condition::Not(condition) => RuleCondition::Not {
+            not: Box::new(as_table(condition)),
+        },
+    }
+}
+
+proptest! {
+    #[test]
+    fn config_survives_toml_round_trip(config in config()) {
+        let written = toml::to_string(&config).unwrap();
+        let read: Config = toml::from_str(&written).unwrap();
+        prop_assert_eq!(toml::to_string(&read).unwrap(), written);
+    }
+
+    #[test]
+    fn config_survives_json_round_trip(config in config()) {
+        let written = serde_json::to_string(&config).unwrap();
+        let read: Config = serde_json::from_str(&written).unwrap();
+        prop_assert_eq!(serde_json::to_string(&read).unwrap(), written);
+    }
+
+    #[test]
+    fn toml_condition_reads_like_the_dsl(condition in condition()) {
+        let table = as_table(&condition);
+        prop_assert_eq!(table.to_condition().unwrap(), condition.clone());
+        prop_assert_eq!(table.to_string(), condition.to_string());
+    }
+
+    #[test]
+    fn printed_condition_parses_to_itself(condition in condition()) {
+        let printed = condition.to_string();
+        prop_assert_eq!(Condition::parse(&printed).unwrap(), condition);
+    }
+}
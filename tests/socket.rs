@@ -0,0 +1,525 @@
+//! Runs the service in-process against a fake window manager and talks to it over its
+//! socket, like the CLI does.
+
+use aerospace_rules::client::{Client, ServiceError};
+use aerospace_rules::service::{Service, ServiceOptions};
+use aerospace_rules::testing::{window, Call, FakeWindowManager};
+use aerospace_rules::{ErrorKind, Event, Request, Response, WindowChange};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+const CONFIG: &str = r#"
+[[rules]]
+name = "Move Slack"
+type = "window"
+condition = "app-name = 'Slack'"
+action = "move-to-workspace 9"
+"#;
+
+struct Harness {
+    client: Client,
+    wm: Arc<FakeWindowManager>,
+    // Removed on drop, together with the socket and config in it
+    _dir: TempDir,
+}
+
+async fn start() -> Harness {
+    start_with(CONFIG).await
+}
+
+async fn start_with(config: &str) -> Harness {
+    let dir = TempDir::new().unwrap();
+    let config_path = dir.path().join("rules.toml");
+    std::fs::write(&config_path, config).unwrap();
+    let wm = Arc::new(FakeWindowManager::new([
+        window("Slack").id(1),
+        window("Safari").id(2),
+    ]));
+
+    let service = Service::bind(ServiceOptions {
+        config_path: Some(config_path.display().to_string()),
+        socket_path: dir.path().join("service.sock"),
+        pid_path: None,
+        backend: Some(wm.clone()),
+        refresh_interval: Duration::from_secs(60),
+    })
+    .await
+    .unwrap();
+    tokio::spawn(service.run());
+
+    Harness {
+        client: Client::with_socket(dir.path().join("service.sock")),
+        wm,
+        _dir: dir,
+    }
+}
+
+#[tokio::test]
+async fn test_evaluate_moves_matching_windows() {
+    let harness = start().await;
+    assert_eq!(harness.client.get_windows().await.unwrap().len(), 2);
+
+    let results = harness.client.evaluate("1").await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(results[0].success);
+    assert_eq!(
+        harness.wm.calls(),
+        [Call::Move {
+            window_id: 1,
+            workspace: "9".to_string()
+        }]
+    );
+
+    let rules = harness.client.get_rules().await.unwrap();
+    assert_eq!(rules[0].hits, 1);
+}
+
+#[tokio::test]
+async fn test_ping_reports_the_age_of_the_window_state() {
+    let harness = start().await;
+    let before = chrono::Utc::now();
+    let pong = harness.client.ping().await.unwrap();
+    assert!(pong.at >= before);
+    let age = pong.windows_age_ms.expect("windows are listed at startup");
+    assert!(age < 60_000);
+}
+
+#[tokio::test]
+async fn test_answers_while_actions_run() {
+    let harness = start_with(&format!(
+        "{CONFIG}\n[[rules]]\nname = \"Slow\"\ntype = \"empty-workspace\"\nworkspace = \"5\"\ncommand = \"sleep 2\"\ndetach = false\n"
+    ))
+    .await;
+
+    let started = std::time::Instant::now();
+    let (evaluated, pong) = tokio::join!(harness.client.evaluate("5"), async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        harness.client.ping().await.unwrap();
+        started.elapsed()
+    });
+    assert_eq!(evaluated.unwrap().len(), 1);
+    assert!(pong < Duration::from_secs(1), "ping took {pong:?}");
+}
+
+#[tokio::test]
+async fn test_rule_hits_survive_restarts() {
+    let dir = TempDir::new().unwrap();
+    let hits_file = dir.path().join("hits.json");
+    let config = format!(
+        "[service]\nrule_hits_file = '{}'\n{CONFIG}",
+        hits_file.display()
+    );
+
+    let harness = start_with(&config).await;
+    harness.client.evaluate("1").await.unwrap();
+    let rules = harness.client.get_rules().await.unwrap();
+    assert_eq!((rules[0].hits, rules[0].executed), (1, 1));
+    assert!(rules[0].last_hit.is_some());
+
+    let restarted = start_with(&config).await;
+    let rules = restarted.client.get_rules().await.unwrap();
+    assert_eq!(rules[0].hits, 1);
+}
+
+#[tokio::test]
+async fn test_evaluate_window_only_acts_on_that_window() {
+    let harness = start().await;
+    harness
+        .wm
+        .set_windows([window("Slack").id(1), window("Slack").id(3)]);
+
+    let evaluate = Request::EvaluateWindow {
+        window_id: 3,
+        dry_run: false,
+    };
+    let response = harness.client.request(&evaluate).await.unwrap();
+    let Response::RulesEvaluated { results, .. } = response else {
+        panic!("unexpected response: {response:?}");
+    };
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        harness.wm.calls(),
+        [Call::Move {
+            window_id: 3,
+            workspace: "9".to_string()
+        }]
+    );
+    // The window opened after the last refresh, yet the service knows it now
+    let windows = harness.client.get_windows().await.unwrap();
+    assert!(windows.iter().any(|window| window.window_id == 3));
+}
+
+#[tokio::test]
+async fn test_paused_service_only_dry_runs() {
+    let harness = start().await;
+    let response = harness
+        .client
+        .request(&Request::SetPaused { paused: true })
+        .await
+        .unwrap();
+    assert!(matches!(response, Response::Paused(true)));
+
+    assert!(harness.client.evaluate("1").await.unwrap().is_empty());
+    let response = harness
+        .client
+        .request(&Request::EvaluateRules {
+            workspace: "1".to_string(),
+            dry_run: true,
+            changed_only: false,
+        })
+        .await
+        .unwrap();
+    assert!(matches!(response, Response::RulesEvaluated { results, .. } if results.len() == 1));
+    assert!(harness.wm.calls().is_empty());
+}
+
+#[tokio::test]
+async fn test_errors_carry_their_kind() {
+    let harness = start().await;
+    let response = harness
+        .client
+        .request(&Request::RemoveRule {
+            name: "Missing".to_string(),
+        })
+        .await
+        .unwrap();
+    assert!(matches!(
+        response,
+        Response::Error {
+            kind: ErrorKind::InvalidRequest,
+            ..
+        }
+    ));
+
+    let unreachable = Client::with_socket("/nonexistent/service.sock")
+        .get_windows()
+        .await;
+    assert!(unreachable.is_err_and(|e| !e.is::<ServiceError>()));
+}
+
+#[tokio::test]
+async fn test_subscribers_hear_about_fired_rules() {
+    let harness = start().await;
+    let mut subscription = harness.client.subscribe().await.unwrap();
+    harness.client.evaluate("1").await.unwrap();
+
+    let fired = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match subscription.next_event().await.unwrap() {
+                Some(Event::RuleFired { result }) => return result,
+                Some(_) => continue,
+                None => panic!("service closed the subscription"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(fired.rule, "Move Slack");
+}
+
+#[tokio::test]
+async fn test_subscribers_hear_about_failed_actions_and_focus_changes() {
+    let harness = start().await;
+    let mut subscription = harness.client.subscribe().await.unwrap();
+    harness.wm.fail_window(1, "window is gone");
+    let evaluate = Request::EvaluateRules {
+        workspace: "1".to_string(),
+        dry_run: false,
+        changed_only: false,
+    };
+    let response = harness.client.request(&evaluate).await.unwrap();
+    assert!(matches!(
+        response,
+        Response::RulesEvaluated {
+            partial_failure: true,
+            ..
+        }
+    ));
+    let failed = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match subscription.next_event().await.unwrap() {
+                Some(Event::ActionFailed { result }) => return result,
+                Some(Event::RuleFired { .. }) => panic!("failed action announced as fired"),
+                Some(_) => continue,
+                None => panic!("service closed the subscription"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(failed.error.as_deref(), Some("window is gone"));
+
+    harness.wm.set_focused_workspace(Some("3"));
+    harness.client.evaluate("3").await.unwrap();
+    let focused = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match subscription.next_event().await.unwrap() {
+                Some(Event::WorkspaceFocused { workspace, .. }) => return workspace,
+                Some(_) => continue,
+                None => panic!("service closed the subscription"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(focused, "3");
+}
+
+#[tokio::test]
+async fn test_rule_firing_too_often_raises_an_alarm() {
+    let harness = start_with(&format!("[service]\nrate_alarm_per_minute = 2\n{CONFIG}")).await;
+    let mut subscription = harness.client.subscribe().await.unwrap();
+    for _ in 0..3 {
+        let evaluate = Request::EvaluateWindow {
+            window_id: 1,
+            dry_run: false,
+        };
+        harness.client.request(&evaluate).await.unwrap();
+    }
+
+    let alarm = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match subscription.next_event().await.unwrap() {
+                Some(Event::RuleRateExceeded { rule, per_minute }) => return (rule, per_minute),
+                Some(_) => continue,
+                None => panic!("service closed the subscription"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(alarm, ("Move Slack".to_string(), 3));
+    assert!(harness.client.get_rules().await.unwrap()[0].rate_alarm);
+}
+
+#[tokio::test]
+async fn test_docking_applies_the_display_profile() {
+    let harness = start_with(&format!(
+        "[display_profiles.docked]\nmonitors = 2\nworkspaces = {{ \"9\" = \"secondary\" }}\n{CONFIG}"
+    ))
+    .await;
+    harness
+        .wm
+        .set_monitors(&["Built-in Retina Display", "LG Ultrawide"]);
+    harness.client.request(&Request::Reload).await.unwrap();
+
+    assert_eq!(
+        harness.wm.calls(),
+        [Call::WorkspaceToMonitor {
+            workspace: "9".to_string(),
+            monitor: "secondary".to_string()
+        }]
+    );
+    // Only a change of monitors moves workspaces again
+    harness.client.request(&Request::Reload).await.unwrap();
+    assert_eq!(harness.wm.calls().len(), 1);
+}
+
+#[tokio::test]
+async fn test_report_summarizes_rule_actions_and_placements() {
+    let harness = start().await;
+    harness.client.evaluate("1").await.unwrap();
+
+    let response = harness.client.request(&Request::GetReport).await.unwrap();
+    let Response::Report(report) = response else {
+        panic!("unexpected response: {response:?}");
+    };
+    assert_eq!(report.rules.len(), 1);
+    assert_eq!(report.rules[0].rule, "Move Slack");
+    assert_eq!((report.rules[0].actions, report.rules[0].failed), (1, 0));
+    assert!(report
+        .placements
+        .iter()
+        .any(|placement| placement.app_name == "Safari"));
+}
+
+#[tokio::test]
+async fn test_changed_only_evaluation_skips_windows_already_evaluated() {
+    let harness = start().await;
+    let evaluate = Request::EvaluateRules {
+        workspace: "1".to_string(),
+        dry_run: false,
+        changed_only: true,
+    };
+    harness.client.request(&evaluate).await.unwrap();
+    assert_eq!(harness.wm.calls().len(), 1);
+
+    // Slack was already evaluated on this workspace, only the new window is
+    harness
+        .wm
+        .set_windows([window("Slack").id(1), window("Slack").id(3)]);
+    harness.client.request(&Request::Reload).await.unwrap();
+    let response = harness.client.request(&evaluate).await.unwrap();
+    let Response::RulesEvaluated { results, .. } = response else {
+        panic!("unexpected response: {response:?}");
+    };
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].window_id, Some(3));
+}
+
+#[tokio::test]
+async fn test_windows_are_tracked_across_moves() {
+    let harness = start_with(&format!(
+        "{CONFIG}\n[[rules]]\nname = \"Maximize moved Slack\"\ntype = \"window\"\ncondition = \"previously-matched = 'Move Slack'\"\naction = \"maximize\"\n"
+    ))
+    .await;
+    harness.client.evaluate("1").await.unwrap();
+    harness.client.request(&Request::Reload).await.unwrap();
+    harness.client.evaluate("9").await.unwrap();
+    assert!(harness
+        .wm
+        .calls()
+        .contains(&Call::Fullscreen { window_id: 1 }));
+
+    let response = harness
+        .client
+        .request(&Request::ExplainWindow {
+            window_id: Some(1),
+            app_name: None,
+        })
+        .await
+        .unwrap();
+    let Response::Explained(explanations) = response else {
+        panic!("unexpected response: {response:?}");
+    };
+    let record = explanations[0].record.as_ref().unwrap();
+    assert_eq!(record.workspaces, ["1", "9"]);
+    assert!(record.matched_rules.contains("Maximize moved Slack"));
+}
+
+#[tokio::test]
+async fn test_windows_since_returns_only_changes() {
+    let harness = start().await;
+    let since = |seq| {
+        let client = &harness.client;
+        async move { client.request(&Request::GetWindowsSince { seq }).await }
+    };
+    let Response::WindowsSince { seq, changes, .. } = since(0).await.unwrap() else {
+        panic!("unexpected response");
+    };
+    assert_eq!(changes.len(), 2);
+
+    harness
+        .wm
+        .set_windows([window("Slack").id(1).title("general")]);
+    harness.client.request(&Request::Reload).await.unwrap();
+    let response = since(seq).await.unwrap();
+    let Response::WindowsSince {
+        seq: next,
+        changes,
+        reset,
+        ..
+    } = response
+    else {
+        panic!("unexpected response: {response:?}");
+    };
+    assert!(!reset);
+    assert_eq!(next, seq + 1);
+    assert_eq!(
+        changes,
+        [
+            WindowChange::Updated(window("Slack").id(1).title("general").build()),
+            WindowChange::Closed(2),
+        ]
+    );
+
+    let response = since(next).await.unwrap();
+    assert!(matches!(response, Response::WindowsSince { changes, .. } if changes.is_empty()));
+    // A sequence number the service never handed out starts over
+    let response = since(next + 10).await.unwrap();
+    assert!(matches!(
+        response,
+        Response::WindowsSince { reset: true, .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_subscribers_hear_what_is_wrong_with_a_reloaded_config() {
+    let harness = start_with(&format!(
+        "{CONFIG}\n[[rules]]\nname = \"Later\"\ntype = \"window\"\nenabled = false\ncondition = \"app-name = 'Mail'\"\naction = \"maximize\"\n"
+    ))
+    .await;
+    let mut subscription = harness.client.subscribe().await.unwrap();
+    let reloaded = tokio::time::timeout(Duration::from_secs(5), async {
+        // Once a rule firing arrives the subscription is live
+        harness.client.evaluate("1").await.unwrap();
+        loop {
+            match subscription.next_event().await.unwrap() {
+                Some(Event::RuleFired { .. }) => break,
+                Some(_) => continue,
+                None => panic!("service closed the subscription"),
+            }
+        }
+        harness.client.request(&Request::Reload).await.unwrap();
+        loop {
+            match subscription.next_event().await.unwrap() {
+                Some(Event::ConfigReloaded {
+                    rules,
+                    warnings,
+                    errors,
+                }) => return (rules, warnings, errors),
+                Some(_) => continue,
+                None => panic!("service closed the subscription"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    let (rules, warnings, errors) = reloaded;
+    assert_eq!(rules, 2);
+    assert_eq!(warnings, ["rule 'Later': enabled: Rule is disabled"]);
+    assert!(errors.is_empty());
+}
+
+#[tokio::test]
+async fn test_reload_reports_invalid_regexes() {
+    let harness = start_with(&format!(
+        "{CONFIG}\n[[rules]]\nname = \"PDFs\"\ntype = \"window\"\ncondition = \"window-title =~ '(pdf'\"\naction = \"maximize\"\n"
+    ))
+    .await;
+    let response = harness.client.request(&Request::Reload).await.unwrap();
+    let Response::Error { kind, message } = response else {
+        panic!("unexpected response: {response:?}");
+    };
+    assert_eq!(kind, ErrorKind::InvalidConfig);
+    assert!(message.starts_with("Rule 'PDFs' (line 11) has an invalid condition: Invalid regex"));
+}
+
+#[tokio::test]
+async fn test_restores_sessions_larger_than_one_read() {
+    let harness = start().await;
+    let title = "~/src/aerospace-rules — cargo test --workspace -- --nocapture".repeat(2);
+    let mut saved = vec![
+        window("Slack").id(1).workspace("3").build(),
+        window("Safari").id(2).workspace("4").build(),
+    ];
+    saved.extend((10..30).map(|id| {
+        window("Ghostty")
+            .id(id)
+            .title(&title)
+            .workspace("5")
+            .build()
+    }));
+    let session = aerospace_rules::session::Session::capture(&saved);
+    let restore = Request::RestoreSession {
+        name: "work".to_string(),
+        session,
+        dry_run: false,
+    };
+    assert!(serde_json::to_string(&restore).unwrap().len() > 4096);
+
+    let response = harness.client.request(&restore).await.unwrap();
+    let Response::RulesEvaluated { results, .. } = response else {
+        panic!("unexpected response: {response:?}");
+    };
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(|result| result.success));
+    let workspaces: Vec<_> = harness
+        .wm
+        .windows()
+        .into_iter()
+        .map(|window| window.workspace)
+        .collect();
+    assert_eq!(workspaces, ["3", "4"]);
+}